@@ -361,7 +361,7 @@ fn all_perm_scheduling_benchmark(c: &mut Criterion) {
             max_available_effect,
         ));
 
-        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm {};
+        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm::new();
 
         b.iter(|| {
             all_perm_scheduler_algorithm.schedule(&mut discrete_graph.clone(), tasks.clone())
@@ -390,7 +390,7 @@ fn all_perm_scheduling_benchmark(c: &mut Criterion) {
             max_available_effect,
         ));
 
-        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm {};
+        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm::new();
 
         b.iter(|| {
             all_perm_scheduler_algorithm.schedule(&mut discrete_graph.clone(), tasks.clone())
@@ -419,7 +419,7 @@ fn all_perm_scheduling_benchmark(c: &mut Criterion) {
             max_available_effect,
         ));
 
-        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm {};
+        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm::new();
 
         b.iter(|| {
             all_perm_scheduler_algorithm.schedule(&mut discrete_graph.clone(), tasks.clone())
@@ -448,7 +448,7 @@ fn all_perm_scheduling_benchmark(c: &mut Criterion) {
             max_available_effect,
         ));
 
-        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm {};
+        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm::new();
 
         b.iter(|| {
             all_perm_scheduler_algorithm.schedule(&mut discrete_graph.clone(), tasks.clone())
@@ -477,7 +477,7 @@ fn all_perm_scheduling_benchmark(c: &mut Criterion) {
             max_available_effect,
         ));
 
-        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm {};
+        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm::new();
 
         b.iter(|| {
             all_perm_scheduler_algorithm.schedule(&mut discrete_graph.clone(), tasks.clone())
@@ -506,7 +506,7 @@ fn all_perm_scheduling_benchmark(c: &mut Criterion) {
             max_available_effect,
         ));
 
-        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm {};
+        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm::new();
 
         b.iter(|| {
             all_perm_scheduler_algorithm.schedule(&mut discrete_graph.clone(), tasks.clone())
@@ -535,7 +535,7 @@ fn all_perm_scheduling_benchmark(c: &mut Criterion) {
             max_available_effect,
         ));
 
-        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm {};
+        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm::new();
 
         b.iter(|| {
             all_perm_scheduler_algorithm.schedule(&mut discrete_graph.clone(), tasks.clone())
@@ -570,6 +570,64 @@ fn all_perm_scheduling_benchmark(c: &mut Criterion) {
             all_perm_scheduler_algorithm.schedule(&mut discrete_graph.clone(), tasks.clone())
         });
     });
+
+    c.bench_function("all_perm_scheduling_benchmark, 10 tasks", |b| {
+        let amount_of_tasks = 10;
+        let max_effect = 10000.0;
+        let time_now = Utc::now();
+        let total_duration = Duration::hours(24);
+        let min_available_effect = 1000;
+        let max_available_effect = 1000000000;
+
+        let tasks = criterion::black_box(TaskFactory::new().make_tasks(
+            amount_of_tasks,
+            time_now,
+            max_effect,
+            total_duration,
+        ));
+        let discrete_graph = criterion::black_box(make_discrete_graph_from_delta(
+            time_now,
+            Duration::minutes(1),
+            total_duration,
+            min_available_effect,
+            max_available_effect,
+        ));
+
+        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm::new();
+
+        b.iter(|| {
+            all_perm_scheduler_algorithm.schedule(&mut discrete_graph.clone(), tasks.clone())
+        });
+    });
+
+    c.bench_function("all_perm_scheduling_benchmark, 12 tasks", |b| {
+        let amount_of_tasks = 12;
+        let max_effect = 10000.0;
+        let time_now = Utc::now();
+        let total_duration = Duration::hours(24);
+        let min_available_effect = 1000;
+        let max_available_effect = 1000000000;
+
+        let tasks = criterion::black_box(TaskFactory::new().make_tasks(
+            amount_of_tasks,
+            time_now,
+            max_effect,
+            total_duration,
+        ));
+        let discrete_graph = criterion::black_box(make_discrete_graph_from_delta(
+            time_now,
+            Duration::minutes(1),
+            total_duration,
+            min_available_effect,
+            max_available_effect,
+        ));
+
+        let all_perm_scheduler_algorithm = AllPermutationsAlgorithm::new();
+
+        b.iter(|| {
+            all_perm_scheduler_algorithm.schedule(&mut discrete_graph.clone(), tasks.clone())
+        });
+    });
 }
 
 criterion_group!(
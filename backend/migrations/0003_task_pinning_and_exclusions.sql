@@ -0,0 +1,9 @@
+ALTER TABLE Tasks ADD COLUMN pinned_start DATETIME;
+
+CREATE TABLE TaskExclusions(
+  id INTEGER PRIMARY KEY NOT NULL,
+  task_id INTEGER NOT NULL
+    REFERENCES Tasks(id) ON DELETE CASCADE,
+  timespan_start DATETIME NOT NULL,
+  timespan_end   DATETIME NOT NULL
+);
@@ -0,0 +1,26 @@
+CREATE TABLE SchedulingRuns(
+  id INTEGER PRIMARY KEY NOT NULL,
+  -- The protocol Algorithm and DiscreteGraph given to the scheduler, as JSON
+  algorithm   TEXT NOT NULL,
+  input_graph TEXT NOT NULL,
+  task_count  INTEGER NOT NULL,
+  -- NULL if the run failed
+  cost        REAL,
+  started_at  DATETIME NOT NULL,
+  duration    INTEGER NOT NULL,
+  error       TEXT
+);
+
+CREATE TABLE EventHistory(
+  id INTEGER PRIMARY KEY NOT NULL,
+  task_id INTEGER NOT NULL
+    REFERENCES Tasks(id) ON DELETE CASCADE,
+  start_time DATETIME NOT NULL,
+  -- NULL if the event was moved by the user, e.g. by pinning the task
+  scheduling_run_id INTEGER
+    REFERENCES SchedulingRuns(id) ON DELETE SET NULL,
+  changed_at DATETIME NOT NULL
+);
+
+CREATE INDEX EventHistoryTaskId ON EventHistory(task_id);
+CREATE INDEX EventHistorySchedulingRunId ON EventHistory(scheduling_run_id);
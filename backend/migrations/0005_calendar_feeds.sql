@@ -0,0 +1,9 @@
+CREATE TABLE CalendarFeeds(
+  -- The secret in the feed url
+  id VARCHAR(64) PRIMARY KEY NOT NULL,
+  account_id INTEGER NOT NULL
+    REFERENCES Accounts(id) ON DELETE CASCADE,
+  -- NULL if the feed contains the events of all the account's devices
+  device_id INTEGER
+    REFERENCES Devices(id) ON DELETE CASCADE
+);
@@ -0,0 +1,10 @@
+CREATE TABLE CarbonIntensity(
+  id INTEGER PRIMARY KEY NOT NULL,
+  -- The protocol DiscreteGraph of gCO2/kWh per timeslot, as JSON
+  series     TEXT NOT NULL,
+  created_at DATETIME NOT NULL
+);
+
+-- The estimated grams of CO2 of the energy imported from the grid,
+-- NULL if no carbon intensity was known or the run failed
+ALTER TABLE SchedulingRuns ADD COLUMN emissions REAL;
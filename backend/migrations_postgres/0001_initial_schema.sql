@@ -0,0 +1,29 @@
+CREATE TABLE Accounts(
+  id            BIGSERIAL PRIMARY KEY,
+  username      VARCHAR(255) NOT NULL,
+  password_hash VARCHAR(255) NOT NULL,
+  UNIQUE(username)
+);
+
+CREATE TABLE AuthTokens(
+  id         UUID PRIMARY KEY,
+  account_id BIGINT NOT NULL
+    REFERENCES Accounts(id) ON DELETE CASCADE
+);
+
+CREATE TABLE Devices(
+  id         BIGSERIAL PRIMARY KEY,
+  name       VARCHAR(255) NOT NULL,
+  effect     DOUBLE PRECISION NOT NULL,
+  account_id BIGINT NOT NULL
+    REFERENCES Accounts(id) ON DELETE CASCADE
+);
+
+CREATE TABLE Tasks(
+  id             BIGSERIAL PRIMARY KEY,
+  timespan_start TIMESTAMPTZ NOT NULL,
+  timespan_end   TIMESTAMPTZ NOT NULL,
+  duration       BIGINT NOT NULL,
+  device_id      BIGINT NOT NULL
+    REFERENCES Devices(id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+CREATE TABLE Events(
+  id         BIGSERIAL PRIMARY KEY,
+  task_id    BIGINT NOT NULL
+    REFERENCES Tasks(id) ON DELETE CASCADE,
+  start_time TIMESTAMPTZ NOT NULL,
+  UNIQUE(task_id)
+);
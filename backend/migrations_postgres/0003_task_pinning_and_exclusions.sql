@@ -0,0 +1,9 @@
+ALTER TABLE Tasks ADD COLUMN pinned_start TIMESTAMPTZ;
+
+CREATE TABLE TaskExclusions(
+  id             BIGSERIAL PRIMARY KEY,
+  task_id        BIGINT NOT NULL
+    REFERENCES Tasks(id) ON DELETE CASCADE,
+  timespan_start TIMESTAMPTZ NOT NULL,
+  timespan_end   TIMESTAMPTZ NOT NULL
+);
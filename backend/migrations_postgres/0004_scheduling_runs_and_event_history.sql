@@ -0,0 +1,26 @@
+CREATE TABLE SchedulingRuns(
+  id          BIGSERIAL PRIMARY KEY,
+  -- The protocol Algorithm and DiscreteGraph given to the scheduler, as JSON
+  algorithm   TEXT NOT NULL,
+  input_graph TEXT NOT NULL,
+  task_count  BIGINT NOT NULL,
+  -- NULL if the run failed
+  cost        DOUBLE PRECISION,
+  started_at  TIMESTAMPTZ NOT NULL,
+  duration    BIGINT NOT NULL,
+  error       TEXT
+);
+
+CREATE TABLE EventHistory(
+  id                BIGSERIAL PRIMARY KEY,
+  task_id           BIGINT NOT NULL
+    REFERENCES Tasks(id) ON DELETE CASCADE,
+  start_time        TIMESTAMPTZ NOT NULL,
+  -- NULL if the event was moved by the user, e.g. by pinning the task
+  scheduling_run_id BIGINT
+    REFERENCES SchedulingRuns(id) ON DELETE SET NULL,
+  changed_at        TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX EventHistoryTaskId ON EventHistory(task_id);
+CREATE INDEX EventHistorySchedulingRunId ON EventHistory(scheduling_run_id);
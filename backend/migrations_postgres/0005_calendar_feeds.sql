@@ -0,0 +1,9 @@
+CREATE TABLE CalendarFeeds(
+  -- The secret in the feed url
+  id         UUID PRIMARY KEY,
+  account_id BIGINT NOT NULL
+    REFERENCES Accounts(id) ON DELETE CASCADE,
+  -- NULL if the feed contains the events of all the account's devices
+  device_id  BIGINT
+    REFERENCES Devices(id) ON DELETE CASCADE
+);
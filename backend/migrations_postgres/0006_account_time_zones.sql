@@ -0,0 +1,2 @@
+-- The IANA name of the time zone the account's local times are in
+ALTER TABLE Accounts ADD COLUMN time_zone VARCHAR(64) NOT NULL DEFAULT 'UTC';
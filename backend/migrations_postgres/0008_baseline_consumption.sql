@@ -0,0 +1,6 @@
+CREATE TABLE BaselineConsumption(
+  account_id BIGINT PRIMARY KEY
+    REFERENCES Accounts(id) ON DELETE CASCADE,
+  -- The protocol DailyProfile of the account's unscheduled load in W, as JSON
+  profile    TEXT NOT NULL
+);
@@ -0,0 +1,3 @@
+-- The protocol EventExplanation of why the scheduler chose the start time, as JSON.
+-- It is NULL for pinned events and events that were scheduled before explanations existed.
+ALTER TABLE Events ADD COLUMN explanation TEXT;
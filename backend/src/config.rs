@@ -0,0 +1,353 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::Duration;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use protocol::scheduling::Algorithm;
+
+use crate::{
+    scheduling::{
+        background_service::SchedulingConfig, registry::create_algorithm,
+        scheduler::SchedulerAlgorithm,
+    },
+    Args,
+};
+
+/// The longest scheduling horizon, as the production graph holds a value for every timeslot in it.
+const MAX_HORIZON_HOURS: i64 = 366 * 24;
+/// The longest wait for changes or between periodic runs, which are all used as timers.
+const MAX_WAIT_MINUTES: u64 = 7 * 24 * 60;
+
+/// The configuration of the backend.
+///
+/// Values are read from the TOML file given by `--config`,
+/// and are then overridden by environment variables and command line arguments.
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind_address: String,
+    pub database_url: Option<String>,
+    /// The token that must be sent in the `X-Admin-Token` header to call the admin endpoints,
+    /// which are disabled if it is not set
+    pub admin_token: Option<String>,
+    /// A JSON file with a carbon intensity series that is ingested on startup
+    pub carbon_intensity_file: Option<PathBuf>,
+    pub scheduling: SchedulingSection,
+    pub algorithm: Algorithm,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulingSection {
+    pub debounce_seconds: u64,
+    pub max_debounce_seconds: u64,
+    pub slot_resolution_minutes: i64,
+    pub horizon_hours: i64,
+    pub interval_minutes: u64,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum AlgorithmName {
+    Naive,
+    Global,
+    AllPermutations,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind_address: "127.0.0.1:3000".to_string(),
+            database_url: None,
+            admin_token: None,
+            carbon_intensity_file: None,
+            scheduling: SchedulingSection::default(),
+            algorithm: Algorithm::default(),
+        }
+    }
+}
+
+impl Default for SchedulingSection {
+    fn default() -> Self {
+        let defaults = SchedulingConfig::default();
+        SchedulingSection {
+            debounce_seconds: defaults.debounce.as_secs(),
+            max_debounce_seconds: defaults.max_debounce.as_secs(),
+            slot_resolution_minutes: defaults.slot_resolution.num_minutes(),
+            horizon_hours: defaults.horizon.num_hours(),
+            interval_minutes: defaults.interval.as_secs() / 60,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file given in `args`, applies the overrides from `args` and validates the result.
+    pub fn load(args: &Args) -> Result<Config> {
+        let mut config = match &args.config {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+        config.apply_overrides(args)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Config> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read config file '{}'", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Invalid config file '{}'", path.display()))
+    }
+
+    fn apply_overrides(&mut self, args: &Args) -> Result<()> {
+        if let Some(bind_address) = &args.bind_address {
+            self.bind_address = bind_address.clone();
+        }
+        if let Some(database_url) = &args.database_url {
+            self.database_url = Some(database_url.clone());
+        }
+        if let Some(admin_token) = &args.admin_token {
+            self.admin_token = Some(admin_token.clone());
+        }
+        if let Some(carbon_intensity_file) = &args.carbon_intensity_file {
+            self.carbon_intensity_file = Some(carbon_intensity_file.clone());
+        }
+        if let Some(debounce_seconds) = args.debounce_seconds {
+            self.scheduling.debounce_seconds = debounce_seconds;
+        }
+        if let Some(max_debounce_seconds) = args.max_debounce_seconds {
+            self.scheduling.max_debounce_seconds = max_debounce_seconds;
+        }
+        if let Some(slot_resolution_minutes) = args.slot_resolution_minutes {
+            self.scheduling.slot_resolution_minutes = slot_resolution_minutes;
+        }
+        if let Some(horizon_hours) = args.horizon_hours {
+            self.scheduling.horizon_hours = horizon_hours;
+        }
+        if let Some(interval_minutes) = args.interval_minutes {
+            self.scheduling.interval_minutes = interval_minutes;
+        }
+
+        if let Some(algorithm) = args.algorithm {
+            self.algorithm = match algorithm {
+                AlgorithmName::Naive => Algorithm::Naive,
+                AlgorithmName::Global => Algorithm::Global,
+                AlgorithmName::AllPermutations => Algorithm::AllPermutations { max_tasks: None },
+            };
+        }
+        if let Some(max_tasks) = args.max_tasks {
+            match &mut self.algorithm {
+                Algorithm::AllPermutations { max_tasks: current } => *current = Some(max_tasks),
+                _ => bail!("max_tasks can only be set for the all_permutations algorithm"),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.bind_address.parse::<SocketAddr>().with_context(|| {
+            format!(
+                "bind_address '{}' is not a valid socket address, e.g. 127.0.0.1:3000",
+                self.bind_address
+            )
+        })?;
+
+        match &self.database_url {
+            None => bail!("No database URL is configured, set DATABASE_URL or database_url in the config file"),
+            Some(url)
+                if !["sqlite:", "postgres:", "postgresql:"]
+                    .iter()
+                    .any(|scheme| url.starts_with(scheme)) =>
+            {
+                bail!(
+                    "database_url '{}' must be a sqlite or postgres URL, e.g. sqlite://dev.db",
+                    url
+                )
+            }
+            Some(_) => {}
+        }
+
+        if self
+            .admin_token
+            .as_ref()
+            .is_some_and(|admin_token| admin_token.trim().is_empty())
+        {
+            bail!("admin_token must not be empty");
+        }
+
+        let scheduling = &self.scheduling;
+        if scheduling.slot_resolution_minutes <= 0 {
+            bail!("slot_resolution_minutes must be positive");
+        }
+        if scheduling.horizon_hours <= 0 {
+            bail!("horizon_hours must be positive");
+        }
+        if scheduling.horizon_hours > MAX_HORIZON_HOURS {
+            bail!("horizon_hours must be at most {}", MAX_HORIZON_HOURS);
+        }
+        if scheduling.slot_resolution_minutes > scheduling.horizon_hours * 60 {
+            bail!("slot_resolution_minutes must not be longer than the scheduling horizon");
+        }
+        if scheduling.interval_minutes == 0 {
+            bail!("interval_minutes must be positive");
+        }
+        if scheduling.interval_minutes > MAX_WAIT_MINUTES {
+            bail!("interval_minutes must be at most {}", MAX_WAIT_MINUTES);
+        }
+        if scheduling.max_debounce_seconds > MAX_WAIT_MINUTES * 60 {
+            bail!(
+                "max_debounce_seconds must be at most {}",
+                MAX_WAIT_MINUTES * 60
+            );
+        }
+        if scheduling.max_debounce_seconds < scheduling.debounce_seconds {
+            bail!("max_debounce_seconds must not be shorter than debounce_seconds");
+        }
+
+        if let Algorithm::AllPermutations { max_tasks: Some(0) } = self.algorithm {
+            bail!("max_tasks must be positive");
+        }
+
+        Ok(())
+    }
+
+    pub fn scheduling_config(&self) -> SchedulingConfig {
+        SchedulingConfig {
+            debounce: std::time::Duration::from_secs(self.scheduling.debounce_seconds),
+            max_debounce: std::time::Duration::from_secs(self.scheduling.max_debounce_seconds),
+            slot_resolution: Duration::minutes(self.scheduling.slot_resolution_minutes),
+            horizon: Duration::hours(self.scheduling.horizon_hours),
+            interval: std::time::Duration::from_secs(self.scheduling.interval_minutes * 60),
+        }
+    }
+
+    pub fn algorithm(&self) -> Arc<dyn SchedulerAlgorithm + Send + Sync> {
+        create_algorithm(&self.algorithm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn parse(toml: &str, args: &[&str]) -> Result<Config> {
+        let mut config: Config = toml::from_str(toml)?;
+        let args = Args::try_parse_from(std::iter::once("backend").chain(args.iter().copied()))?;
+        config.apply_overrides(&args)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn config_file_is_parsed() {
+        let config = parse(
+            r#"
+            bind_address = "0.0.0.0:8080"
+            database_url = "sqlite://prod.db"
+            admin_token = "secret"
+            carbon_intensity_file = "carbon.json"
+
+            [scheduling]
+            debounce_seconds = 60
+            horizon_hours = 72
+            interval_minutes = 15
+
+            [algorithm]
+            name = "all_permutations"
+            max_tasks = 6
+            "#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(config.bind_address, "0.0.0.0:8080");
+        assert_eq!(config.admin_token.as_deref(), Some("secret"));
+        assert_eq!(
+            config.carbon_intensity_file,
+            Some(PathBuf::from("carbon.json"))
+        );
+        assert_eq!(
+            config.scheduling,
+            SchedulingSection {
+                debounce_seconds: 60,
+                max_debounce_seconds: 900,
+                slot_resolution_minutes: 1,
+                horizon_hours: 72,
+                interval_minutes: 15,
+            }
+        );
+        assert_eq!(
+            config.algorithm,
+            Algorithm::AllPermutations { max_tasks: Some(6) }
+        );
+    }
+
+    #[test]
+    fn arguments_override_config_file() {
+        let config = parse(
+            r#"
+            database_url = "sqlite://prod.db"
+
+            [algorithm]
+            name = "naive"
+            "#,
+            &[
+                "--algorithm",
+                "global",
+                "--database-url",
+                "sqlite::memory:",
+                "--slot-resolution-minutes",
+                "15",
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.algorithm, Algorithm::Global);
+        assert_eq!(config.database_url.as_deref(), Some("sqlite::memory:"));
+        assert_eq!(config.scheduling.slot_resolution_minutes, 15);
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let database = "database_url = \"sqlite://dev.db\"\n";
+
+        assert!(parse(database, &["--bind-address", "localhost"]).is_err());
+        assert!(parse(database, &["--slot-resolution-minutes", "0"]).is_err());
+        assert!(parse(
+            database,
+            &["--horizon-hours", "1", "--slot-resolution-minutes", "90"]
+        )
+        .is_err());
+        assert!(parse(database, &["--algorithm", "naive", "--max-tasks", "3"]).is_err());
+        assert!(parse("[algorithm]\nname = \"unknown\"\n", &[]).is_err());
+        assert!(parse("", &["--database-url", "mysql://localhost"]).is_err());
+        assert!(parse(database, &["--admin-token", " "]).is_err());
+        assert!(parse(database, &["--interval-minutes", "0"]).is_err());
+        assert!(parse(database, &["--horizon-hours", &i64::MAX.to_string()]).is_err());
+        assert!(parse(database, &["--interval-minutes", &u64::MAX.to_string()]).is_err());
+        assert!(parse(
+            database,
+            &[
+                "--debounce-seconds",
+                &u64::MAX.to_string(),
+                "--max-debounce-seconds",
+                &u64::MAX.to_string()
+            ]
+        )
+        .is_err());
+        assert!(parse(
+            database,
+            &["--debounce-seconds", "600", "--max-debounce-seconds", "300"]
+        )
+        .is_err());
+    }
+}
@@ -1 +1,4 @@
 pub mod account;
+pub mod postgres;
+pub mod repository;
+pub mod sqlite;
@@ -0,0 +1,1275 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use chrono_tz::Tz;
+use itertools::Itertools;
+use protocol::{
+    accounts::AuthToken,
+    baseline::DailyProfile,
+    calendar::{CalendarFeed, CalendarFeedSecret},
+    devices::{Device, DeviceId},
+    events::{Event, EventExplanation, EventId},
+    graph::DiscreteGraph,
+    scheduling::{EventChange, SchedulingRun, SchedulingRunId},
+    tasks::{Task, TaskId},
+    time::{DateTimeUtc, Milliseconds, Timespan},
+};
+use sqlx::{postgres::PgPoolOptions, FromRow, PgConnection, PgPool};
+use tracing::{event, Level};
+
+use super::{
+    account::AccountId,
+    repository::{
+        is_schedulable, AccountBaseline, AccountRepository, BaselineRepository, CalendarEntry,
+        CalendarRepository, CarbonIntensityRepository, DeviceRepository, EventRepository,
+        NewSchedulingRun, PinnedTask, PoolStatus, ReportEntry, Repository, SchedulingRunRepository,
+        StoredAccount, TaskRepository,
+    },
+};
+use crate::scheduling::{
+    task_for_scheduler::TaskForScheduler, unpublished_event::UnpublishedEvent,
+};
+
+/// Stores the data in PostgreSQL.
+///
+/// The queries are checked at runtime, as the compile time checks use the SQLite database.
+pub struct PostgresRepository {
+    pool: PgPool,
+}
+
+#[derive(FromRow)]
+struct TaskRow {
+    id: TaskId,
+    timespan_start: DateTimeUtc,
+    timespan_end: DateTimeUtc,
+    duration: Milliseconds,
+    device_id: DeviceId,
+    pinned_start: Option<DateTimeUtc>,
+}
+
+#[derive(FromRow)]
+struct SchedulingTaskRow {
+    id: TaskId,
+    timespan_start: DateTimeUtc,
+    timespan_end: DateTimeUtc,
+    duration: Milliseconds,
+    effect: f64,
+}
+
+#[derive(FromRow)]
+struct SchedulingRunRow {
+    id: SchedulingRunId,
+    algorithm: String,
+    input_graph: String,
+    task_count: i64,
+    cost: Option<f64>,
+    emissions: Option<f64>,
+    started_at: DateTimeUtc,
+    duration: Milliseconds,
+    error: Option<String>,
+}
+
+impl SchedulingRunRow {
+    fn into_scheduling_run(self) -> Result<(SchedulingRun, String)> {
+        let run = SchedulingRun {
+            id: self.id,
+            algorithm: serde_json::from_str(&self.algorithm)?,
+            task_count: self.task_count,
+            cost: self.cost,
+            emissions: self.emissions,
+            started_at: self.started_at,
+            duration: self.duration,
+            error: self.error,
+        };
+        Ok((run, self.input_graph))
+    }
+}
+
+impl PostgresRepository {
+    pub fn new(pool: PgPool) -> Self {
+        PostgresRepository { pool }
+    }
+
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new().connect(database_url).await?;
+        Ok(PostgresRepository::new(pool))
+    }
+
+    pub async fn migrate(&self) -> Result<()> {
+        sqlx::migrate!("./migrations_postgres")
+            .run(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// The exclusions of the tasks, ordered by their start.
+    async fn exclusions(&self, account_id: Option<&AccountId>) -> Result<Vec<(TaskId, Timespan)>> {
+        let exclusions = sqlx::query_as::<_, (TaskId, DateTimeUtc, DateTimeUtc)>(
+            r#"
+            SELECT TaskExclusions.task_id, TaskExclusions.timespan_start, TaskExclusions.timespan_end
+            FROM TaskExclusions
+            JOIN Tasks ON TaskExclusions.task_id = Tasks.id
+            JOIN Devices ON Tasks.device_id = Devices.id
+            WHERE $1::BIGINT IS NULL OR Devices.account_id = $1
+            ORDER BY TaskExclusions.timespan_start
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(task_id, start, end)| (task_id, Timespan::new(start, end)))
+        .collect();
+
+        Ok(exclusions)
+    }
+}
+
+/// Creates or moves the events of the tasks, unchanged events only get their explanation updated.
+///
+/// Every new start time is recorded in `EventHistory`, along with the run that caused it, if any.
+async fn upsert_events(
+    connection: &mut PgConnection,
+    events: &[UnpublishedEvent],
+    run_id: Option<SchedulingRunId>,
+) -> Result<()> {
+    for event in events {
+        let current_start_time = sqlx::query_scalar::<_, DateTimeUtc>(
+            r#"
+            SELECT start_time
+            FROM Events
+            WHERE task_id = $1
+            "#,
+        )
+        .bind(event.task_id)
+        .fetch_optional(&mut *connection)
+        .await?;
+
+        let explanation = event
+            .explanation
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+
+        if current_start_time.is_some_and(|start_time| start_time == event.start_time) {
+            // The start time is kept, but the reasons for it may have changed
+            sqlx::query(
+                r#"
+                UPDATE Events
+                SET explanation = $1
+                WHERE task_id = $2
+                "#,
+            )
+            .bind(explanation)
+            .bind(event.task_id)
+            .execute(&mut *connection)
+            .await?;
+            continue;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO Events (task_id, start_time, explanation)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (task_id) DO UPDATE SET start_time = EXCLUDED.start_time, explanation = EXCLUDED.explanation
+            "#,
+        )
+        .bind(event.task_id)
+        .bind(event.start_time)
+        .bind(explanation)
+        .execute(&mut *connection)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO EventHistory (task_id, start_time, scheduling_run_id, changed_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(event.task_id)
+        .bind(event.start_time)
+        .bind(run_id)
+        .bind(Utc::now())
+        .execute(&mut *connection)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Whether the task belongs to one of the account's devices.
+///
+/// Every task that is changed on behalf of an account is checked here.
+async fn owns_task(
+    connection: &mut PgConnection,
+    account_id: &AccountId,
+    task_id: TaskId,
+) -> Result<bool> {
+    let owned = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM Tasks
+            JOIN Devices ON Tasks.device_id = Devices.id
+            WHERE Tasks.id = $1 AND Devices.account_id = $2
+        )
+        "#,
+    )
+    .bind(task_id)
+    .bind(account_id)
+    .fetch_one(&mut *connection)
+    .await?;
+
+    Ok(owned)
+}
+
+/// Whether the device belongs to the account.
+async fn owns_device(
+    connection: &mut PgConnection,
+    account_id: &AccountId,
+    device_id: DeviceId,
+) -> Result<bool> {
+    let owned = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM Devices
+            WHERE id = $1 AND account_id = $2
+        )
+        "#,
+    )
+    .bind(device_id)
+    .bind(account_id)
+    .fetch_one(&mut *connection)
+    .await?;
+
+    Ok(owned)
+}
+
+#[async_trait]
+impl AccountRepository for PostgresRepository {
+    async fn create_account(
+        &self,
+        username: &str,
+        password_hash: &str,
+    ) -> Result<Option<AccountId>> {
+        let result = sqlx::query_scalar::<_, AccountId>(
+            r#"
+            INSERT INTO Accounts (username, password_hash)
+            VALUES ($1, $2)
+            RETURNING id
+            "#,
+        )
+        .bind(username)
+        .bind(password_hash)
+        .fetch_one(&self.pool)
+        .await;
+
+        match result {
+            Ok(account_id) => Ok(Some(account_id)),
+            Err(e)
+                if e.as_database_error()
+                    .is_some_and(|e| e.is_unique_violation()) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn account_by_username(&self, username: &str) -> Result<Option<StoredAccount>> {
+        let account = sqlx::query_as::<_, (AccountId, String)>(
+            r#"
+            SELECT id, password_hash
+            FROM Accounts
+            WHERE username = $1
+            "#,
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|(id, password_hash)| StoredAccount { id, password_hash });
+
+        Ok(account)
+    }
+
+    async fn create_auth_token(&self, account_id: &AccountId) -> Result<AuthToken> {
+        let auth_token = AuthToken::new();
+
+        sqlx::query(
+            r#"
+            INSERT INTO AuthTokens (id, account_id)
+            VALUES ($1, $2)
+            "#,
+        )
+        .bind(&auth_token)
+        .bind(account_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(auth_token)
+    }
+
+    async fn account_for_auth_token(&self, auth_token: &AuthToken) -> Result<Option<AccountId>> {
+        let account_id = sqlx::query_scalar::<_, AccountId>(
+            r#"
+            SELECT account_id
+            FROM AuthTokens
+            WHERE id = $1
+            "#,
+        )
+        .bind(auth_token)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(account_id)
+    }
+
+    async fn account_time_zone(&self, account_id: &AccountId) -> Result<Tz> {
+        let time_zone = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT time_zone
+            FROM Accounts
+            WHERE id = $1
+            "#,
+        )
+        .bind(account_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        time_zone.parse().map_err(|error| anyhow!("{}", error))
+    }
+
+    async fn set_account_time_zone(&self, account_id: &AccountId, time_zone: Tz) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE Accounts
+            SET time_zone = $1
+            WHERE id = $2
+            "#,
+        )
+        .bind(time_zone.name())
+        .bind(account_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DeviceRepository for PostgresRepository {
+    async fn devices_for_account(&self, account_id: &AccountId) -> Result<Vec<Device>> {
+        let devices = sqlx::query_as::<_, (DeviceId, String, f64)>(
+            r#"
+            SELECT id, name, effect
+            FROM Devices
+            WHERE account_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(id, name, effect)| Device { id, name, effect })
+        .collect();
+
+        Ok(devices)
+    }
+
+    async fn device_for_account(
+        &self,
+        account_id: &AccountId,
+        device_id: DeviceId,
+    ) -> Result<Option<Device>> {
+        let device = sqlx::query_as::<_, (DeviceId, String, f64)>(
+            r#"
+            SELECT id, name, effect
+            FROM Devices
+            WHERE account_id = $1 AND id = $2
+            "#,
+        )
+        .bind(account_id)
+        .bind(device_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|(id, name, effect)| Device { id, name, effect });
+
+        Ok(device)
+    }
+
+    async fn create_device(
+        &self,
+        account_id: &AccountId,
+        name: &str,
+        effect: f64,
+    ) -> Result<Device> {
+        let id = sqlx::query_scalar::<_, DeviceId>(
+            r#"
+            INSERT INTO Devices (name, effect, account_id)
+            VALUES ($1, $2, $3)
+            RETURNING id
+            "#,
+        )
+        .bind(name)
+        .bind(effect)
+        .bind(account_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Device {
+            id,
+            name: name.to_string(),
+            effect,
+        })
+    }
+
+    async fn delete_device(&self, account_id: &AccountId, device_id: DeviceId) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM Devices
+            WHERE id = $1 AND account_id = $2
+            "#,
+        )
+        .bind(device_id)
+        .bind(account_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[async_trait]
+impl TaskRepository for PostgresRepository {
+    async fn tasks_for_account(
+        &self,
+        account_id: &AccountId,
+        now: DateTimeUtc,
+    ) -> Result<Vec<Task>> {
+        let tasks = sqlx::query_as::<_, TaskRow>(
+            r#"
+            SELECT Tasks.id, Tasks.timespan_start, Tasks.timespan_end, Tasks.duration, Tasks.device_id, Tasks.pinned_start
+            FROM Tasks
+            JOIN Devices ON Tasks.device_id = Devices.id
+            WHERE Devices.account_id = $1 AND Tasks.timespan_end >= $2
+            ORDER BY Tasks.id
+            "#,
+        )
+        .bind(account_id)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut exclusions = self
+            .exclusions(Some(account_id))
+            .await?
+            .into_iter()
+            .into_group_map();
+
+        let tasks = tasks
+            .into_iter()
+            .map(|t| Task {
+                id: t.id,
+                timespan: Timespan::new(t.timespan_start, t.timespan_end),
+                duration: t.duration,
+                device_id: t.device_id,
+                pinned_start: t.pinned_start,
+                exclusions: exclusions.remove(&t.id).unwrap_or_default(),
+            })
+            .collect();
+
+        Ok(tasks)
+    }
+
+    async fn task_for_account(
+        &self,
+        account_id: &AccountId,
+        task_id: TaskId,
+    ) -> Result<Option<Task>> {
+        let mut connection = self.pool.acquire().await?;
+        if !owns_task(&mut connection, account_id, task_id).await? {
+            return Ok(None);
+        }
+
+        let task = sqlx::query_as::<_, TaskRow>(
+            r#"
+            SELECT id, timespan_start, timespan_end, duration, device_id, pinned_start
+            FROM Tasks
+            WHERE id = $1
+            "#,
+        )
+        .bind(task_id)
+        .fetch_one(&mut *connection)
+        .await?;
+
+        let exclusions = sqlx::query_as::<_, (DateTimeUtc, DateTimeUtc)>(
+            r#"
+            SELECT timespan_start, timespan_end
+            FROM TaskExclusions
+            WHERE task_id = $1
+            ORDER BY timespan_start
+            "#,
+        )
+        .bind(task_id)
+        .fetch_all(&mut *connection)
+        .await?
+        .into_iter()
+        .map(|(start, end)| Timespan::new(start, end))
+        .collect();
+
+        Ok(Some(Task {
+            id: task.id,
+            timespan: Timespan::new(task.timespan_start, task.timespan_end),
+            duration: task.duration,
+            device_id: task.device_id,
+            pinned_start: task.pinned_start,
+            exclusions,
+        }))
+    }
+
+    async fn create_task(
+        &self,
+        account_id: &AccountId,
+        device_id: DeviceId,
+        timespan: &Timespan,
+        duration: Milliseconds,
+    ) -> Result<Option<Task>> {
+        let mut connection = self.pool.acquire().await?;
+        if !owns_device(&mut connection, account_id, device_id).await? {
+            return Ok(None);
+        }
+
+        // The stored timespan is returned, as PostgreSQL only keeps microseconds
+        let task = sqlx::query_as::<_, TaskRow>(
+            r#"
+            INSERT INTO Tasks (timespan_start, timespan_end, duration, device_id)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, timespan_start, timespan_end, duration, device_id, pinned_start
+            "#,
+        )
+        .bind(timespan.start)
+        .bind(timespan.end)
+        .bind(duration)
+        .bind(device_id)
+        .fetch_one(&mut *connection)
+        .await?;
+
+        Ok(Some(Task {
+            id: task.id,
+            timespan: Timespan::new(task.timespan_start, task.timespan_end),
+            duration: task.duration,
+            device_id: task.device_id,
+            pinned_start: task.pinned_start,
+            exclusions: Vec::new(),
+        }))
+    }
+
+    async fn delete_task(&self, account_id: &AccountId, task_id: TaskId) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        if !owns_task(&mut transaction, account_id, task_id).await? {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            r#"
+            DELETE FROM Tasks
+            WHERE id = $1
+            "#,
+        )
+        .bind(task_id)
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn pin_task(
+        &self,
+        account_id: &AccountId,
+        task_id: TaskId,
+        start_time: DateTimeUtc,
+    ) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        if !owns_task(&mut transaction, account_id, task_id).await? {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE Tasks
+            SET pinned_start = $1
+            WHERE id = $2
+            "#,
+        )
+        .bind(start_time)
+        .bind(task_id)
+        .execute(&mut *transaction)
+        .await?;
+
+        let event = UnpublishedEvent {
+            task_id,
+            start_time,
+            explanation: None,
+        };
+        upsert_events(&mut transaction, &[event], None).await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn unpin_task(&self, account_id: &AccountId, task_id: TaskId) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        if !owns_task(&mut transaction, account_id, task_id).await? {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE Tasks
+            SET pinned_start = NULL
+            WHERE id = $1
+            "#,
+        )
+        .bind(task_id)
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn clear_exclusions(&self, account_id: &AccountId, task_id: TaskId) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        if !owns_task(&mut transaction, account_id, task_id).await? {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            r#"
+            DELETE FROM TaskExclusions
+            WHERE task_id = $1
+            "#,
+        )
+        .bind(task_id)
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn add_exclusion(
+        &self,
+        account_id: &AccountId,
+        task_id: TaskId,
+        timespan: &Timespan,
+    ) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        if !owns_task(&mut transaction, account_id, task_id).await? {
+            return Ok(false);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO TaskExclusions (task_id, timespan_start, timespan_end)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(task_id)
+        .bind(timespan.start)
+        .bind(timespan.end)
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn tasks_for_scheduling(&self, now: DateTimeUtc) -> Result<Vec<TaskForScheduler>> {
+        // TODO: Filter out tasks that have a started event
+        let tasks = sqlx::query_as::<_, SchedulingTaskRow>(
+            r#"
+            SELECT Tasks.id, Tasks.timespan_start, Tasks.timespan_end, Tasks.duration, Devices.effect
+            FROM Tasks
+            JOIN Devices ON Tasks.device_id = Devices.id
+            WHERE Tasks.pinned_start IS NULL AND Tasks.timespan_end >= $1 AND EXTRACT(EPOCH FROM (Tasks.timespan_end - $1)) * 1000 >= Tasks.duration
+            ORDER BY Tasks.id
+            "#,
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut exclusions = self.exclusions(None).await?.into_iter().into_group_map();
+
+        let tasks = tasks
+            .into_iter()
+            .filter(|t| {
+                let valid = is_schedulable(t.timespan_start, t.timespan_end, t.duration);
+                if !valid {
+                    event!(target: "backend", Level::WARN, "Skipping invalid task with id: {}", t.id);
+                }
+                valid
+            })
+            .map(|t| {
+                let mut task = TaskForScheduler::new(
+                    t.id,
+                    Timespan::new(t.timespan_start, t.timespan_end),
+                    t.duration,
+                    t.effect,
+                );
+                task.exclusions = exclusions.remove(&t.id).unwrap_or_default();
+                task
+            })
+            .collect();
+
+        Ok(tasks)
+    }
+
+    async fn pinned_tasks(&self, now: DateTimeUtc) -> Result<Vec<PinnedTask>> {
+        let tasks = sqlx::query_as::<_, (TaskId, DateTimeUtc, DateTimeUtc, Milliseconds, DateTimeUtc, f64)>(
+            r#"
+            SELECT Tasks.id, Tasks.timespan_start, Tasks.timespan_end, Tasks.duration, Tasks.pinned_start, Devices.effect
+            FROM Tasks
+            JOIN Devices ON Tasks.device_id = Devices.id
+            WHERE Tasks.pinned_start IS NOT NULL AND Tasks.timespan_end >= $1
+            ORDER BY Tasks.id
+            "#,
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(id, start, end, duration, pinned_start, effect)| PinnedTask {
+            task: TaskForScheduler {
+                id,
+                timespan: Timespan::new(start, end),
+                duration,
+                effect,
+                exclusions: Vec::new(),
+            },
+            start_time: pinned_start,
+        })
+        .collect();
+
+        Ok(tasks)
+    }
+}
+
+#[async_trait]
+impl EventRepository for PostgresRepository {
+    async fn events_for_account(
+        &self,
+        account_id: &AccountId,
+        now: DateTimeUtc,
+    ) -> Result<Vec<Event>> {
+        let events = sqlx::query_as::<_, (EventId, TaskId, DateTimeUtc)>(
+            r#"
+            SELECT Events.id, Events.task_id, Events.start_time
+            FROM Events
+            JOIN Tasks ON Events.task_id = Tasks.id
+            JOIN Devices ON Tasks.device_id = Devices.id
+            WHERE Devices.account_id = $1 AND Tasks.timespan_end >= $2
+            ORDER BY Events.id
+            "#,
+        )
+        .bind(account_id)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(id, task_id, start_time)| Event {
+            id,
+            task_id,
+            start_time,
+        })
+        .collect();
+
+        Ok(events)
+    }
+
+    async fn event_for_device(
+        &self,
+        account_id: &AccountId,
+        device_id: DeviceId,
+        now: DateTimeUtc,
+    ) -> Result<Option<Event>> {
+        let event = sqlx::query_as::<_, (EventId, TaskId, DateTimeUtc)>(
+            r#"
+            SELECT Events.id, Events.task_id, Events.start_time
+            FROM Events
+            JOIN Tasks ON Events.task_id = Tasks.id
+            JOIN Devices ON Tasks.device_id = Devices.id
+            WHERE Devices.account_id = $1 AND Devices.id = $2 AND Events.start_time >= $3
+            ORDER BY Events.start_time
+            LIMIT 1
+            "#,
+        )
+        .bind(account_id)
+        .bind(device_id)
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|(id, task_id, start_time)| Event {
+            id,
+            task_id,
+            start_time,
+        });
+
+        Ok(event)
+    }
+
+    async fn report_entries(
+        &self,
+        account_id: &AccountId,
+        timespan: &Timespan,
+    ) -> Result<Vec<ReportEntry>> {
+        let entries = sqlx::query_as::<
+            _,
+            (DeviceId, String, f64, DateTimeUtc, DateTimeUtc, Milliseconds),
+        >(
+            r#"
+            SELECT Devices.id, Devices.name, Devices.effect, Tasks.timespan_start, Events.start_time, Tasks.duration
+            FROM Events
+            JOIN Tasks ON Events.task_id = Tasks.id
+            JOIN Devices ON Tasks.device_id = Devices.id
+            WHERE Devices.account_id = $1 AND Events.start_time >= $2 AND Events.start_time < $3
+            ORDER BY Events.start_time, Events.task_id
+            "#,
+        )
+        .bind(account_id)
+        .bind(timespan.start)
+        .bind(timespan.end)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(
+            |(device_id, device_name, effect, timespan_start, start_time, duration)| ReportEntry {
+                device_id,
+                device_name,
+                effect,
+                timespan_start,
+                start_time,
+                duration,
+            },
+        )
+        .collect();
+
+        Ok(entries)
+    }
+
+    async fn create_event(&self, task_id: TaskId, start_time: DateTimeUtc) -> Result<Event> {
+        let (id, start_time) = sqlx::query_as::<_, (EventId, DateTimeUtc)>(
+            r#"
+            INSERT INTO Events (task_id, start_time) VALUES ($1, $2)
+            RETURNING id, start_time
+            "#,
+        )
+        .bind(task_id)
+        .bind(start_time)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Event {
+            id,
+            task_id,
+            start_time,
+        })
+    }
+
+    async fn upsert_events(
+        &self,
+        events: &[UnpublishedEvent],
+        run_id: Option<SchedulingRunId>,
+    ) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        upsert_events(&mut transaction, events, run_id).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn event_explanation(
+        &self,
+        account_id: &AccountId,
+        event_id: EventId,
+    ) -> Result<Option<EventExplanation>> {
+        let explanation = sqlx::query_scalar::<_, Option<String>>(
+            r#"
+            SELECT Events.explanation
+            FROM Events
+            JOIN Tasks ON Events.task_id = Tasks.id
+            JOIN Devices ON Tasks.device_id = Devices.id
+            WHERE Devices.account_id = $1 AND Events.id = $2
+            "#,
+        )
+        .bind(account_id)
+        .bind(event_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .flatten();
+
+        Ok(explanation
+            .map(|explanation| serde_json::from_str(&explanation))
+            .transpose()?)
+    }
+}
+
+#[async_trait]
+impl SchedulingRunRepository for PostgresRepository {
+    async fn record_scheduling_run(&self, run: &NewSchedulingRun) -> Result<SchedulingRunId> {
+        let algorithm = serde_json::to_string(&run.algorithm)?;
+        let input_graph = serde_json::to_string(&run.input_graph)?;
+
+        let run_id = sqlx::query_scalar::<_, SchedulingRunId>(
+            r#"
+            INSERT INTO SchedulingRuns (algorithm, input_graph, task_count, cost, emissions, started_at, duration, error)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id
+            "#,
+        )
+        .bind(algorithm)
+        .bind(input_graph)
+        .bind(run.task_count)
+        .bind(run.cost)
+        .bind(run.emissions)
+        .bind(run.started_at)
+        .bind(run.duration)
+        .bind(&run.error)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(run_id)
+    }
+
+    async fn scheduling_runs(&self, limit: u32) -> Result<Vec<SchedulingRun>> {
+        let runs = sqlx::query_as::<_, SchedulingRunRow>(
+            r#"
+            SELECT id, algorithm, '' AS input_graph, task_count, cost, emissions, started_at, duration, error
+            FROM SchedulingRuns
+            ORDER BY id DESC
+            LIMIT $1
+            "#,
+        )
+        .bind(i64::from(limit))
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|r| Ok(r.into_scheduling_run()?.0))
+        .collect::<Result<_>>()?;
+
+        Ok(runs)
+    }
+
+    async fn scheduling_run(
+        &self,
+        run_id: SchedulingRunId,
+    ) -> Result<Option<(SchedulingRun, DiscreteGraph)>> {
+        let Some(run) = sqlx::query_as::<_, SchedulingRunRow>(
+            r#"
+            SELECT id, algorithm, input_graph, task_count, cost, emissions, started_at, duration, error
+            FROM SchedulingRuns
+            WHERE id = $1
+            "#,
+        )
+        .bind(run_id)
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let (run, input_graph) = run.into_scheduling_run()?;
+        Ok(Some((run, serde_json::from_str(&input_graph)?)))
+    }
+
+    async fn event_changes(&self, run_id: SchedulingRunId) -> Result<Vec<EventChange>> {
+        let changes = sqlx::query_as::<_, (TaskId, DateTimeUtc, Option<DateTimeUtc>)>(
+            r#"
+            SELECT EventHistory.task_id, EventHistory.start_time, (
+                SELECT Previous.start_time
+                FROM EventHistory AS Previous
+                WHERE Previous.task_id = EventHistory.task_id AND Previous.id < EventHistory.id
+                ORDER BY Previous.id DESC
+                LIMIT 1
+            )
+            FROM EventHistory
+            WHERE EventHistory.scheduling_run_id = $1
+            ORDER BY EventHistory.task_id
+            "#,
+        )
+        .bind(run_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(task_id, start_time, previous_start_time)| EventChange {
+            task_id,
+            previous_start_time,
+            start_time,
+        })
+        .collect();
+
+        Ok(changes)
+    }
+}
+
+#[async_trait]
+impl BaselineRepository for PostgresRepository {
+    async fn set_baseline(&self, account_id: &AccountId, profile: &DailyProfile) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO BaselineConsumption (account_id, profile)
+            VALUES ($1, $2)
+            ON CONFLICT (account_id) DO UPDATE SET profile = excluded.profile
+            "#,
+        )
+        .bind(account_id)
+        .bind(serde_json::to_string(profile)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn baseline(&self, account_id: &AccountId) -> Result<Option<DailyProfile>> {
+        let profile = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT profile
+            FROM BaselineConsumption
+            WHERE account_id = $1
+            "#,
+        )
+        .bind(account_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(profile
+            .map(|profile| serde_json::from_str(&profile))
+            .transpose()?)
+    }
+
+    async fn delete_baseline(&self, account_id: &AccountId) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM BaselineConsumption
+            WHERE account_id = $1
+            "#,
+        )
+        .bind(account_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn baselines(&self) -> Result<Vec<AccountBaseline>> {
+        let rows = sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT Accounts.time_zone, BaselineConsumption.profile
+            FROM BaselineConsumption
+            JOIN Accounts ON Accounts.id = BaselineConsumption.account_id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|(time_zone, profile)| {
+                Ok(AccountBaseline {
+                    time_zone: time_zone.parse().map_err(|error| anyhow!("{}", error))?,
+                    profile: serde_json::from_str(&profile)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl CarbonIntensityRepository for PostgresRepository {
+    async fn set_carbon_intensity(&self, series: &DiscreteGraph) -> Result<()> {
+        let series = serde_json::to_string(series)?;
+
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("DELETE FROM CarbonIntensity")
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO CarbonIntensity (series, created_at)
+            VALUES ($1, $2)
+            "#,
+        )
+        .bind(series)
+        .bind(Utc::now())
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn carbon_intensity(&self) -> Result<Option<DiscreteGraph>> {
+        let series = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT series
+            FROM CarbonIntensity
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(series
+            .map(|series| serde_json::from_str(&series))
+            .transpose()?)
+    }
+}
+
+#[async_trait]
+impl CalendarRepository for PostgresRepository {
+    async fn calendar_entries(
+        &self,
+        account_id: &AccountId,
+        device_id: Option<DeviceId>,
+    ) -> Result<Vec<CalendarEntry>> {
+        let entries = sqlx::query_as::<_, (TaskId, DateTimeUtc, Milliseconds, String)>(
+            r#"
+            SELECT Events.task_id, Events.start_time, Tasks.duration, Devices.name
+            FROM Events
+            JOIN Tasks ON Events.task_id = Tasks.id
+            JOIN Devices ON Tasks.device_id = Devices.id
+            WHERE Devices.account_id = $1 AND ($2::BIGINT IS NULL OR Devices.id = $2)
+            ORDER BY Events.start_time, Events.task_id
+            "#,
+        )
+        .bind(account_id)
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(
+            |(task_id, start_time, duration, device_name)| CalendarEntry {
+                task_id,
+                start_time,
+                duration,
+                device_name,
+            },
+        )
+        .collect();
+
+        Ok(entries)
+    }
+
+    async fn create_calendar_feed(
+        &self,
+        account_id: &AccountId,
+        device_id: Option<DeviceId>,
+    ) -> Result<Option<CalendarFeed>> {
+        let mut connection = self.pool.acquire().await?;
+        if let Some(device_id) = device_id {
+            if !owns_device(&mut connection, account_id, device_id).await? {
+                return Ok(None);
+            }
+        }
+
+        let secret = CalendarFeedSecret::new();
+        sqlx::query(
+            r#"
+            INSERT INTO CalendarFeeds (id, account_id, device_id)
+            VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(&secret)
+        .bind(account_id)
+        .bind(device_id)
+        .execute(&mut *connection)
+        .await?;
+
+        Ok(Some(CalendarFeed { secret, device_id }))
+    }
+
+    async fn calendar_feeds(&self, account_id: &AccountId) -> Result<Vec<CalendarFeed>> {
+        let feeds = sqlx::query_as::<_, (CalendarFeedSecret, Option<DeviceId>)>(
+            r#"
+            SELECT id, device_id
+            FROM CalendarFeeds
+            WHERE account_id = $1
+            ORDER BY ctid
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(secret, device_id)| CalendarFeed { secret, device_id })
+        .collect();
+
+        Ok(feeds)
+    }
+
+    async fn delete_calendar_feed(
+        &self,
+        account_id: &AccountId,
+        secret: &CalendarFeedSecret,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM CalendarFeeds
+            WHERE id = $1 AND account_id = $2
+            "#,
+        )
+        .bind(secret)
+        .bind(account_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn calendar_feed(
+        &self,
+        secret: &CalendarFeedSecret,
+    ) -> Result<Option<(AccountId, CalendarFeed)>> {
+        let feed = sqlx::query_as::<_, (AccountId, Option<DeviceId>)>(
+            r#"
+            SELECT account_id, device_id
+            FROM CalendarFeeds
+            WHERE id = $1
+            "#,
+        )
+        .bind(secret)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|(account_id, device_id)| {
+            let feed = CalendarFeed {
+                secret: secret.clone(),
+                device_id,
+            };
+            (account_id, feed)
+        });
+
+        Ok(feed)
+    }
+}
+
+#[async_trait]
+impl Repository for PostgresRepository {
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    fn pool_status(&self) -> PoolStatus {
+        PoolStatus {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+        }
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+}
@@ -0,0 +1,311 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use protocol::{
+    accounts::AuthToken,
+    baseline::DailyProfile,
+    calendar::{CalendarFeed, CalendarFeedSecret},
+    devices::{Device, DeviceId},
+    events::{Event, EventExplanation, EventId},
+    graph::DiscreteGraph,
+    scheduling::{Algorithm, EventChange, SchedulingRun, SchedulingRunId},
+    tasks::{Task, TaskId},
+    time::{DateTimeUtc, Milliseconds, Timespan},
+};
+
+use super::{account::AccountId, postgres::PostgresRepository, sqlite::SqliteRepository};
+use crate::scheduling::{
+    task_for_scheduler::TaskForScheduler, unpublished_event::UnpublishedEvent,
+};
+
+/// An account as it is stored, used to verify logins.
+pub struct StoredAccount {
+    pub id: AccountId,
+    pub password_hash: String,
+}
+
+/// A task that has been pinned to `start_time` by its owner.
+pub struct PinnedTask {
+    pub task: TaskForScheduler,
+    pub start_time: DateTimeUtc,
+}
+
+/// A finished run of the scheduler, before it has been stored.
+pub struct NewSchedulingRun {
+    pub algorithm: Algorithm,
+    pub input_graph: DiscreteGraph,
+    pub task_count: i64,
+    pub cost: Option<f64>,
+    pub emissions: Option<f64>,
+    pub started_at: DateTimeUtc,
+    pub duration: Milliseconds,
+    pub error: Option<String>,
+}
+
+/// An event together with what is shown for it in a calendar.
+pub struct CalendarEntry {
+    pub task_id: TaskId,
+    pub start_time: DateTimeUtc,
+    pub duration: Milliseconds,
+    pub device_name: String,
+}
+
+/// A run of a device, with what is needed to compute its energy use.
+pub struct ReportEntry {
+    pub device_id: DeviceId,
+    pub device_name: String,
+    pub effect: f64,
+    pub timespan_start: DateTimeUtc,
+    pub start_time: DateTimeUtc,
+    pub duration: Milliseconds,
+}
+
+/// The baseline consumption of an account, whose profile is in the account's local time.
+pub struct AccountBaseline {
+    pub time_zone: Tz,
+    pub profile: DailyProfile,
+}
+
+pub struct PoolStatus {
+    pub size: u32,
+    pub idle: usize,
+}
+
+#[async_trait]
+pub trait AccountRepository {
+    /// Returns `None` if the username is taken.
+    async fn create_account(
+        &self,
+        username: &str,
+        password_hash: &str,
+    ) -> Result<Option<AccountId>>;
+    async fn account_by_username(&self, username: &str) -> Result<Option<StoredAccount>>;
+    async fn create_auth_token(&self, account_id: &AccountId) -> Result<AuthToken>;
+    async fn account_for_auth_token(&self, auth_token: &AuthToken) -> Result<Option<AccountId>>;
+    async fn account_time_zone(&self, account_id: &AccountId) -> Result<Tz>;
+    async fn set_account_time_zone(&self, account_id: &AccountId, time_zone: Tz) -> Result<()>;
+}
+
+#[async_trait]
+pub trait DeviceRepository {
+    async fn devices_for_account(&self, account_id: &AccountId) -> Result<Vec<Device>>;
+    /// Returns `None` if the device does not exist or is owned by another account.
+    async fn device_for_account(
+        &self,
+        account_id: &AccountId,
+        device_id: DeviceId,
+    ) -> Result<Option<Device>>;
+    async fn create_device(
+        &self,
+        account_id: &AccountId,
+        name: &str,
+        effect: f64,
+    ) -> Result<Device>;
+    /// Returns false if the device does not exist or is owned by another account.
+    async fn delete_device(&self, account_id: &AccountId, device_id: DeviceId) -> Result<bool>;
+}
+
+#[async_trait]
+pub trait TaskRepository {
+    /// The tasks of the account that have not ended before `now`.
+    async fn tasks_for_account(
+        &self,
+        account_id: &AccountId,
+        now: DateTimeUtc,
+    ) -> Result<Vec<Task>>;
+    /// Returns `None` if the task does not exist or is owned by another account.
+    async fn task_for_account(
+        &self,
+        account_id: &AccountId,
+        task_id: TaskId,
+    ) -> Result<Option<Task>>;
+    /// Returns `None` if the device does not exist or is owned by another account.
+    async fn create_task(
+        &self,
+        account_id: &AccountId,
+        device_id: DeviceId,
+        timespan: &Timespan,
+        duration: Milliseconds,
+    ) -> Result<Option<Task>>;
+    /// Returns false if the task does not exist or is owned by another account.
+    async fn delete_task(&self, account_id: &AccountId, task_id: TaskId) -> Result<bool>;
+    /// Pins the task and moves its event to `start_time`.
+    ///
+    /// Returns false if the task does not exist or is owned by another account.
+    async fn pin_task(
+        &self,
+        account_id: &AccountId,
+        task_id: TaskId,
+        start_time: DateTimeUtc,
+    ) -> Result<bool>;
+    /// Removes the pin of the task.
+    ///
+    /// Returns false if the task does not exist or is owned by another account.
+    async fn unpin_task(&self, account_id: &AccountId, task_id: TaskId) -> Result<bool>;
+    /// Removes all exclusions of the task.
+    ///
+    /// Returns false if the task does not exist or is owned by another account.
+    async fn clear_exclusions(&self, account_id: &AccountId, task_id: TaskId) -> Result<bool>;
+    /// Returns false if the task does not exist or is owned by another account.
+    async fn add_exclusion(
+        &self,
+        account_id: &AccountId,
+        task_id: TaskId,
+        timespan: &Timespan,
+    ) -> Result<bool>;
+    /// The tasks that are not pinned and can still be completed after `now`.
+    async fn tasks_for_scheduling(&self, now: DateTimeUtc) -> Result<Vec<TaskForScheduler>>;
+    /// The pinned tasks that have not ended before `now`.
+    async fn pinned_tasks(&self, now: DateTimeUtc) -> Result<Vec<PinnedTask>>;
+}
+
+#[async_trait]
+pub trait EventRepository {
+    /// The events of the account's tasks that have not ended before `now`.
+    async fn events_for_account(
+        &self,
+        account_id: &AccountId,
+        now: DateTimeUtc,
+    ) -> Result<Vec<Event>>;
+    /// The first event of the device that starts after `now`.
+    async fn event_for_device(
+        &self,
+        account_id: &AccountId,
+        device_id: DeviceId,
+        now: DateTimeUtc,
+    ) -> Result<Option<Event>>;
+    /// The account's events that start within `timespan`, ordered by start time.
+    async fn report_entries(
+        &self,
+        account_id: &AccountId,
+        timespan: &Timespan,
+    ) -> Result<Vec<ReportEntry>>;
+    // Events are created by the scheduler, this is only used by the tests
+    #[allow(dead_code)]
+    async fn create_event(&self, task_id: TaskId, start_time: DateTimeUtc) -> Result<Event>;
+    /// Creates or moves the events of the tasks in one transaction.
+    ///
+    /// Every new start time is recorded in the event history, along with the run that caused it, if any.
+    async fn upsert_events(
+        &self,
+        events: &[UnpublishedEvent],
+        run_id: Option<SchedulingRunId>,
+    ) -> Result<()>;
+    /// Why the scheduler chose the start time of the account's event,
+    /// `None` if the event does not exist or has no explanation.
+    async fn event_explanation(
+        &self,
+        account_id: &AccountId,
+        event_id: EventId,
+    ) -> Result<Option<EventExplanation>>;
+}
+
+#[async_trait]
+pub trait SchedulingRunRepository {
+    async fn record_scheduling_run(&self, run: &NewSchedulingRun) -> Result<SchedulingRunId>;
+    /// The latest runs, newest first.
+    async fn scheduling_runs(&self, limit: u32) -> Result<Vec<SchedulingRun>>;
+    /// The run together with its input graph.
+    async fn scheduling_run(
+        &self,
+        run_id: SchedulingRunId,
+    ) -> Result<Option<(SchedulingRun, DiscreteGraph)>>;
+    /// The events that were created or moved by the run.
+    async fn event_changes(&self, run_id: SchedulingRunId) -> Result<Vec<EventChange>>;
+}
+
+#[async_trait]
+pub trait CarbonIntensityRepository {
+    /// Replaces the carbon intensity series, which is in gCO2/kWh per timeslot.
+    async fn set_carbon_intensity(&self, series: &DiscreteGraph) -> Result<()>;
+    /// `None` if no series has been ingested.
+    async fn carbon_intensity(&self) -> Result<Option<DiscreteGraph>>;
+}
+
+#[async_trait]
+pub trait BaselineRepository {
+    /// Replaces the account's baseline consumption profile.
+    async fn set_baseline(&self, account_id: &AccountId, profile: &DailyProfile) -> Result<()>;
+    /// `None` if the account has no baseline consumption.
+    async fn baseline(&self, account_id: &AccountId) -> Result<Option<DailyProfile>>;
+    /// Returns false if the account had no baseline consumption.
+    async fn delete_baseline(&self, account_id: &AccountId) -> Result<bool>;
+    /// The baseline consumption of every account that has one.
+    async fn baselines(&self) -> Result<Vec<AccountBaseline>>;
+}
+
+#[async_trait]
+pub trait CalendarRepository {
+    /// The events of the account, or only those of one of its devices, ordered by their start.
+    async fn calendar_entries(
+        &self,
+        account_id: &AccountId,
+        device_id: Option<DeviceId>,
+    ) -> Result<Vec<CalendarEntry>>;
+    /// Returns `None` if the device does not exist or is owned by another account.
+    async fn create_calendar_feed(
+        &self,
+        account_id: &AccountId,
+        device_id: Option<DeviceId>,
+    ) -> Result<Option<CalendarFeed>>;
+    async fn calendar_feeds(&self, account_id: &AccountId) -> Result<Vec<CalendarFeed>>;
+    /// Returns false if the feed does not exist or is owned by another account.
+    async fn delete_calendar_feed(
+        &self,
+        account_id: &AccountId,
+        secret: &CalendarFeedSecret,
+    ) -> Result<bool>;
+    /// The feed and the account that owns it, `None` if the feed has been revoked.
+    async fn calendar_feed(
+        &self,
+        secret: &CalendarFeedSecret,
+    ) -> Result<Option<(AccountId, CalendarFeed)>>;
+}
+
+/// All data stored by the backend.
+#[async_trait]
+pub trait Repository:
+    AccountRepository
+    + DeviceRepository
+    + TaskRepository
+    + EventRepository
+    + SchedulingRunRepository
+    + CalendarRepository
+    + CarbonIntensityRepository
+    + BaselineRepository
+    + Send
+    + Sync
+{
+    /// Checks that the database can be reached.
+    async fn ping(&self) -> Result<()>;
+    fn pool_status(&self) -> PoolStatus;
+    async fn close(&self);
+}
+
+/// Connects to the database and runs its migrations.
+///
+/// SQLite is used for `sqlite:` urls and PostgreSQL for `postgres:` and `postgresql:` urls.
+pub async fn connect(database_url: &str) -> Result<Arc<dyn Repository>> {
+    let repository: Arc<dyn Repository> = if database_url.starts_with("sqlite:") {
+        let repository = SqliteRepository::connect(database_url).await?;
+        repository.migrate().await?;
+        Arc::new(repository)
+    } else if database_url.starts_with("postgres:") || database_url.starts_with("postgresql:") {
+        let repository = PostgresRepository::connect(database_url).await?;
+        repository.migrate().await?;
+        Arc::new(repository)
+    } else {
+        bail!("Unsupported database url, it must start with sqlite: or postgres:");
+    };
+
+    Ok(repository)
+}
+
+/// Whether a stored task can be scheduled, as tasks created before input validation existed may not be.
+pub(super) fn is_schedulable(start: DateTimeUtc, end: DateTimeUtc, duration: Milliseconds) -> bool {
+    i64::from(duration) > 0
+        && start < end
+        && (end - start).num_milliseconds() >= i64::from(duration)
+}
@@ -0,0 +1,1492 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use itertools::Itertools;
+use protocol::{
+    accounts::AuthToken,
+    baseline::DailyProfile,
+    calendar::{CalendarFeed, CalendarFeedSecret},
+    devices::{Device, DeviceId},
+    events::{Event, EventExplanation, EventId},
+    graph::DiscreteGraph,
+    scheduling::{EventChange, SchedulingRun, SchedulingRunId},
+    tasks::{Task, TaskId},
+    time::{DateTimeUtc, Milliseconds, Timespan},
+};
+use sqlx::{sqlite::SqlitePoolOptions, SqliteConnection, SqlitePool};
+use tracing::{event, Level};
+
+use super::{
+    account::AccountId,
+    repository::{
+        is_schedulable, AccountBaseline, AccountRepository, BaselineRepository, CalendarEntry,
+        CalendarRepository, CarbonIntensityRepository, DeviceRepository, EventRepository,
+        NewSchedulingRun, PinnedTask, PoolStatus, ReportEntry, Repository, SchedulingRunRepository,
+        StoredAccount, TaskRepository,
+    },
+};
+use crate::scheduling::{
+    task_for_scheduler::TaskForScheduler, unpublished_event::UnpublishedEvent,
+};
+
+/// Stores the data in SQLite.
+pub struct SqliteRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        SqliteRepository { pool }
+    }
+
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let pool = SqlitePoolOptions::new().connect(database_url).await?;
+        Ok(SqliteRepository::new(pool))
+    }
+
+    pub async fn migrate(&self) -> Result<()> {
+        sqlx::migrate!("./migrations").run(&self.pool).await?;
+        Ok(())
+    }
+
+    /// A new in-memory database with the migrations applied.
+    #[cfg(test)]
+    pub async fn in_memory() -> Result<Self> {
+        let repository = SqliteRepository::connect("sqlite::memory:").await?;
+        repository.migrate().await?;
+        Ok(repository)
+    }
+
+    /// The exclusions of the tasks, ordered by their start.
+    async fn exclusions(&self, account_id: Option<&AccountId>) -> Result<Vec<(TaskId, Timespan)>> {
+        let exclusions = sqlx::query!(
+            r#"
+            SELECT TaskExclusions.task_id as "task_id: TaskId", TaskExclusions.timespan_start, TaskExclusions.timespan_end
+            FROM TaskExclusions
+            JOIN Tasks ON TaskExclusions.task_id == Tasks.id
+            JOIN Devices ON Tasks.device_id == Devices.id
+            WHERE ? IS NULL OR Devices.account_id == ?
+            ORDER BY TaskExclusions.timespan_start
+            "#,
+            account_id,
+            account_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|e| {
+            (
+                e.task_id,
+                Timespan::new_from_naive(e.timespan_start, e.timespan_end),
+            )
+        })
+        .collect();
+
+        Ok(exclusions)
+    }
+}
+
+fn to_utc(date_time: NaiveDateTime) -> DateTimeUtc {
+    Utc.from_utc_datetime(&date_time)
+}
+
+/// Creates or moves the events of the tasks, unchanged events only get their explanation updated.
+///
+/// Every new start time is recorded in `EventHistory`, along with the run that caused it, if any.
+async fn upsert_events(
+    connection: &mut SqliteConnection,
+    events: &[UnpublishedEvent],
+    run_id: Option<SchedulingRunId>,
+) -> Result<()> {
+    for event in events {
+        let current_start_time = sqlx::query_scalar!(
+            r#"
+            SELECT start_time
+            FROM Events
+            WHERE task_id == ?
+            "#,
+            event.task_id
+        )
+        .fetch_optional(&mut *connection)
+        .await?;
+
+        let explanation = event
+            .explanation
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+
+        if current_start_time.is_some_and(|start_time| to_utc(start_time) == event.start_time) {
+            // The start time is kept, but the reasons for it may have changed
+            sqlx::query!(
+                r#"
+                UPDATE Events
+                SET explanation = ?
+                WHERE task_id == ?
+                "#,
+                explanation,
+                event.task_id
+            )
+            .execute(&mut *connection)
+            .await?;
+            continue;
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO Events (task_id, start_time, explanation)
+            VALUES (?, ?, ?)
+            ON CONFLICT (task_id) DO UPDATE SET start_time = excluded.start_time, explanation = excluded.explanation
+            "#,
+            event.task_id,
+            event.start_time,
+            explanation,
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        let changed_at = Utc::now();
+        sqlx::query!(
+            r#"
+            INSERT INTO EventHistory (task_id, start_time, scheduling_run_id, changed_at)
+            VALUES (?, ?, ?, ?)
+            "#,
+            event.task_id,
+            event.start_time,
+            run_id,
+            changed_at
+        )
+        .execute(&mut *connection)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Whether the task belongs to one of the account's devices.
+///
+/// Every task that is changed on behalf of an account is checked here.
+async fn owns_task(
+    connection: &mut SqliteConnection,
+    account_id: &AccountId,
+    task_id: TaskId,
+) -> Result<bool> {
+    let owned = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM Tasks
+            JOIN Devices ON Tasks.device_id == Devices.id
+            WHERE Tasks.id == ? AND Devices.account_id == ?
+        ) as "owned!: bool"
+        "#,
+        task_id,
+        account_id
+    )
+    .fetch_one(&mut *connection)
+    .await?;
+
+    Ok(owned)
+}
+
+/// Whether the device belongs to the account.
+async fn owns_device(
+    connection: &mut SqliteConnection,
+    account_id: &AccountId,
+    device_id: DeviceId,
+) -> Result<bool> {
+    let owned = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM Devices
+            WHERE id == ? AND account_id == ?
+        ) as "owned!: bool"
+        "#,
+        device_id,
+        account_id
+    )
+    .fetch_one(&mut *connection)
+    .await?;
+
+    Ok(owned)
+}
+
+#[async_trait]
+impl AccountRepository for SqliteRepository {
+    async fn create_account(
+        &self,
+        username: &str,
+        password_hash: &str,
+    ) -> Result<Option<AccountId>> {
+        let result = sqlx::query_scalar!(
+            r#"
+            INSERT INTO Accounts (username, password_hash)
+            VALUES (?, ?)
+            RETURNING id as "id: AccountId"
+            "#,
+            username,
+            password_hash
+        )
+        .fetch_one(&self.pool)
+        .await;
+
+        match result {
+            Ok(account_id) => Ok(Some(account_id)),
+            Err(e)
+                if e.as_database_error()
+                    .is_some_and(|e| e.is_unique_violation()) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn account_by_username(&self, username: &str) -> Result<Option<StoredAccount>> {
+        let account = sqlx::query_as!(
+            StoredAccount,
+            r#"
+            SELECT id as "id: AccountId", password_hash
+            FROM Accounts
+            WHERE username = ?
+            "#,
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(account)
+    }
+
+    async fn create_auth_token(&self, account_id: &AccountId) -> Result<AuthToken> {
+        let auth_token = AuthToken::new();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO AuthTokens (id, account_id)
+            VALUES (?, ?)
+            "#,
+            auth_token,
+            account_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(auth_token)
+    }
+
+    async fn account_for_auth_token(&self, auth_token: &AuthToken) -> Result<Option<AccountId>> {
+        let account_id = sqlx::query_scalar!(
+            r#"
+            SELECT account_id as "id: AccountId"
+            FROM AuthTokens
+            WHERE id = ?
+            "#,
+            auth_token
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(account_id)
+    }
+
+    async fn account_time_zone(&self, account_id: &AccountId) -> Result<Tz> {
+        let time_zone = sqlx::query_scalar!(
+            r#"
+            SELECT time_zone
+            FROM Accounts
+            WHERE id = ?
+            "#,
+            account_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        time_zone.parse().map_err(|error| anyhow!("{}", error))
+    }
+
+    async fn set_account_time_zone(&self, account_id: &AccountId, time_zone: Tz) -> Result<()> {
+        let name = time_zone.name();
+        sqlx::query!(
+            r#"
+            UPDATE Accounts
+            SET time_zone = ?
+            WHERE id = ?
+            "#,
+            name,
+            account_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DeviceRepository for SqliteRepository {
+    async fn devices_for_account(&self, account_id: &AccountId) -> Result<Vec<Device>> {
+        let devices = sqlx::query_as!(
+            Device,
+            r#"
+            SELECT id, name, effect
+            FROM Devices
+            WHERE account_id = ?
+            ORDER BY id
+            "#,
+            account_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(devices)
+    }
+
+    async fn device_for_account(
+        &self,
+        account_id: &AccountId,
+        device_id: DeviceId,
+    ) -> Result<Option<Device>> {
+        let device = sqlx::query_as!(
+            Device,
+            r#"
+            SELECT id, name, effect
+            FROM Devices
+            WHERE account_id == ? AND id == ?
+            "#,
+            account_id,
+            device_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(device)
+    }
+
+    async fn create_device(
+        &self,
+        account_id: &AccountId,
+        name: &str,
+        effect: f64,
+    ) -> Result<Device> {
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO Devices (name, effect, account_id)
+            VALUES (?, ?, ?)
+            RETURNING id as "id: DeviceId"
+            "#,
+            name,
+            effect,
+            account_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Device {
+            id,
+            name: name.to_string(),
+            effect,
+        })
+    }
+
+    async fn delete_device(&self, account_id: &AccountId, device_id: DeviceId) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM Devices
+            WHERE id == ? AND account_id == ?
+            "#,
+            device_id,
+            account_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[async_trait]
+impl TaskRepository for SqliteRepository {
+    async fn tasks_for_account(
+        &self,
+        account_id: &AccountId,
+        now: DateTimeUtc,
+    ) -> Result<Vec<Task>> {
+        let tasks = sqlx::query!(
+            r#"
+            SELECT Tasks.id as "id: TaskId", Tasks.timespan_start, Tasks.timespan_end, Tasks.duration as "duration: Milliseconds", Tasks.device_id as "device_id: DeviceId", Tasks.pinned_start
+            FROM Tasks
+            JOIN Devices ON Tasks.device_id == Devices.id
+            WHERE Devices.account_id = ? AND (julianday(Tasks.timespan_end, 'utc') >= julianday(?, 'utc'))
+            ORDER BY Tasks.id
+            "#,
+            account_id,
+            now
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut exclusions = self
+            .exclusions(Some(account_id))
+            .await?
+            .into_iter()
+            .into_group_map();
+
+        let tasks = tasks
+            .into_iter()
+            .map(|t| Task {
+                id: t.id,
+                timespan: Timespan::new_from_naive(t.timespan_start, t.timespan_end),
+                duration: t.duration,
+                device_id: t.device_id,
+                pinned_start: t.pinned_start.map(to_utc),
+                exclusions: exclusions.remove(&t.id).unwrap_or_default(),
+            })
+            .collect();
+
+        Ok(tasks)
+    }
+
+    async fn task_for_account(
+        &self,
+        account_id: &AccountId,
+        task_id: TaskId,
+    ) -> Result<Option<Task>> {
+        let mut connection = self.pool.acquire().await?;
+        if !owns_task(&mut connection, account_id, task_id).await? {
+            return Ok(None);
+        }
+
+        let task = sqlx::query!(
+            r#"
+            SELECT id as "id: TaskId", timespan_start, timespan_end, duration as "duration: Milliseconds", device_id as "device_id: DeviceId", pinned_start
+            FROM Tasks
+            WHERE id == ?
+            "#,
+            task_id
+        )
+        .fetch_one(&mut *connection)
+        .await?;
+
+        let exclusions = sqlx::query!(
+            r#"
+            SELECT timespan_start, timespan_end
+            FROM TaskExclusions
+            WHERE task_id == ?
+            ORDER BY timespan_start
+            "#,
+            task_id
+        )
+        .fetch_all(&mut *connection)
+        .await?
+        .into_iter()
+        .map(|e| Timespan::new_from_naive(e.timespan_start, e.timespan_end))
+        .collect();
+
+        Ok(Some(Task {
+            id: task.id,
+            timespan: Timespan::new_from_naive(task.timespan_start, task.timespan_end),
+            duration: task.duration,
+            device_id: task.device_id,
+            pinned_start: task.pinned_start.map(to_utc),
+            exclusions,
+        }))
+    }
+
+    async fn create_task(
+        &self,
+        account_id: &AccountId,
+        device_id: DeviceId,
+        timespan: &Timespan,
+        duration: Milliseconds,
+    ) -> Result<Option<Task>> {
+        let mut connection = self.pool.acquire().await?;
+        if !owns_device(&mut connection, account_id, device_id).await? {
+            return Ok(None);
+        }
+
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO Tasks (timespan_start, timespan_end, duration, device_id)
+            VALUES (?, ?, ?, ?)
+            RETURNING id as "id: TaskId"
+            "#,
+            timespan.start,
+            timespan.end,
+            duration,
+            device_id
+        )
+        .fetch_one(&mut *connection)
+        .await?;
+
+        Ok(Some(Task {
+            id,
+            timespan: timespan.clone(),
+            duration,
+            device_id,
+            pinned_start: None,
+            exclusions: Vec::new(),
+        }))
+    }
+
+    async fn delete_task(&self, account_id: &AccountId, task_id: TaskId) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        if !owns_task(&mut transaction, account_id, task_id).await? {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM Tasks
+            WHERE id == ?
+            "#,
+            task_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn pin_task(
+        &self,
+        account_id: &AccountId,
+        task_id: TaskId,
+        start_time: DateTimeUtc,
+    ) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        if !owns_task(&mut transaction, account_id, task_id).await? {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE Tasks
+            SET pinned_start = ?
+            WHERE id == ?
+            "#,
+            start_time,
+            task_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        let event = UnpublishedEvent {
+            task_id,
+            start_time,
+            explanation: None,
+        };
+        upsert_events(&mut transaction, &[event], None).await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn unpin_task(&self, account_id: &AccountId, task_id: TaskId) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        if !owns_task(&mut transaction, account_id, task_id).await? {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE Tasks
+            SET pinned_start = NULL
+            WHERE id == ?
+            "#,
+            task_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn clear_exclusions(&self, account_id: &AccountId, task_id: TaskId) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        if !owns_task(&mut transaction, account_id, task_id).await? {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM TaskExclusions
+            WHERE task_id == ?
+            "#,
+            task_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn add_exclusion(
+        &self,
+        account_id: &AccountId,
+        task_id: TaskId,
+        timespan: &Timespan,
+    ) -> Result<bool> {
+        let mut transaction = self.pool.begin().await?;
+        if !owns_task(&mut transaction, account_id, task_id).await? {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO TaskExclusions (task_id, timespan_start, timespan_end)
+            VALUES (?, ?, ?)
+            "#,
+            task_id,
+            timespan.start,
+            timespan.end
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    async fn tasks_for_scheduling(&self, now: DateTimeUtc) -> Result<Vec<TaskForScheduler>> {
+        // TODO: Filter out tasks that have a started event
+        let tasks = sqlx::query!(
+            r#"
+            SELECT Tasks.id as "id: TaskId", Tasks.timespan_start, Tasks.timespan_end, Tasks.duration as "duration: Milliseconds", Devices.effect as "effect: f64"
+            FROM Tasks
+            JOIN Devices ON Tasks.device_id == Devices.id
+            WHERE Tasks.pinned_start IS NULL AND Tasks.timespan_end >= ? AND ((julianday(Tasks.timespan_end, 'utc') - julianday(?, 'utc')) * 24 * 60 * 60 * 1000) >= duration
+            ORDER BY Tasks.id
+            "#,
+            now,
+            now)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut exclusions = self.exclusions(None).await?.into_iter().into_group_map();
+
+        let tasks = tasks
+            .into_iter()
+            .filter(|t| {
+                let valid = is_schedulable(
+                    to_utc(t.timespan_start),
+                    to_utc(t.timespan_end),
+                    t.duration,
+                );
+                if !valid {
+                    event!(target: "backend", Level::WARN, "Skipping invalid task with id: {}", t.id);
+                }
+                valid
+            })
+            .map(|t| {
+                let mut task = TaskForScheduler::new(
+                    t.id,
+                    Timespan::new_from_naive(t.timespan_start, t.timespan_end),
+                    t.duration,
+                    t.effect,
+                );
+                task.exclusions = exclusions.remove(&t.id).unwrap_or_default();
+                task
+            })
+            .collect();
+
+        Ok(tasks)
+    }
+
+    async fn pinned_tasks(&self, now: DateTimeUtc) -> Result<Vec<PinnedTask>> {
+        let tasks = sqlx::query!(
+            r#"
+            SELECT Tasks.id as "id: TaskId", Tasks.timespan_start, Tasks.timespan_end, Tasks.duration as "duration: Milliseconds", Tasks.pinned_start as "pinned_start!", Devices.effect as "effect: f64"
+            FROM Tasks
+            JOIN Devices ON Tasks.device_id == Devices.id
+            WHERE Tasks.pinned_start IS NOT NULL AND Tasks.timespan_end >= ?
+            ORDER BY Tasks.id
+            "#,
+            now)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let tasks = tasks
+            .into_iter()
+            .map(|t| PinnedTask {
+                task: TaskForScheduler {
+                    id: t.id,
+                    timespan: Timespan::new_from_naive(t.timespan_start, t.timespan_end),
+                    duration: t.duration,
+                    effect: t.effect,
+                    exclusions: Vec::new(),
+                },
+                start_time: to_utc(t.pinned_start),
+            })
+            .collect();
+
+        Ok(tasks)
+    }
+}
+
+#[async_trait]
+impl EventRepository for SqliteRepository {
+    async fn events_for_account(
+        &self,
+        account_id: &AccountId,
+        now: DateTimeUtc,
+    ) -> Result<Vec<Event>> {
+        let events = sqlx::query!(
+            r#"
+            SELECT Events.id as "id: EventId", Events.task_id as "task_id: TaskId", Events.start_time
+            FROM Events
+            JOIN Tasks ON Events.task_id == Tasks.id
+            JOIN Devices ON Tasks.device_id == Devices.id
+            WHERE Devices.account_id = ? AND (julianday(Tasks.timespan_end, 'utc') >= julianday(?, 'utc'))
+            ORDER BY Events.id
+            "#,
+            account_id,
+            now
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|e| Event {
+            id: e.id,
+            task_id: e.task_id,
+            start_time: to_utc(e.start_time),
+        })
+        .collect();
+
+        Ok(events)
+    }
+
+    async fn event_for_device(
+        &self,
+        account_id: &AccountId,
+        device_id: DeviceId,
+        now: DateTimeUtc,
+    ) -> Result<Option<Event>> {
+        let event = sqlx::query!(
+            r#"
+            SELECT Events.id as "id: EventId", Events.task_id as "task_id: TaskId", Events.start_time
+            FROM Events
+            JOIN Tasks ON Events.task_id == Tasks.id
+            JOIN Devices ON Tasks.device_id == Devices.id
+            WHERE Devices.account_id = ? AND Devices.id = ? AND Events.start_time >= ?
+            ORDER BY Events.start_time
+            LIMIT 1
+            "#,
+            account_id,
+            device_id,
+            now
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|e| Event {
+            id: e.id,
+            task_id: e.task_id,
+            start_time: to_utc(e.start_time),
+        });
+
+        Ok(event)
+    }
+
+    async fn report_entries(
+        &self,
+        account_id: &AccountId,
+        timespan: &Timespan,
+    ) -> Result<Vec<ReportEntry>> {
+        let entries = sqlx::query!(
+            r#"
+            SELECT Devices.id as "device_id: DeviceId", Devices.name, Devices.effect, Tasks.timespan_start, Events.start_time, Tasks.duration as "duration: Milliseconds"
+            FROM Events
+            JOIN Tasks ON Events.task_id == Tasks.id
+            JOIN Devices ON Tasks.device_id == Devices.id
+            WHERE Devices.account_id == ?
+                AND julianday(Events.start_time, 'utc') >= julianday(?, 'utc')
+                AND julianday(Events.start_time, 'utc') < julianday(?, 'utc')
+            ORDER BY Events.start_time, Events.task_id
+            "#,
+            account_id,
+            timespan.start,
+            timespan.end
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|e| ReportEntry {
+            device_id: e.device_id,
+            device_name: e.name,
+            effect: e.effect,
+            timespan_start: to_utc(e.timespan_start),
+            start_time: to_utc(e.start_time),
+            duration: e.duration,
+        })
+        .collect();
+
+        Ok(entries)
+    }
+
+    async fn create_event(&self, task_id: TaskId, start_time: DateTimeUtc) -> Result<Event> {
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO Events (task_id, start_time) VALUES (?, ?)
+            RETURNING id as "id: EventId"
+            "#,
+            task_id,
+            start_time
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Event {
+            id,
+            task_id,
+            start_time,
+        })
+    }
+
+    async fn upsert_events(
+        &self,
+        events: &[UnpublishedEvent],
+        run_id: Option<SchedulingRunId>,
+    ) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        upsert_events(&mut transaction, events, run_id).await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn event_explanation(
+        &self,
+        account_id: &AccountId,
+        event_id: EventId,
+    ) -> Result<Option<EventExplanation>> {
+        let explanation = sqlx::query_scalar!(
+            r#"
+            SELECT Events.explanation
+            FROM Events
+            JOIN Tasks ON Events.task_id == Tasks.id
+            JOIN Devices ON Tasks.device_id == Devices.id
+            WHERE Devices.account_id == ? AND Events.id == ?
+            "#,
+            account_id,
+            event_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .flatten();
+
+        Ok(explanation
+            .map(|explanation| serde_json::from_str(&explanation))
+            .transpose()?)
+    }
+}
+
+#[async_trait]
+impl SchedulingRunRepository for SqliteRepository {
+    async fn record_scheduling_run(&self, run: &NewSchedulingRun) -> Result<SchedulingRunId> {
+        let algorithm = serde_json::to_string(&run.algorithm)?;
+        let input_graph = serde_json::to_string(&run.input_graph)?;
+
+        let run_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO SchedulingRuns (algorithm, input_graph, task_count, cost, emissions, started_at, duration, error)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING id as "id: SchedulingRunId"
+            "#,
+            algorithm,
+            input_graph,
+            run.task_count,
+            run.cost,
+            run.emissions,
+            run.started_at,
+            run.duration,
+            run.error
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(run_id)
+    }
+
+    async fn scheduling_runs(&self, limit: u32) -> Result<Vec<SchedulingRun>> {
+        let runs = sqlx::query!(
+            r#"
+            SELECT id as "id: SchedulingRunId", algorithm, task_count, cost, emissions, started_at, duration as "duration: Milliseconds", error
+            FROM SchedulingRuns
+            ORDER BY id DESC
+            LIMIT ?
+            "#,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|r| {
+            Ok(SchedulingRun {
+                id: r.id,
+                algorithm: serde_json::from_str(&r.algorithm)?,
+                task_count: r.task_count,
+                cost: r.cost,
+                emissions: r.emissions,
+                started_at: to_utc(r.started_at),
+                duration: r.duration,
+                error: r.error,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+        Ok(runs)
+    }
+
+    async fn scheduling_run(
+        &self,
+        run_id: SchedulingRunId,
+    ) -> Result<Option<(SchedulingRun, DiscreteGraph)>> {
+        let Some(run) = sqlx::query!(
+            r#"
+            SELECT id as "id: SchedulingRunId", algorithm, input_graph, task_count, cost, emissions, started_at, duration as "duration: Milliseconds", error
+            FROM SchedulingRuns
+            WHERE id == ?
+            "#,
+            run_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let scheduling_run = SchedulingRun {
+            id: run.id,
+            algorithm: serde_json::from_str(&run.algorithm)?,
+            task_count: run.task_count,
+            cost: run.cost,
+            emissions: run.emissions,
+            started_at: to_utc(run.started_at),
+            duration: run.duration,
+            error: run.error,
+        };
+        let input_graph = serde_json::from_str(&run.input_graph)?;
+
+        Ok(Some((scheduling_run, input_graph)))
+    }
+
+    async fn event_changes(&self, run_id: SchedulingRunId) -> Result<Vec<EventChange>> {
+        let changes = sqlx::query!(
+            r#"
+            SELECT EventHistory.task_id as "task_id: TaskId", EventHistory.start_time, (
+                SELECT Previous.start_time
+                FROM EventHistory AS Previous
+                WHERE Previous.task_id == EventHistory.task_id AND Previous.id < EventHistory.id
+                ORDER BY Previous.id DESC
+                LIMIT 1
+            ) as "previous_start_time?: NaiveDateTime"
+            FROM EventHistory
+            WHERE EventHistory.scheduling_run_id == ?
+            ORDER BY EventHistory.task_id
+            "#,
+            run_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|c| EventChange {
+            task_id: c.task_id,
+            previous_start_time: c.previous_start_time.map(to_utc),
+            start_time: to_utc(c.start_time),
+        })
+        .collect();
+
+        Ok(changes)
+    }
+}
+
+#[async_trait]
+impl BaselineRepository for SqliteRepository {
+    async fn set_baseline(&self, account_id: &AccountId, profile: &DailyProfile) -> Result<()> {
+        let profile = serde_json::to_string(profile)?;
+        sqlx::query!(
+            r#"
+            INSERT INTO BaselineConsumption (account_id, profile)
+            VALUES (?, ?)
+            ON CONFLICT (account_id) DO UPDATE SET profile = excluded.profile
+            "#,
+            account_id,
+            profile
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn baseline(&self, account_id: &AccountId) -> Result<Option<DailyProfile>> {
+        let profile = sqlx::query_scalar!(
+            r#"
+            SELECT profile
+            FROM BaselineConsumption
+            WHERE account_id = ?
+            "#,
+            account_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(profile
+            .map(|profile| serde_json::from_str(&profile))
+            .transpose()?)
+    }
+
+    async fn delete_baseline(&self, account_id: &AccountId) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM BaselineConsumption
+            WHERE account_id = ?
+            "#,
+            account_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn baselines(&self) -> Result<Vec<AccountBaseline>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT Accounts.time_zone, BaselineConsumption.profile
+            FROM BaselineConsumption
+            JOIN Accounts ON Accounts.id = BaselineConsumption.account_id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(AccountBaseline {
+                    time_zone: row
+                        .time_zone
+                        .parse()
+                        .map_err(|error| anyhow!("{}", error))?,
+                    profile: serde_json::from_str(&row.profile)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl CarbonIntensityRepository for SqliteRepository {
+    async fn set_carbon_intensity(&self, series: &DiscreteGraph) -> Result<()> {
+        let series = serde_json::to_string(series)?;
+        let created_at = Utc::now();
+
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM CarbonIntensity")
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO CarbonIntensity (series, created_at)
+            VALUES (?, ?)
+            "#,
+            series,
+            created_at
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn carbon_intensity(&self) -> Result<Option<DiscreteGraph>> {
+        let series = sqlx::query_scalar!(
+            r#"
+            SELECT series
+            FROM CarbonIntensity
+            ORDER BY id DESC
+            LIMIT 1
+            "#
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(series
+            .map(|series| serde_json::from_str(&series))
+            .transpose()?)
+    }
+}
+
+#[async_trait]
+impl CalendarRepository for SqliteRepository {
+    async fn calendar_entries(
+        &self,
+        account_id: &AccountId,
+        device_id: Option<DeviceId>,
+    ) -> Result<Vec<CalendarEntry>> {
+        let entries = sqlx::query!(
+            r#"
+            SELECT Events.task_id as "task_id: TaskId", Events.start_time, Tasks.duration as "duration: Milliseconds", Devices.name
+            FROM Events
+            JOIN Tasks ON Events.task_id == Tasks.id
+            JOIN Devices ON Tasks.device_id == Devices.id
+            WHERE Devices.account_id == ? AND (? IS NULL OR Devices.id == ?)
+            ORDER BY Events.start_time, Events.task_id
+            "#,
+            account_id,
+            device_id,
+            device_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|e| CalendarEntry {
+            task_id: e.task_id,
+            start_time: to_utc(e.start_time),
+            duration: e.duration,
+            device_name: e.name,
+        })
+        .collect();
+
+        Ok(entries)
+    }
+
+    async fn create_calendar_feed(
+        &self,
+        account_id: &AccountId,
+        device_id: Option<DeviceId>,
+    ) -> Result<Option<CalendarFeed>> {
+        let mut connection = self.pool.acquire().await?;
+        if let Some(device_id) = device_id {
+            if !owns_device(&mut connection, account_id, device_id).await? {
+                return Ok(None);
+            }
+        }
+
+        let secret = CalendarFeedSecret::new();
+        sqlx::query!(
+            r#"
+            INSERT INTO CalendarFeeds (id, account_id, device_id)
+            VALUES (?, ?, ?)
+            "#,
+            secret,
+            account_id,
+            device_id
+        )
+        .execute(&mut *connection)
+        .await?;
+
+        Ok(Some(CalendarFeed { secret, device_id }))
+    }
+
+    async fn calendar_feeds(&self, account_id: &AccountId) -> Result<Vec<CalendarFeed>> {
+        let feeds = sqlx::query!(
+            r#"
+            SELECT id as "secret: CalendarFeedSecret", device_id as "device_id: DeviceId"
+            FROM CalendarFeeds
+            WHERE account_id == ?
+            ORDER BY rowid
+            "#,
+            account_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|f| CalendarFeed {
+            secret: f.secret,
+            device_id: f.device_id,
+        })
+        .collect();
+
+        Ok(feeds)
+    }
+
+    async fn delete_calendar_feed(
+        &self,
+        account_id: &AccountId,
+        secret: &CalendarFeedSecret,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM CalendarFeeds
+            WHERE id == ? AND account_id == ?
+            "#,
+            secret,
+            account_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn calendar_feed(
+        &self,
+        secret: &CalendarFeedSecret,
+    ) -> Result<Option<(AccountId, CalendarFeed)>> {
+        let feed = sqlx::query!(
+            r#"
+            SELECT account_id as "account_id: AccountId", device_id as "device_id: DeviceId"
+            FROM CalendarFeeds
+            WHERE id == ?
+            "#,
+            secret
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|f| {
+            let feed = CalendarFeed {
+                secret: secret.clone(),
+                device_id: f.device_id,
+            };
+            (f.account_id, feed)
+        });
+
+        Ok(feed)
+    }
+}
+
+#[async_trait]
+impl Repository for SqliteRepository {
+    async fn ping(&self) -> Result<()> {
+        sqlx::query!("SELECT 1 as one")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    fn pool_status(&self) -> PoolStatus {
+        PoolStatus {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+        }
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use protocol::scheduling::Algorithm;
+
+    use super::*;
+
+    async fn account_with_device(
+        repository: &SqliteRepository,
+        username: &str,
+    ) -> (AccountId, Device) {
+        let account_id = repository
+            .create_account(username, "hash")
+            .await
+            .unwrap()
+            .unwrap();
+        let device = repository
+            .create_device(&account_id, "test", 1000.0)
+            .await
+            .unwrap();
+        (account_id, device)
+    }
+
+    #[tokio::test]
+    async fn baselines_are_stored_per_account() {
+        let repository = SqliteRepository::in_memory().await.unwrap();
+        let (owner, _) = account_with_device(&repository, "owner").await;
+        let (other, _) = account_with_device(&repository, "other").await;
+        repository
+            .set_account_time_zone(&owner, chrono_tz::Europe::Copenhagen)
+            .await
+            .unwrap();
+
+        let profile = |watts| DailyProfile {
+            resolution_minutes: 720,
+            values: vec![watts, watts],
+        };
+        repository
+            .set_baseline(&owner, &profile(100.0))
+            .await
+            .unwrap();
+        repository
+            .set_baseline(&owner, &profile(200.0))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository.baseline(&owner).await.unwrap(),
+            Some(profile(200.0))
+        );
+        assert_eq!(repository.baseline(&other).await.unwrap(), None);
+
+        let baselines = repository.baselines().await.unwrap();
+        assert_eq!(baselines.len(), 1);
+        assert_eq!(baselines[0].time_zone, chrono_tz::Europe::Copenhagen);
+        assert_eq!(baselines[0].profile, profile(200.0));
+
+        assert!(!repository.delete_baseline(&other).await.unwrap());
+        assert!(repository.delete_baseline(&owner).await.unwrap());
+        assert!(repository.baselines().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn task_ownership_is_enforced() {
+        let repository = SqliteRepository::in_memory().await.unwrap();
+        let (owner, device) = account_with_device(&repository, "owner").await;
+        let (other, _) = account_with_device(&repository, "other").await;
+
+        let now = Utc::now();
+        let timespan = Timespan::new(now, now + Duration::hours(4));
+        let duration = Duration::hours(1).into();
+
+        let created = repository
+            .create_task(&other, device.id, &timespan, duration)
+            .await
+            .unwrap();
+        assert!(created.is_none());
+
+        let task = repository
+            .create_task(&owner, device.id, &timespan, duration)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(repository
+            .task_for_account(&other, task.id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(!repository.pin_task(&other, task.id, now).await.unwrap());
+        assert!(!repository.unpin_task(&other, task.id).await.unwrap());
+        assert!(!repository.clear_exclusions(&other, task.id).await.unwrap());
+        assert!(!repository
+            .add_exclusion(&other, task.id, &timespan)
+            .await
+            .unwrap());
+        assert!(!repository.delete_task(&other, task.id).await.unwrap());
+
+        let owned = repository.task_for_account(&owner, task.id).await.unwrap();
+        assert_eq!(owned, Some(task));
+        let task_id = owned.unwrap().id;
+        assert!(repository.pin_task(&owner, task_id, now).await.unwrap());
+        assert!(repository.delete_task(&owner, task_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn tasks_for_scheduling_skips_pinned_and_expired_tasks() {
+        let repository = SqliteRepository::in_memory().await.unwrap();
+        let (account_id, device) = account_with_device(&repository, "owner").await;
+
+        let now = Utc::now();
+        let duration = Duration::hours(1).into();
+        let mut tasks = Vec::new();
+        for timespan in [
+            Timespan::new(now, now + Duration::hours(4)),
+            Timespan::new(now, now + Duration::hours(4)),
+            // Can no longer be completed
+            Timespan::new(now - Duration::hours(2), now + Duration::minutes(30)),
+        ] {
+            let task = repository
+                .create_task(&account_id, device.id, &timespan, duration)
+                .await
+                .unwrap()
+                .unwrap();
+            tasks.push(task);
+        }
+        repository
+            .pin_task(&account_id, tasks[1].id, now)
+            .await
+            .unwrap();
+
+        let scheduled = repository.tasks_for_scheduling(now).await.unwrap();
+        assert_eq!(
+            scheduled.iter().map(|task| task.id).collect::<Vec<_>>(),
+            vec![tasks[0].id]
+        );
+        assert_eq!(scheduled[0].effect, device.effect);
+
+        let pinned = repository.pinned_tasks(now).await.unwrap();
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(pinned[0].task.id, tasks[1].id);
+        assert_eq!(pinned[0].start_time, now);
+    }
+
+    #[tokio::test]
+    async fn upsert_events_records_changes() {
+        let repository = SqliteRepository::in_memory().await.unwrap();
+        let (account_id, device) = account_with_device(&repository, "owner").await;
+
+        let now = Utc::now();
+        let task = repository
+            .create_task(
+                &account_id,
+                device.id,
+                &Timespan::new(now, now + Duration::hours(4)),
+                Duration::hours(1).into(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut run_ids = Vec::new();
+        for start_time in [now, now, now + Duration::hours(1)] {
+            let run_id = repository
+                .record_scheduling_run(&NewSchedulingRun {
+                    algorithm: Algorithm::Naive,
+                    input_graph: DiscreteGraph::new(vec![0.0], Duration::hours(1), now),
+                    task_count: 1,
+                    cost: Some(0.0),
+                    emissions: None,
+                    started_at: now,
+                    duration: 0.into(),
+                    error: None,
+                })
+                .await
+                .unwrap();
+            let event = UnpublishedEvent {
+                task_id: task.id,
+                start_time,
+                explanation: None,
+            };
+            repository
+                .upsert_events(&[event], Some(run_id))
+                .await
+                .unwrap();
+            run_ids.push(run_id);
+        }
+
+        let events = repository
+            .events_for_account(&account_id, now)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].start_time, now + Duration::hours(1));
+
+        let mut changes = Vec::new();
+        for run_id in run_ids {
+            changes.push(repository.event_changes(run_id).await.unwrap());
+        }
+        assert_eq!(
+            changes,
+            vec![
+                vec![EventChange {
+                    task_id: task.id,
+                    previous_start_time: None,
+                    start_time: now,
+                }],
+                // The event did not move
+                vec![],
+                vec![EventChange {
+                    task_id: task.id,
+                    previous_start_time: Some(now),
+                    start_time: now + Duration::hours(1),
+                }],
+            ]
+        );
+    }
+}
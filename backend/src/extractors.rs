@@ -1 +1,3 @@
 pub mod auth;
+pub mod json;
+pub mod query;
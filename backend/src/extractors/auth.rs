@@ -1,19 +1,18 @@
 use axum::{
     async_trait,
     extract::FromRequestParts,
-    http::{request::Parts, HeaderMap, StatusCode},
+    http::{request::Parts, HeaderMap},
 };
 use protocol::accounts::AuthToken;
-use sqlx::SqlitePool;
 
-use crate::{data_model::account::AccountId, MyState};
+use crate::{data_model::account::AccountId, handlers::error::ApiError, MyState};
 
 // Account id
 pub struct Authentication(pub AccountId);
 
 #[async_trait]
 impl FromRequestParts<MyState> for Authentication {
-    type Rejection = (StatusCode, String);
+    type Rejection = ApiError;
 
     async fn from_request_parts(
         parts: &mut Parts,
@@ -21,17 +20,20 @@ impl FromRequestParts<MyState> for Authentication {
     ) -> Result<Self, Self::Rejection> {
         match get_auth_token(&parts.headers) {
             Some(token) => {
-                if let Some(account_id) = get_account_id_from_token(token, &state.pool).await {
+                let account_id = state
+                    .repository
+                    .account_for_auth_token(&token)
+                    .await
+                    .map_err(ApiError::Internal)?;
+                if let Some(account_id) = account_id {
                     Ok(Authentication(account_id))
                 } else {
-                    Err((
-                        StatusCode::UNAUTHORIZED,
+                    Err(ApiError::Unauthorized(
                         "Auth token is not in the database".to_string(),
                     ))
                 }
             }
-            _ => Err((
-                StatusCode::UNAUTHORIZED,
+            _ => Err(ApiError::Unauthorized(
                 "Auth token invalid or missing".to_string(),
             )),
         }
@@ -43,36 +45,28 @@ fn get_auth_token(headers: &HeaderMap) -> Option<AuthToken> {
     AuthToken::try_parse(string).ok()
 }
 
-async fn get_account_id_from_token(token: AuthToken, pool: &SqlitePool) -> Option<AccountId> {
-    sqlx::query_scalar!(
-        r#"
-        SELECT account_id as "id: AccountId"
-        FROM AuthTokens
-        WHERE id = ?
-        "#,
-        token
-    )
-    .fetch_optional(pool)
-    .await
-    .ok()?
-}
+/// Only lets requests with the configured admin token through.
+pub struct AdminAuthentication;
 
-pub async fn create_auth_token(
-    account_id: AccountId,
-    pool: &SqlitePool,
-) -> Result<AuthToken, sqlx::Error> {
-    let auth_token = AuthToken::new();
+#[async_trait]
+impl FromRequestParts<MyState> for AdminAuthentication {
+    type Rejection = ApiError;
 
-    sqlx::query!(
-        r#"
-        INSERT INTO AuthTokens (id, account_id)
-        VALUES (?, ?)
-        "#,
-        auth_token,
-        account_id
-    )
-    .execute(pool)
-    .await?;
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &MyState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(admin_token) = &state.admin_token else {
+            return Err(ApiError::Unauthorized(
+                "Admin endpoints are disabled, as no admin token is configured".to_string(),
+            ));
+        };
 
-    Ok(auth_token)
+        match parts.headers.get("X-Admin-Token") {
+            Some(token) if token.as_bytes() == admin_token.as_bytes() => Ok(AdminAuthentication),
+            _ => Err(ApiError::Unauthorized(
+                "Admin token invalid or missing".to_string(),
+            )),
+        }
+    }
 }
@@ -0,0 +1,8 @@
+use axum::extract::FromRequest;
+
+use crate::handlers::error::ApiError;
+
+/// Same as [axum::Json], but rejects malformed bodies with an [ApiError].
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct ApiJson<T>(pub T);
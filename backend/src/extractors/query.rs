@@ -0,0 +1,8 @@
+use axum::extract::FromRequestParts;
+
+use crate::handlers::error::ApiError;
+
+/// Same as [axum::extract::Query], but rejects malformed query strings with an [ApiError].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct ApiQuery<T>(pub T);
@@ -1,5 +1,11 @@
 pub mod accounts;
+pub mod baseline;
+pub mod calendar;
+pub mod carbon;
 pub mod devices;
+pub mod error;
 pub mod events;
+pub mod health;
+pub mod reports;
+pub mod scheduling;
 pub mod tasks;
-pub mod util;
@@ -2,19 +2,24 @@ use argon2::{
     password_hash::{rand_core::OsRng, SaltString},
     Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
 };
-use axum::{debug_handler, extract::State, http::StatusCode, Json};
-use protocol::accounts::{RegisterOrLoginRequest, RegisterOrLoginResponse};
+use axum::{debug_handler, extract::State, Json};
+use protocol::{
+    accounts::{AccountSettings, RegisterOrLoginRequest, RegisterOrLoginResponse},
+    errors::ErrorDetail,
+};
 
 use crate::{
-    data_model::account::AccountId, extractors::auth::create_auth_token,
-    handlers::util::internal_error, MyState,
+    extractors::{auth::Authentication, json::ApiJson},
+    handlers::error::{internal_error, ApiError},
+    local_time::parse_time_zone,
+    MyState,
 };
 
 #[debug_handler]
 pub async fn register_account(
     State(state): State<MyState>,
-    Json(register_request): Json<RegisterOrLoginRequest>,
-) -> Result<Json<RegisterOrLoginResponse>, (StatusCode, String)> {
+    ApiJson(register_request): ApiJson<RegisterOrLoginRequest>,
+) -> Result<Json<RegisterOrLoginResponse>, ApiError> {
     let password = register_request.password;
     let password_bytes = password.as_bytes();
     let salt = SaltString::generate(&mut OsRng);
@@ -26,22 +31,20 @@ pub async fn register_account(
         .map_err(internal_error)?
         .to_string();
 
-    let account_id = sqlx::query_scalar!(
-        r#"
-        INSERT INTO Accounts (username, password_hash)
-        VALUES (?, ?)
-        RETURNING id as "id: AccountId"
-        "#,
-        register_request.username,
-        password_hash
-    )
-    .fetch_one(&state.pool)
-    .await
-    .map_err(internal_error)?;
-
-    let auth_token = create_auth_token(account_id, &state.pool)
+    let account_id = state
+        .repository
+        .create_account(&register_request.username, &password_hash)
+        .await
+        .map_err(ApiError::Internal)?
+        .ok_or(ApiError::Conflict(
+            "An account with that username already exists".to_string(),
+        ))?;
+
+    let auth_token = state
+        .repository
+        .create_auth_token(&account_id)
         .await
-        .map_err(internal_error)?;
+        .map_err(ApiError::Internal)?;
 
     Ok(Json(RegisterOrLoginResponse { auth_token }))
 }
@@ -49,22 +52,15 @@ pub async fn register_account(
 #[debug_handler]
 pub async fn login_to_account(
     State(state): State<MyState>,
-    Json(login_request): Json<RegisterOrLoginRequest>,
-) -> Result<Json<RegisterOrLoginResponse>, (StatusCode, String)> {
-    let account = sqlx::query!(
-        r#"
-        SELECT id as "id: AccountId", password_hash
-        FROM Accounts
-        WHERE username = ?
-        "#,
-        login_request.username
-    )
-    .fetch_optional(&state.pool)
-    .await
-    .map_err(internal_error)?;
-
-    let account = account.ok_or((
-        StatusCode::NOT_FOUND,
+    ApiJson(login_request): ApiJson<RegisterOrLoginRequest>,
+) -> Result<Json<RegisterOrLoginResponse>, ApiError> {
+    let account = state
+        .repository
+        .account_by_username(&login_request.username)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    let account = account.ok_or(ApiError::NotFound(
         "No account with username exists".to_string(),
     ))?;
 
@@ -72,10 +68,52 @@ pub async fn login_to_account(
 
     Argon2::default()
         .verify_password(login_request.password.as_bytes(), &password_hash)
-        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid password".to_string()))?;
+        .map_err(|_| ApiError::Unauthorized("Invalid password".to_string()))?;
 
-    let auth_token = create_auth_token(account.id, &state.pool)
+    let auth_token = state
+        .repository
+        .create_auth_token(&account.id)
         .await
-        .map_err(internal_error)?;
+        .map_err(ApiError::Internal)?;
     Ok(Json(RegisterOrLoginResponse { auth_token }))
 }
+
+#[debug_handler]
+pub async fn get_account_settings(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+) -> Result<Json<AccountSettings>, ApiError> {
+    let time_zone = state
+        .repository
+        .account_time_zone(&account_id)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    Ok(Json(AccountSettings {
+        time_zone: time_zone.name().to_owned(),
+    }))
+}
+
+#[debug_handler]
+pub async fn update_account_settings(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiJson(account_settings): ApiJson<AccountSettings>,
+) -> Result<Json<AccountSettings>, ApiError> {
+    let time_zone = parse_time_zone(&account_settings.time_zone).ok_or_else(|| {
+        ApiError::Validation(vec![ErrorDetail::field(
+            "time_zone",
+            "The time zone must be an IANA time zone name, e.g. Europe/Copenhagen",
+        )])
+    })?;
+
+    state
+        .repository
+        .set_account_time_zone(&account_id, time_zone)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    Ok(Json(AccountSettings {
+        time_zone: time_zone.name().to_owned(),
+    }))
+}
@@ -0,0 +1,75 @@
+use axum::{debug_handler, extract::State, Json};
+use protocol::baseline::{DailyProfile, GetBaselineResponse, SetBaselineRequest};
+
+use crate::{
+    extractors::{auth::Authentication, json::ApiJson},
+    handlers::error::{internal_error, ApiError},
+    scheduling::baseline::standard_profile,
+    MyState,
+};
+
+#[debug_handler]
+pub async fn get_baseline(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+) -> Result<Json<GetBaselineResponse>, ApiError> {
+    let profile = state
+        .repository
+        .baseline(&account_id)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    Ok(Json(GetBaselineResponse { profile }))
+}
+
+/// Replaces the account's baseline consumption, after which the tasks are rescheduled.
+#[debug_handler]
+pub async fn update_baseline(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiJson(set_baseline_request): ApiJson<SetBaselineRequest>,
+) -> Result<Json<DailyProfile>, ApiError> {
+    set_baseline_request
+        .validate()
+        .map_err(ApiError::Validation)?;
+
+    let profile = match set_baseline_request {
+        SetBaselineRequest::Profile { profile } => profile,
+        SetBaselineRequest::Standard {
+            load_profile,
+            annual_kwh,
+        } => standard_profile(load_profile, annual_kwh),
+    };
+
+    state
+        .repository
+        .set_baseline(&account_id, &profile)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    state.update_schedule().map_err(internal_error)?;
+
+    Ok(Json(profile))
+}
+
+#[debug_handler]
+pub async fn delete_baseline(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+) -> Result<(), ApiError> {
+    let deleted = state
+        .repository
+        .delete_baseline(&account_id)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    if !deleted {
+        return Err(ApiError::NotFound(
+            "No baseline consumption found".to_owned(),
+        ));
+    }
+
+    state.update_schedule().map_err(internal_error)?;
+
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use axum::{
+    debug_handler,
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use protocol::{
+    calendar::{
+        CreateCalendarFeedRequest, CreateCalendarFeedResponse, DeleteCalendarFeedRequest,
+        GetCalendarFeedRequest, GetCalendarFeedsResponse, GetCalendarRequest,
+    },
+    devices::DeviceId,
+    time::DateTimeUtc,
+};
+
+use crate::{
+    data_model::{account::AccountId, repository::CalendarEntry},
+    extractors::{auth::Authentication, json::ApiJson, query::ApiQuery},
+    handlers::error::ApiError,
+    MyState,
+};
+
+const PRODUCT_ID: &str = "-//scheduling-backend//calendar//EN";
+// Content lines longer than this many octets must be folded
+const MAX_LINE_OCTETS: usize = 75;
+
+/// The account's events as an iCalendar file, optionally only those of one device.
+#[debug_handler]
+pub async fn get_calendar(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiQuery(get_calendar_request): ApiQuery<GetCalendarRequest>,
+) -> Result<Response, ApiError> {
+    calendar_response(&state, &account_id, get_calendar_request.device_id).await
+}
+
+/// The events of a calendar feed as an iCalendar file, for calendar apps that can not send an auth token.
+#[debug_handler]
+pub async fn get_calendar_feed(
+    State(state): State<MyState>,
+    ApiQuery(get_calendar_feed_request): ApiQuery<GetCalendarFeedRequest>,
+) -> Result<Response, ApiError> {
+    let (account_id, feed) = state
+        .repository
+        .calendar_feed(&get_calendar_feed_request.secret)
+        .await
+        .map_err(ApiError::Internal)?
+        .ok_or(ApiError::NotFound("No calendar feed found".to_owned()))?;
+
+    calendar_response(&state, &account_id, feed.device_id).await
+}
+
+#[debug_handler]
+pub async fn get_calendar_feeds(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+) -> Result<Json<GetCalendarFeedsResponse>, ApiError> {
+    let feeds = state
+        .repository
+        .calendar_feeds(&account_id)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    Ok(Json(GetCalendarFeedsResponse { feeds }))
+}
+
+#[debug_handler]
+pub async fn create_calendar_feed(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiJson(create_calendar_feed_request): ApiJson<CreateCalendarFeedRequest>,
+) -> Result<Json<CreateCalendarFeedResponse>, ApiError> {
+    let feed = state
+        .repository
+        .create_calendar_feed(&account_id, create_calendar_feed_request.device_id)
+        .await
+        .map_err(ApiError::Internal)?
+        .ok_or(ApiError::NotFound("No associated device found".to_owned()))?;
+
+    Ok(Json(CreateCalendarFeedResponse { feed }))
+}
+
+/// Revokes the feed, after which its url no longer works.
+#[debug_handler]
+pub async fn delete_calendar_feed(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiQuery(delete_calendar_feed_request): ApiQuery<DeleteCalendarFeedRequest>,
+) -> Result<(), ApiError> {
+    let deleted = state
+        .repository
+        .delete_calendar_feed(&account_id, &delete_calendar_feed_request.secret)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    if !deleted {
+        return Err(ApiError::NotFound("No calendar feed found".to_owned()));
+    }
+
+    Ok(())
+}
+
+async fn calendar_response(
+    state: &MyState,
+    account_id: &AccountId,
+    device_id: Option<DeviceId>,
+) -> Result<Response, ApiError> {
+    let name = match device_id {
+        Some(device_id) => {
+            let device = state
+                .repository
+                .device_for_account(account_id, device_id)
+                .await
+                .map_err(ApiError::Internal)?
+                .ok_or(ApiError::NotFound("No associated device found".to_owned()))?;
+            format!("Scheduled runs of {}", device.name)
+        }
+        None => "Scheduled device runs".to_owned(),
+    };
+
+    let entries = state
+        .repository
+        .calendar_entries(account_id, device_id)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    let calendar = render_calendar(&name, &entries, Utc::now());
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        calendar,
+    )
+        .into_response())
+}
+
+/// Renders the entries as an iCalendar (RFC 5545) file with a VEVENT per entry.
+///
+/// The UID of an event is derived from its task, so calendar apps move the event when it is rescheduled.
+pub fn render_calendar(name: &str, entries: &[CalendarEntry], now: DateTimeUtc) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        format!("PRODID:{}", PRODUCT_ID),
+        "CALSCALE:GREGORIAN".to_owned(),
+        "METHOD:PUBLISH".to_owned(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+    ];
+
+    for entry in entries {
+        let end_time = entry.start_time + Duration::from(entry.duration);
+        lines.extend([
+            "BEGIN:VEVENT".to_owned(),
+            format!("UID:task-{}@scheduling-backend", entry.task_id),
+            format!("DTSTAMP:{}", format_date_time(now)),
+            format!("DTSTART:{}", format_date_time(entry.start_time)),
+            format!("DTEND:{}", format_date_time(end_time)),
+            format!("SUMMARY:{}", escape_text(&entry.device_name)),
+            "END:VEVENT".to_owned(),
+        ]);
+    }
+
+    lines.push("END:VCALENDAR".to_owned());
+
+    lines
+        .iter()
+        .map(|line| fold_line(line))
+        .map(|line| line + "\r\n")
+        .collect()
+}
+
+fn format_date_time(date_time: DateTimeUtc) -> String {
+    date_time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Splits the line into lines of at most 75 octets, where every continuation line starts with a space.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            // The space counts towards the length of the continuation line
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn calendar_has_an_event_per_entry() {
+        let start_time = Utc.with_ymd_and_hms(2024, 5, 1, 10, 30, 0).unwrap();
+        let entries = vec![CalendarEntry {
+            task_id: 7.into(),
+            start_time,
+            duration: Duration::minutes(90).into(),
+            device_name: "Washer, upstairs; left".to_owned(),
+        }];
+
+        let calendar = render_calendar("Runs", &entries, start_time);
+
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        assert!(calendar.contains("UID:task-7@scheduling-backend\r\n"));
+        assert!(calendar.contains("DTSTART:20240501T103000Z\r\n"));
+        assert!(calendar.contains("DTEND:20240501T120000Z\r\n"));
+        assert!(calendar.contains("SUMMARY:Washer\\, upstairs\\; left\r\n"));
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let line = format!("SUMMARY:{}", "ø".repeat(100));
+
+        let folded = fold_line(&line);
+
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_OCTETS));
+        assert!(lines[1..].iter().all(|line| line.starts_with(' ')));
+        let unfolded = folded.replace("\r\n ", "");
+        assert_eq!(unfolded, line);
+    }
+}
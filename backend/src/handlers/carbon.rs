@@ -0,0 +1,134 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use axum::{debug_handler, extract::State, Json};
+use protocol::{
+    carbon::{CarbonIntensitySeries, GetCarbonIntensityResponse},
+    time_series::{GapPolicy, TimeSeries},
+};
+use serde::Deserialize;
+
+use crate::{
+    data_model::repository::Repository,
+    extractors::{
+        auth::{AdminAuthentication, Authentication},
+        json::ApiJson,
+    },
+    handlers::error::{internal_error, ApiError},
+    MyState,
+};
+
+#[debug_handler]
+pub async fn get_carbon_intensity(
+    State(state): State<MyState>,
+    Authentication(_): Authentication,
+) -> Result<Json<GetCarbonIntensityResponse>, ApiError> {
+    let series = state
+        .repository
+        .carbon_intensity()
+        .await
+        .map_err(ApiError::Internal)?;
+
+    Ok(Json(GetCarbonIntensityResponse {
+        series: series.map(CarbonIntensitySeries::from),
+    }))
+}
+
+/// Replaces the carbon intensity series, after which the tasks are rescheduled.
+#[debug_handler]
+pub async fn set_carbon_intensity(
+    State(state): State<MyState>,
+    _: AdminAuthentication,
+    ApiJson(series): ApiJson<CarbonIntensitySeries>,
+) -> Result<(), ApiError> {
+    series.validate().map_err(ApiError::Validation)?;
+
+    state
+        .repository
+        .set_carbon_intensity(&series.into())
+        .await
+        .map_err(ApiError::Internal)?;
+
+    state.forecast_updated().map_err(internal_error)?;
+
+    Ok(())
+}
+
+/// The formats of a carbon intensity file in JSON.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CarbonIntensityFile {
+    Series(CarbonIntensitySeries),
+    TimeSeries(TimeSeries),
+}
+
+/// Reads the carbon intensity from a file and stores it.
+///
+/// A `.csv` file has a `timestamp,value` row per timeslot, and any other file is JSON,
+/// either a [CarbonIntensitySeries] or a [TimeSeries].
+/// Missing timeslots in a CSV file or a [TimeSeries] are interpolated.
+pub async fn ingest_carbon_intensity_file(repository: &dyn Repository, path: &Path) -> Result<()> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Could not read carbon intensity file '{}'", path.display()))?;
+    let series = parse_carbon_intensity_file(path, &contents)
+        .with_context(|| format!("Invalid carbon intensity file '{}'", path.display()))?;
+
+    if let Err(errors) = series.validate() {
+        let messages: Vec<String> = errors.into_iter().map(|error| error.message).collect();
+        return Err(anyhow!(
+            "Invalid carbon intensity file '{}': {}",
+            path.display(),
+            messages.join(", ")
+        ));
+    }
+
+    repository.set_carbon_intensity(&series.into()).await
+}
+
+fn parse_carbon_intensity_file(path: &Path, contents: &str) -> Result<CarbonIntensitySeries> {
+    let is_csv = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    let time_series = if is_csv {
+        TimeSeries::from_csv(contents)?
+    } else {
+        match serde_json::from_str(contents)? {
+            CarbonIntensityFile::Series(series) => return Ok(series),
+            CarbonIntensityFile::TimeSeries(time_series) => time_series,
+        }
+    };
+
+    Ok(time_series.to_graph(GapPolicy::Interpolate)?.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    #[test]
+    fn carbon_intensity_is_read_from_csv_and_json() {
+        let start_time = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let expected = CarbonIntensitySeries {
+            start_time,
+            resolution_minutes: 30,
+            values: vec![100.0, 150.0, 200.0],
+        };
+
+        // The missing value at 00:30 is interpolated
+        let csv = "timestamp,value\n2024-06-01T00:00:00Z,100\n2024-06-01T01:00:00Z,200\n2024-06-01T01:30:00Z,200\n";
+        let series = parse_carbon_intensity_file(Path::new("carbon.CSV"), csv).unwrap();
+        assert_eq!(series.values, vec![100.0, 150.0, 200.0, 200.0]);
+
+        let time_series = TimeSeries::from_graph(&expected.clone().into());
+        let json = serde_json::to_string(&time_series).unwrap();
+        let series = parse_carbon_intensity_file(Path::new("carbon.json"), &json).unwrap();
+        assert_eq!(series, expected);
+
+        let json = serde_json::to_string(&expected).unwrap();
+        let series = parse_carbon_intensity_file(Path::new("carbon.json"), &json).unwrap();
+        assert_eq!(series, expected);
+    }
+}
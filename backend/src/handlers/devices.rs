@@ -1,33 +1,24 @@
-use axum::{
-    debug_handler,
-    extract::{Query, State},
-    http::StatusCode,
-    Json,
-};
+use axum::{debug_handler, extract::State, Json};
 use protocol::devices::{
-    CreateDeviceRequest, CreateDeviceResponse, DeleteDeviceRequest, Device, DeviceId,
-    GetDevicesResponse,
+    CreateDeviceRequest, CreateDeviceResponse, DeleteDeviceRequest, GetDevicesResponse,
 };
 
-use crate::{extractors::auth::Authentication, handlers::util::internal_error, MyState};
+use crate::{
+    extractors::{auth::Authentication, json::ApiJson, query::ApiQuery},
+    handlers::error::{internal_error, ApiError},
+    MyState,
+};
 
 #[debug_handler]
 pub async fn get_all_devices(
     State(state): State<MyState>,
     Authentication(account_id): Authentication,
-) -> Result<Json<GetDevicesResponse>, (StatusCode, String)> {
-    let devices = sqlx::query_as!(
-        Device,
-        r#"
-        SELECT id, name, effect
-        FROM Devices
-        WHERE account_id = ?
-        "#,
-        account_id
-    )
-    .fetch_all(&state.pool)
-    .await
-    .map_err(internal_error)?;
+) -> Result<Json<GetDevicesResponse>, ApiError> {
+    let devices = state
+        .repository
+        .devices_for_account(&account_id)
+        .await
+        .map_err(ApiError::Internal)?;
 
     Ok(Json(GetDevicesResponse { devices }))
 }
@@ -36,27 +27,21 @@ pub async fn get_all_devices(
 pub async fn create_device(
     State(state): State<MyState>,
     Authentication(account_id): Authentication,
-    Json(create_device_request): Json<CreateDeviceRequest>,
-) -> Result<Json<CreateDeviceResponse>, (StatusCode, String)> {
-    let id = sqlx::query_scalar!(
-        r#"
-        INSERT INTO Devices (name, effect, account_id)
-        VALUES (?, ?, ?)
-        RETURNING id as "id: DeviceId"
-        "#,
-        create_device_request.name,
-        create_device_request.effect,
-        account_id
-    )
-    .fetch_one(&state.pool)
-    .await
-    .map_err(internal_error)?;
+    ApiJson(create_device_request): ApiJson<CreateDeviceRequest>,
+) -> Result<Json<CreateDeviceResponse>, ApiError> {
+    create_device_request
+        .validate()
+        .map_err(ApiError::Validation)?;
 
-    let device = Device {
-        id,
-        name: create_device_request.name,
-        effect: create_device_request.effect,
-    };
+    let device = state
+        .repository
+        .create_device(
+            &account_id,
+            &create_device_request.name,
+            create_device_request.effect,
+        )
+        .await
+        .map_err(ApiError::Internal)?;
 
     Ok(Json(CreateDeviceResponse { device }))
 }
@@ -65,19 +50,17 @@ pub async fn create_device(
 pub async fn delete_device(
     State(state): State<MyState>,
     Authentication(account_id): Authentication,
-    Query(delete_device_request): Query<DeleteDeviceRequest>,
-) -> Result<(), (StatusCode, String)> {
-    sqlx::query!(
-        r#"
-        DELETE FROM Devices
-        WHERE id == ? AND account_id == ?
-        "#,
-        delete_device_request.id,
-        account_id
-    )
-    .execute(&state.pool)
-    .await
-    .map_err(internal_error)?;
+    ApiQuery(delete_device_request): ApiQuery<DeleteDeviceRequest>,
+) -> Result<(), ApiError> {
+    let deleted = state
+        .repository
+        .delete_device(&account_id, delete_device_request.id)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    if !deleted {
+        return Err(ApiError::NotFound("No associated device found".to_owned()));
+    }
 
     state.update_schedule().map_err(internal_error)?;
 
@@ -0,0 +1,98 @@
+use axum::{
+    extract::rejection::{JsonRejection, QueryRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use protocol::errors::{ErrorCode, ErrorDetail, ErrorResponse};
+use tracing::{event, Level};
+
+/// The error type returned by every handler and extractor.
+///
+/// It is rendered as an [ErrorResponse] so clients can match on the [ErrorCode].
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized(String),
+    NotFound(String),
+    Conflict(String),
+    Validation(Vec<ErrorDetail>),
+    Unavailable(String),
+    // The inner error is only logged, never sent to the client
+    Internal(anyhow::Error),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ApiError::BadRequest(_) => ErrorCode::BadRequest,
+            ApiError::Unauthorized(_) => ErrorCode::Unauthorized,
+            ApiError::NotFound(_) => ErrorCode::NotFound,
+            ApiError::Conflict(_) => ErrorCode::Conflict,
+            ApiError::Validation(_) => ErrorCode::ValidationFailed,
+            ApiError::Unavailable(_) => ErrorCode::Unavailable,
+            ApiError::Internal(_) => ErrorCode::InternalError,
+        }
+    }
+
+    fn to_response_body(&self) -> ErrorResponse {
+        let (message, details) = match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::Unavailable(message) => (message.clone(), Vec::new()),
+            ApiError::Validation(details) => {
+                ("The request is invalid".to_string(), details.clone())
+            }
+            ApiError::Internal(_) => ("Internal server error".to_string(), Vec::new()),
+        };
+
+        ErrorResponse {
+            code: self.code(),
+            message,
+            details,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if let ApiError::Internal(error) = &self {
+            event!(target: "backend", Level::ERROR, "Internal error: {:#}", error);
+        }
+
+        (self.status(), Json(self.to_response_body())).into_response()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+pub fn internal_error<E>(err: E) -> ApiError
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    ApiError::Internal(err.into())
+}
@@ -1,74 +1,88 @@
-use axum::{debug_handler, extract::State, http::StatusCode, Json};
-use chrono::{TimeZone, Utc};
-use protocol::{
-    events::{Event, EventId, GetDeviceEventRequest, GetEventResponse, GetEventsResponse},
-    tasks::TaskId,
+use axum::{debug_handler, extract::State, Json};
+use chrono::Utc;
+use protocol::events::{
+    GetDeviceEventRequest, GetEventExplanationRequest, GetEventExplanationResponse,
+    GetEventResponse, GetEventsResponse, GetLocalEventsResponse,
 };
 
-use crate::{extractors::auth::Authentication, handlers::util::internal_error, MyState};
+use crate::{
+    extractors::{auth::Authentication, json::ApiJson},
+    handlers::error::ApiError,
+    local_time::local_event,
+    MyState,
+};
 
 #[debug_handler]
 pub async fn get_all_events(
     State(state): State<MyState>,
     Authentication(account_id): Authentication,
-) -> Result<Json<GetEventsResponse>, (StatusCode, String)> {
-    let current_time = Utc::now();
-    let events = sqlx::query!(
-        r#"
-        SELECT Events.id as "id: EventId", Events.task_id as "task_id: TaskId", Events.start_time
-        FROM Events 
-        JOIN Tasks ON Events.task_id == Tasks.id 
-        JOIN Devices ON Tasks.device_id == Devices.id
-        WHERE Devices.account_id = ? AND (julianday(Tasks.timespan_end, 'utc') >= julianday(?, 'utc'))
-        "#,
-        account_id,
-        current_time
-    )
-    .fetch_all(&state.pool)
-    .await
-    .map_err(internal_error)?;
-
-    let events = events
-        .iter()
-        .map(|e| Event {
-            id: e.id,
-            task_id: e.task_id,
-            start_time: Utc.from_utc_datetime(&e.start_time),
-        })
-        .collect();
+) -> Result<Json<GetEventsResponse>, ApiError> {
+    let events = state
+        .repository
+        .events_for_account(&account_id, Utc::now())
+        .await
+        .map_err(ApiError::Internal)?;
 
     Ok(Json(GetEventsResponse { events }))
 }
 
+/// The events with their start times in the account's time zone.
 #[debug_handler]
-pub async fn get_device_event(
+pub async fn get_local_events(
     State(state): State<MyState>,
     Authentication(account_id): Authentication,
-    Json(get_device_event_request): Json<GetDeviceEventRequest>,
-) -> Result<Json<GetEventResponse>, (StatusCode, String)> {
-    let current_time = Utc::now();
-    let event = sqlx::query!(
-        r#"
-        SELECT Events.id as "id: EventId", Events.task_id as "task_id: TaskId", Events.start_time
-        FROM Events 
-        JOIN Tasks ON Events.task_id == Tasks.id 
-        JOIN Devices ON Tasks.device_id == Devices.id
-        WHERE Devices.account_id = ? AND Devices.id = ? AND Events.start_time >= ?
-        LIMIT 1
-        "#,
-        account_id,
-        get_device_event_request.device_id,
-        current_time
-    )
-    .fetch_optional(&state.pool)
-    .await
-    .map_err(internal_error)?;
+) -> Result<Json<GetLocalEventsResponse>, ApiError> {
+    let time_zone = state
+        .repository
+        .account_time_zone(&account_id)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    let events = state
+        .repository
+        .events_for_account(&account_id, Utc::now())
+        .await
+        .map_err(ApiError::Internal)?;
 
-    let event = event.map(|e| Event {
-        id: e.id,
-        task_id: e.task_id,
-        start_time: Utc.from_utc_datetime(&e.start_time),
-    });
+    Ok(Json(GetLocalEventsResponse {
+        time_zone: time_zone.name().to_owned(),
+        events: events
+            .into_iter()
+            .map(|event| local_event(time_zone, event))
+            .collect(),
+    }))
+}
+
+#[debug_handler]
+pub async fn get_device_event(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiJson(get_device_event_request): ApiJson<GetDeviceEventRequest>,
+) -> Result<Json<GetEventResponse>, ApiError> {
+    let event = state
+        .repository
+        .event_for_device(&account_id, get_device_event_request.device_id, Utc::now())
+        .await
+        .map_err(ApiError::Internal)?;
 
     Ok(Json(GetEventResponse { event }))
 }
+
+/// Why the scheduler chose the start time of one of the account's events.
+#[debug_handler]
+pub async fn get_event_explanation(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiJson(request): ApiJson<GetEventExplanationRequest>,
+) -> Result<Json<GetEventExplanationResponse>, ApiError> {
+    let explanation = state
+        .repository
+        .event_explanation(&account_id, request.event_id)
+        .await
+        .map_err(ApiError::Internal)?
+        .ok_or(ApiError::NotFound(
+            "No explanation found for the event".to_owned(),
+        ))?;
+
+    Ok(Json(GetEventExplanationResponse { explanation }))
+}
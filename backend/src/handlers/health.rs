@@ -0,0 +1,43 @@
+use axum::{debug_handler, extract::State, http::StatusCode, Json};
+use protocol::health::{ComponentStatus, ReadinessResponse};
+use tracing::{event, Level};
+
+use crate::MyState;
+
+/// Whether the backend is running, without checking its dependencies.
+#[debug_handler]
+pub async fn get_health() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Whether the backend can serve requests, responds with 503 if the database
+/// or the background service is unavailable.
+#[debug_handler]
+pub async fn get_readiness(State(state): State<MyState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let database = match state.repository.ping().await {
+        Ok(_) => ComponentStatus::Ok,
+        Err(error) => {
+            event!(target: "backend", Level::WARN, "The database is unavailable: {}", error);
+            ComponentStatus::Unavailable
+        }
+    };
+
+    // The receiver is dropped when the background service stops
+    let background_service = if state.sender.is_closed() {
+        ComponentStatus::Unavailable
+    } else {
+        ComponentStatus::Ok
+    };
+
+    let response = ReadinessResponse {
+        database,
+        background_service,
+    };
+    let status = if response.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(response))
+}
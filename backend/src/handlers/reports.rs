@@ -0,0 +1,77 @@
+use axum::{
+    debug_handler,
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use protocol::{
+    reports::{EnergyReport, EnergyReportRequest},
+    time::Timespan,
+};
+
+use crate::{
+    data_model::account::AccountId,
+    extractors::{auth::Authentication, query::ApiQuery},
+    handlers::error::ApiError,
+    scheduling::energy_report::{energy_report, energy_report_csv},
+    MyState,
+};
+
+/// The energy used by the account's devices and the renewable share of it,
+/// compared to running every task at the start of its timespan.
+#[debug_handler]
+pub async fn get_energy_report(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiQuery(energy_report_request): ApiQuery<EnergyReportRequest>,
+) -> Result<Json<EnergyReport>, ApiError> {
+    let report = create_energy_report(&state, &account_id, &energy_report_request).await?;
+
+    Ok(Json(report))
+}
+
+/// The energy report as a CSV file, with a row per device followed by the totals.
+#[debug_handler]
+pub async fn get_energy_report_csv(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiQuery(energy_report_request): ApiQuery<EnergyReportRequest>,
+) -> Result<Response, ApiError> {
+    let report = create_energy_report(&state, &account_id, &energy_report_request).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"energy-report.csv\"",
+            ),
+        ],
+        energy_report_csv(&report),
+    )
+        .into_response())
+}
+
+async fn create_energy_report(
+    state: &MyState,
+    account_id: &AccountId,
+    energy_report_request: &EnergyReportRequest,
+) -> Result<EnergyReport, ApiError> {
+    energy_report_request
+        .validate()
+        .map_err(ApiError::Validation)?;
+
+    let timespan = Timespan::new(energy_report_request.start, energy_report_request.end);
+    let entries = state
+        .repository
+        .report_entries(account_id, &timespan)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    Ok(energy_report(
+        &state.scheduling,
+        energy_report_request,
+        &entries,
+    ))
+}
@@ -0,0 +1,60 @@
+use axum::{debug_handler, extract::State, Json};
+use protocol::scheduling::{
+    GetSchedulingRunDiffRequest, GetSchedulingRunDiffResponse, GetSchedulingRunsRequest,
+    GetSchedulingRunsResponse,
+};
+
+use crate::{
+    extractors::{auth::AdminAuthentication, query::ApiQuery},
+    handlers::error::ApiError,
+    MyState,
+};
+
+const DEFAULT_RUNS_LIMIT: u32 = 50;
+
+/// The latest runs, for admins only, as their input graphs and errors contain the data of every account.
+#[debug_handler]
+pub async fn get_scheduling_runs(
+    State(state): State<MyState>,
+    _: AdminAuthentication,
+    ApiQuery(get_scheduling_runs_request): ApiQuery<GetSchedulingRunsRequest>,
+) -> Result<Json<GetSchedulingRunsResponse>, ApiError> {
+    let limit = get_scheduling_runs_request
+        .limit
+        .unwrap_or(DEFAULT_RUNS_LIMIT);
+    let runs = state
+        .repository
+        .scheduling_runs(limit)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    Ok(Json(GetSchedulingRunsResponse { runs }))
+}
+
+/// The run with its input graph and the events it created or moved, for admins only.
+#[debug_handler]
+pub async fn get_scheduling_run_diff(
+    State(state): State<MyState>,
+    _: AdminAuthentication,
+    ApiQuery(get_scheduling_run_diff_request): ApiQuery<GetSchedulingRunDiffRequest>,
+) -> Result<Json<GetSchedulingRunDiffResponse>, ApiError> {
+    let run_id = get_scheduling_run_diff_request.id;
+    let (run, input_graph) = state
+        .repository
+        .scheduling_run(run_id)
+        .await
+        .map_err(ApiError::Internal)?
+        .ok_or(ApiError::NotFound("No scheduling run found".to_owned()))?;
+
+    let changes = state
+        .repository
+        .event_changes(run_id)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    Ok(Json(GetSchedulingRunDiffResponse {
+        run,
+        input_graph,
+        changes,
+    }))
+}
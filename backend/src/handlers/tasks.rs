@@ -1,47 +1,50 @@
-use axum::{
-    debug_handler,
-    extract::{Query, State},
-    http::StatusCode,
-    Json,
-};
-use chrono::Utc;
+use std::time::Instant;
+
+use anyhow::anyhow;
+use axum::{debug_handler, extract::State, Json};
+use chrono::{Duration, Utc};
+use itertools::Itertools;
 use protocol::{
-    devices::DeviceId,
-    tasks::{CreateTaskRequest, DeleteTaskRequest, GetTasksResponse, Task, TaskId},
-    time::{Milliseconds, Timespan},
+    errors::ErrorDetail,
+    tasks::{
+        ClearExclusionsRequest, CreateLocalTasksRequest, CreateLocalTasksResponse,
+        CreateTaskRequest, DeleteTaskRequest, ExcludeTimespanRequest, GetLocalTasksResponse,
+        GetTasksResponse, PinTaskRequest, PreviewTaskResponse, Task, TaskId, UnpinTaskRequest,
+    },
+    time::{DateTimeUtc, Timespan},
+};
+use tokio::task::spawn_blocking;
+
+use crate::{
+    data_model::account::AccountId,
+    extractors::{auth::Authentication, json::ApiJson, query::ApiQuery},
+    handlers::error::{internal_error, ApiError},
+    local_time::{daily_windows, local_task},
+    scheduling::{
+        background_service::{defer_tasks_beyond_graph, remove_pinned_tasks_from_graph},
+        baseline::subtract_baselines,
+        coverage::renewable_coverage,
+        scheduler::RunControl,
+        task_for_scheduler::TaskForScheduler,
+    },
+    MyState,
 };
 
-use crate::{extractors::auth::Authentication, handlers::util::internal_error, MyState};
+// The id of the task being previewed, which never clashes with a stored task
+const PREVIEW_TASK_ID: i64 = -1;
+// How long the algorithm may search for a preview, before the best schedule found so far is used
+const PREVIEW_TIME_LIMIT: std::time::Duration = std::time::Duration::from_secs(2);
 
 #[debug_handler]
 pub async fn get_all_tasks(
     State(state): State<MyState>,
     Authentication(account_id): Authentication,
-) -> Result<Json<GetTasksResponse>, (StatusCode, String)> {
-    let current_time = Utc::now();
-    let tasks = sqlx::query!(
-        r#"
-        SELECT Tasks.id as "id: TaskId", Tasks.timespan_start, Tasks.timespan_end, Tasks.duration as "duration: Milliseconds", Tasks.device_id as "device_id: DeviceId"
-        FROM Tasks
-        JOIN Devices ON Tasks.device_id == Devices.id
-        WHERE Devices.account_id = ? AND (julianday(Tasks.timespan_end, 'utc') >= julianday(?, 'utc'))
-        "#,
-        account_id,
-        current_time
-    )
-    .fetch_all(&state.pool)
-    .await
-    .map_err(internal_error)?;
-
-    let tasks = tasks
-        .iter()
-        .map(|t| Task {
-            id: t.id,
-            timespan: Timespan::new_from_naive(t.timespan_start, t.timespan_end),
-            duration: t.duration,
-            device_id: t.device_id,
-        })
-        .collect();
+) -> Result<Json<GetTasksResponse>, ApiError> {
+    let tasks = state
+        .repository
+        .tasks_for_account(&account_id, Utc::now())
+        .await
+        .map_err(ApiError::Internal)?;
 
     Ok(Json(GetTasksResponse { tasks }))
 }
@@ -50,71 +53,391 @@ pub async fn get_all_tasks(
 pub async fn create_task(
     State(state): State<MyState>,
     Authentication(account_id): Authentication,
-    Json(create_task_request): Json<CreateTaskRequest>,
-) -> Result<Json<Task>, (StatusCode, String)> {
-    let id = sqlx::query_scalar!(
-        r#"
-        INSERT INTO Tasks (timespan_start, timespan_end, duration, device_id)
-        VALUES (?, ?, ?, (SELECT id FROM Devices WHERE account_id == ? AND id == ?))
-        RETURNING id as "id: TaskId"
-        "#,
-        create_task_request.timespan.start,
-        create_task_request.timespan.end,
-        create_task_request.duration,
-        account_id,
-        create_task_request.device_id
-    )
-    .fetch_one(&state.pool)
-    .await
-    .map_err(internal_error)?;
+    ApiJson(create_task_request): ApiJson<CreateTaskRequest>,
+) -> Result<Json<Task>, ApiError> {
+    create_task_request
+        .validate(Utc::now())
+        .map_err(ApiError::Validation)?;
 
-    state.update_schedule().map_err(internal_error)?;
+    let task = state
+        .repository
+        .create_task(
+            &account_id,
+            create_task_request.device_id,
+            &create_task_request.timespan,
+            create_task_request.duration,
+        )
+        .await
+        .map_err(ApiError::Internal)?
+        .ok_or(ApiError::NotFound("No associated device found".to_owned()))?;
 
-    let task = Task {
-        id,
-        timespan: Timespan {
-            start: create_task_request.timespan.start,
-            end: create_task_request.timespan.end,
-        },
-        duration: create_task_request.duration,
-        device_id: create_task_request.device_id,
-    };
+    state.update_schedule().map_err(internal_error)?;
 
     Ok(Json(task))
 }
 
+/// The tasks with their times in the account's time zone.
 #[debug_handler]
-pub async fn delete_task(
+pub async fn get_local_tasks(
     State(state): State<MyState>,
     Authentication(account_id): Authentication,
-    Query(delete_task_request): Query<DeleteTaskRequest>,
-) -> Result<(), (StatusCode, String)> {
-    let affected_rows = sqlx::query!(
-        r#"
-        DELETE FROM Tasks
-        WHERE id == ? AND EXISTS (
-            SELECT * 
-            FROM Tasks 
-            JOIN Devices ON Tasks.device_id == Devices.id 
-            AND Devices.account_id == ?
-        )
-        RETURNING id
-        "#,
-        delete_task_request.id,
-        account_id
-    )
-    .fetch_all(&state.pool)
+) -> Result<Json<GetLocalTasksResponse>, ApiError> {
+    let time_zone = state
+        .repository
+        .account_time_zone(&account_id)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    let tasks = state
+        .repository
+        .tasks_for_account(&account_id, Utc::now())
+        .await
+        .map_err(ApiError::Internal)?;
+
+    Ok(Json(GetLocalTasksResponse {
+        time_zone: time_zone.name().to_owned(),
+        tasks: tasks
+            .into_iter()
+            .map(|task| local_task(time_zone, task))
+            .collect(),
+    }))
+}
+
+/// Creates a task for the local time window on each of the requested days.
+/// Nothing is created if the window is invalid on any of the days.
+#[debug_handler]
+pub async fn create_local_tasks(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiJson(create_local_tasks_request): ApiJson<CreateLocalTasksRequest>,
+) -> Result<Json<CreateLocalTasksResponse>, ApiError> {
+    create_local_tasks_request
+        .validate()
+        .map_err(ApiError::Validation)?;
+
+    let time_zone = state
+        .repository
+        .account_time_zone(&account_id)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    let now = Utc::now();
+    let windows = daily_windows(
+        time_zone,
+        create_local_tasks_request.date,
+        create_local_tasks_request.start,
+        create_local_tasks_request.end,
+        create_local_tasks_request.days,
+    );
+    for timespan in &windows {
+        CreateTaskRequest {
+            timespan: timespan.clone(),
+            duration: create_local_tasks_request.duration,
+            device_id: create_local_tasks_request.device_id,
+        }
+        .validate(now)
+        .map_err(ApiError::Validation)?;
+    }
+
+    state
+        .repository
+        .device_for_account(&account_id, create_local_tasks_request.device_id)
+        .await
+        .map_err(ApiError::Internal)?
+        .ok_or(ApiError::NotFound("No associated device found".to_owned()))?;
+
+    let mut tasks = Vec::with_capacity(windows.len());
+    for timespan in &windows {
+        let task = state
+            .repository
+            .create_task(
+                &account_id,
+                create_local_tasks_request.device_id,
+                timespan,
+                create_local_tasks_request.duration,
+            )
+            .await
+            .map_err(ApiError::Internal)?
+            .ok_or(ApiError::NotFound("No associated device found".to_owned()))?;
+        tasks.push(local_task(time_zone, task));
+    }
+
+    state.update_schedule().map_err(internal_error)?;
+
+    Ok(Json(CreateLocalTasksResponse { tasks }))
+}
+
+/// Predicts when a task would be scheduled together with all existing tasks, without storing anything.
+#[debug_handler]
+pub async fn preview_task(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiJson(preview_task_request): ApiJson<CreateTaskRequest>,
+) -> Result<Json<PreviewTaskResponse>, ApiError> {
+    let now = Utc::now();
+    preview_task_request
+        .validate(now)
+        .map_err(ApiError::Validation)?;
+
+    let device = state
+        .repository
+        .device_for_account(&account_id, preview_task_request.device_id)
+        .await
+        .map_err(ApiError::Internal)?
+        .ok_or(ApiError::NotFound("No associated device found".to_owned()))?;
+
+    let mut graph = state.scheduling.production_graph(now);
+
+    let task = TaskForScheduler::new(
+        PREVIEW_TASK_ID.into(),
+        preview_task_request.timespan,
+        preview_task_request.duration,
+        device.effect,
+    );
+    // A task beyond the horizon would be deferred, so it cannot be previewed yet
+    if !task.fits_within(&graph) {
+        return Err(ApiError::Validation(vec![ErrorDetail::field(
+            "timespan",
+            "The task does not fit within the scheduling horizon",
+        )]));
+    }
+
+    subtract_baselines(state.repository.as_ref(), &mut graph)
+        .await
+        .map_err(ApiError::Internal)?;
+    remove_pinned_tasks_from_graph(state.repository.as_ref(), &mut graph)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    let mut tasks = state
+        .repository
+        .tasks_for_scheduling(now)
+        .await
+        .map_err(ApiError::Internal)?;
+    defer_tasks_beyond_graph(&graph, &mut tasks);
+    tasks.push(task.clone());
+
+    let carbon_intensity = state
+        .repository
+        .carbon_intensity()
+        .await
+        .map_err(ApiError::Internal)?;
+    // The algorithm may search for long, so it runs on the blocking thread pool until the deadline
+    let algorithm = state.algorithm.clone();
+    let control = RunControl::default().with_deadline(Instant::now() + PREVIEW_TIME_LIMIT);
+    let (schedule, graph) = spawn_blocking(move || {
+        let schedule =
+            algorithm.schedule_cancellable(&mut graph, tasks, carbon_intensity.as_ref(), &control);
+        (schedule, graph)
+    })
     .await
     .map_err(internal_error)?;
+    let schedule = schedule.map_err(ApiError::Internal)?;
+
+    // A cancelled run still answers with the best schedule it found, if it includes the task
+    let event = schedule
+        .events
+        .iter()
+        .find(|event| event.task_id == task.id)
+        .ok_or_else(|| {
+            if schedule.cancelled {
+                ApiError::Unavailable("The preview took too long, try again later".to_owned())
+            } else {
+                ApiError::Internal(anyhow!("The previewed task was not scheduled"))
+            }
+        })?;
+
+    let renewable_coverage =
+        renewable_coverage(&graph, &task, event).map_err(ApiError::Internal)?;
+
+    Ok(Json(PreviewTaskResponse {
+        start_time: event.start_time,
+        renewable_coverage,
+    }))
+}
+
+#[debug_handler]
+pub async fn delete_task(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiQuery(delete_task_request): ApiQuery<DeleteTaskRequest>,
+) -> Result<(), ApiError> {
+    let deleted = state
+        .repository
+        .delete_task(&account_id, delete_task_request.id)
+        .await
+        .map_err(ApiError::Internal)?;
 
-    if affected_rows.len() != 1 {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            "No associated task found".to_owned(),
-        ));
+    if !deleted {
+        return Err(ApiError::NotFound("No associated task found".to_owned()));
     }
 
     state.update_schedule().map_err(internal_error)?;
 
     Ok(())
 }
+
+/// The task if it is owned by the account.
+async fn get_owned_task(
+    state: &MyState,
+    account_id: &AccountId,
+    task_id: TaskId,
+) -> Result<Task, ApiError> {
+    state
+        .repository
+        .task_for_account(account_id, task_id)
+        .await
+        .map_err(ApiError::Internal)?
+        .ok_or(ApiError::NotFound("No associated task found".to_owned()))
+}
+
+#[debug_handler]
+pub async fn pin_task(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiJson(pin_task_request): ApiJson<PinTaskRequest>,
+) -> Result<(), ApiError> {
+    let task = get_owned_task(&state, &account_id, pin_task_request.id).await?;
+
+    let start_time = pin_task_request.start_time;
+    let end_time = start_time + Duration::from(task.duration);
+    if start_time < Utc::now() {
+        return Err(ApiError::Validation(vec![ErrorDetail::field(
+            "start_time",
+            "The task cannot be pinned in the past",
+        )]));
+    }
+    if start_time < task.timespan.start || end_time > task.timespan.end {
+        return Err(ApiError::Validation(vec![ErrorDetail::field(
+            "start_time",
+            "The task must run within its timespan",
+        )]));
+    }
+    if task
+        .exclusions
+        .iter()
+        .any(|exclusion| exclusion.start < end_time && start_time < exclusion.end)
+    {
+        return Err(ApiError::Validation(vec![ErrorDetail::field(
+            "start_time",
+            "The task must not run during an excluded timespan",
+        )]));
+    }
+
+    // The event is moved right away, as the scheduler will never move it
+    let pinned = state
+        .repository
+        .pin_task(&account_id, pin_task_request.id, start_time)
+        .await
+        .map_err(ApiError::Internal)?;
+    if !pinned {
+        return Err(ApiError::NotFound("No associated task found".to_owned()));
+    }
+
+    state.update_schedule().map_err(internal_error)?;
+
+    Ok(())
+}
+
+#[debug_handler]
+pub async fn exclude_timespan(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiJson(exclude_timespan_request): ApiJson<ExcludeTimespanRequest>,
+) -> Result<(), ApiError> {
+    let excluded = exclude_timespan_request.timespan;
+    if excluded.end <= excluded.start {
+        return Err(ApiError::Validation(vec![ErrorDetail::field(
+            "timespan",
+            "The timespan must end after it starts",
+        )]));
+    }
+
+    let task = get_owned_task(&state, &account_id, exclude_timespan_request.id).await?;
+
+    let mut exclusions = task.exclusions;
+    exclusions.push(excluded.clone());
+
+    if !fits_outside_exclusions(
+        &task.timespan,
+        Utc::now(),
+        task.duration.into(),
+        &exclusions,
+    ) {
+        return Err(ApiError::Validation(vec![ErrorDetail::field(
+            "timespan",
+            "The task would no longer fit within its timespan",
+        )]));
+    }
+
+    let added = state
+        .repository
+        .add_exclusion(&account_id, exclude_timespan_request.id, &excluded)
+        .await
+        .map_err(ApiError::Internal)?;
+    if !added {
+        return Err(ApiError::NotFound("No associated task found".to_owned()));
+    }
+
+    state.update_schedule().map_err(internal_error)?;
+
+    Ok(())
+}
+
+#[debug_handler]
+pub async fn unpin_task(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiQuery(unpin_task_request): ApiQuery<UnpinTaskRequest>,
+) -> Result<(), ApiError> {
+    let unpinned = state
+        .repository
+        .unpin_task(&account_id, unpin_task_request.id)
+        .await
+        .map_err(ApiError::Internal)?;
+    if !unpinned {
+        return Err(ApiError::NotFound("No associated task found".to_owned()));
+    }
+
+    state.update_schedule().map_err(internal_error)?;
+
+    Ok(())
+}
+
+#[debug_handler]
+pub async fn clear_exclusions(
+    State(state): State<MyState>,
+    Authentication(account_id): Authentication,
+    ApiQuery(clear_exclusions_request): ApiQuery<ClearExclusionsRequest>,
+) -> Result<(), ApiError> {
+    let cleared = state
+        .repository
+        .clear_exclusions(&account_id, clear_exclusions_request.id)
+        .await
+        .map_err(ApiError::Internal)?;
+    if !cleared {
+        return Err(ApiError::NotFound("No associated task found".to_owned()));
+    }
+
+    state.update_schedule().map_err(internal_error)?;
+
+    Ok(())
+}
+
+/// Whether the task can run for `duration` within `timespan`, after `now`, without overlapping an exclusion.
+fn fits_outside_exclusions(
+    timespan: &Timespan,
+    now: DateTimeUtc,
+    duration: Duration,
+    exclusions: &[Timespan],
+) -> bool {
+    let mut free_start = timespan.start.max(now);
+    for exclusion in exclusions.iter().sorted_by_key(|exclusion| exclusion.start) {
+        if exclusion.start.min(timespan.end) - free_start >= duration {
+            return true;
+        }
+        free_start = free_start.max(exclusion.end);
+    }
+
+    timespan.end - free_start >= duration
+}
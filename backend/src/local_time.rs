@@ -0,0 +1,206 @@
+use chrono::{
+    Days, Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use protocol::{
+    events::{Event, LocalEvent},
+    tasks::{LocalTask, Task},
+    time::{DateTimeLocal, DateTimeUtc, LocalTimespan, Timespan},
+};
+
+/// Parses an IANA time zone name such as "Europe/Copenhagen".
+pub fn parse_time_zone(name: &str) -> Option<Tz> {
+    name.parse().ok()
+}
+
+/// Converts a local date and time to UTC.
+///
+/// A time that is repeated when the clocks fall back resolves to its first occurrence.
+/// A time that is skipped when the clocks spring forward is moved forward by the length of the gap,
+/// so 02:30 on a day where 02:00 becomes 03:00 resolves to 03:30.
+pub fn resolve_local(time_zone: Tz, local: NaiveDateTime) -> DateTimeUtc {
+    match time_zone.from_local_datetime(&local) {
+        LocalResult::Single(date_time) | LocalResult::Ambiguous(date_time, _) => {
+            date_time.with_timezone(&Utc)
+        }
+        LocalResult::None => {
+            // The offset from before the gap, as no transitions happen within a day of each other
+            let offset = time_zone
+                .offset_from_utc_datetime(&(local - Duration::days(1)))
+                .fix();
+            let utc = local - Duration::seconds(offset.local_minus_utc().into());
+            Utc.from_utc_datetime(&utc)
+        }
+    }
+}
+
+/// The window from `start` to `end` local time on each of `days` consecutive dates from `date`.
+///
+/// A window whose end is not after its start ends on the next day.
+/// The local times stay the same across DST transitions, so the windows are not always 24 hours apart.
+pub fn daily_windows(
+    time_zone: Tz,
+    date: NaiveDate,
+    start: NaiveTime,
+    end: NaiveTime,
+    days: u32,
+) -> Vec<Timespan> {
+    (0..days)
+        .filter_map(|day| date.checked_add_days(Days::new(day.into())))
+        .filter_map(|start_date| {
+            let end_date = if end <= start {
+                start_date.succ_opt()?
+            } else {
+                start_date
+            };
+            Some(Timespan::new(
+                resolve_local(time_zone, start_date.and_time(start)),
+                resolve_local(time_zone, end_date.and_time(end)),
+            ))
+        })
+        .collect()
+}
+
+pub fn to_local(time_zone: Tz, date_time: DateTimeUtc) -> DateTimeLocal {
+    date_time.with_timezone(&time_zone).fixed_offset()
+}
+
+pub fn local_timespan(time_zone: Tz, timespan: &Timespan) -> LocalTimespan {
+    LocalTimespan {
+        start: to_local(time_zone, timespan.start),
+        end: to_local(time_zone, timespan.end),
+    }
+}
+
+pub fn local_task(time_zone: Tz, task: Task) -> LocalTask {
+    LocalTask {
+        id: task.id,
+        timespan: local_timespan(time_zone, &task.timespan),
+        duration: task.duration,
+        device_id: task.device_id,
+        pinned_start: task
+            .pinned_start
+            .map(|pinned_start| to_local(time_zone, pinned_start)),
+        exclusions: task
+            .exclusions
+            .iter()
+            .map(|exclusion| local_timespan(time_zone, exclusion))
+            .collect(),
+    }
+}
+
+pub fn local_event(time_zone: Tz, event: Event) -> LocalEvent {
+    LocalEvent {
+        id: event.id,
+        task_id: event.task_id,
+        start_time: to_local(time_zone, event.start_time),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Europe::Copenhagen;
+
+    use super::*;
+
+    fn local(date: NaiveDate, hour: u32, minute: u32) -> NaiveDateTime {
+        date.and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTimeUtc {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    // In Copenhagen the clocks go from 02:00 to 03:00 on 2024-03-31 and from 03:00 to 02:00 on 2024-10-27
+    fn spring_forward() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 31).unwrap()
+    }
+
+    fn fall_back() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 10, 27).unwrap()
+    }
+
+    #[test]
+    fn skipped_times_move_forward_by_the_gap() {
+        assert_eq!(
+            resolve_local(Copenhagen, local(spring_forward(), 2, 30)),
+            utc(2024, 3, 31, 1, 30)
+        );
+        assert_eq!(
+            resolve_local(Copenhagen, local(spring_forward(), 3, 0)),
+            utc(2024, 3, 31, 1, 0)
+        );
+        assert_eq!(
+            resolve_local(Copenhagen, local(spring_forward(), 1, 59)),
+            utc(2024, 3, 31, 0, 59)
+        );
+    }
+
+    #[test]
+    fn repeated_times_resolve_to_the_first_occurrence() {
+        assert_eq!(
+            resolve_local(Copenhagen, local(fall_back(), 2, 30)),
+            utc(2024, 10, 27, 0, 30)
+        );
+        assert_eq!(
+            resolve_local(Copenhagen, local(fall_back(), 3, 0)),
+            utc(2024, 10, 27, 2, 0)
+        );
+    }
+
+    #[test]
+    fn overnight_windows_follow_the_clock_changes() {
+        let night = |date: NaiveDate| {
+            daily_windows(
+                Copenhagen,
+                date.pred_opt().unwrap(),
+                NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
+                1,
+            )
+            .remove(0)
+        };
+
+        let short_night = night(spring_forward());
+        assert_eq!(short_night.start, utc(2024, 3, 30, 21, 0));
+        assert_eq!(short_night.end - short_night.start, Duration::hours(7));
+
+        let long_night = night(fall_back());
+        assert_eq!(long_night.start, utc(2024, 10, 26, 20, 0));
+        assert_eq!(long_night.end - long_night.start, Duration::hours(9));
+    }
+
+    #[test]
+    fn recurring_windows_keep_their_local_time() {
+        let windows = daily_windows(
+            Copenhagen,
+            NaiveDate::from_ymd_opt(2024, 3, 30).unwrap(),
+            NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+            3,
+        );
+
+        let starts: Vec<_> = windows.iter().map(|window| window.start).collect();
+        assert_eq!(
+            starts,
+            vec![
+                utc(2024, 3, 30, 17, 0),
+                utc(2024, 3, 31, 16, 0),
+                utc(2024, 4, 1, 16, 0)
+            ]
+        );
+        assert!(windows
+            .iter()
+            .all(|window| window.end - window.start == Duration::hours(2)));
+    }
+
+    #[test]
+    fn local_times_carry_the_offset_of_the_date() {
+        let winter = to_local(Copenhagen, utc(2024, 1, 15, 12, 0));
+        let summer = to_local(Copenhagen, utc(2024, 7, 15, 12, 0));
+
+        assert_eq!(winter.to_rfc3339(), "2024-01-15T13:00:00+01:00");
+        assert_eq!(summer.to_rfc3339(), "2024-07-15T14:00:00+02:00");
+    }
+}
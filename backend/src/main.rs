@@ -1,50 +1,81 @@
+mod config;
 mod data_model;
 mod extractors;
 mod handlers;
+mod local_time;
+mod monitoring;
 mod scheduling;
 
-use std::error::Error;
+use std::{error::Error, path::PathBuf, sync::Arc};
 
 use axum::{
     debug_handler,
     extract::State,
-    http::StatusCode,
-    routing::{delete, get, post},
+    middleware,
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use clap::Parser;
+use config::{AlgorithmName, Config};
+use data_model::repository::{self, Repository};
 use dotenv::dotenv;
+use metrics::gauge;
+use metrics_exporter_prometheus::PrometheusHandle;
+use monitoring::{get_metrics, metrics_handle, track_http_metrics};
 use protocol::graph::DiscreteGraph;
 use scheduling::{
     background_service::{
         background_service, simulator_background_service, BackgroundServiceMessage,
+        SchedulingConfig,
     },
-    scheduler::{AllPermutationsAlgorithm, GlobalSchedulerAlgorithm, NaiveSchedulerAlgorithm},
+    registry::available_algorithms,
+    scheduler::SchedulerAlgorithm,
 };
-use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
 use tokio::{
     net::TcpListener,
     sync::mpsc::{error::SendError, unbounded_channel, UnboundedSender},
 };
 
-use handlers::{accounts::*, devices::*, events::*, tasks::*};
+use extractors::json::ApiJson;
+use handlers::{
+    accounts::*, baseline::*, calendar::*, carbon::*, devices::*, error::ApiError, events::*,
+    health::*, reports::*, scheduling::*, tasks::*,
+};
 use tower_http::trace::TraceLayer;
 use tracing::{event, Level};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use protocol::scheduling::SchedulingGlob;
+use protocol::scheduling::{GetAlgorithmsResponse, SchedulingGlob};
 
 use crate::scheduling::background_service::run_algorithm;
 
 #[derive(Clone)]
 pub struct MyState {
-    pool: SqlitePool,
+    repository: Arc<dyn Repository>,
     sender: UnboundedSender<BackgroundServiceMessage>,
+    // The algorithm used by the background service
+    algorithm: Arc<dyn SchedulerAlgorithm + Send + Sync>,
+    scheduling: SchedulingConfig,
+    metrics: PrometheusHandle,
+    admin_token: Option<String>,
 }
 
 impl MyState {
     pub fn update_schedule(&self) -> Result<(), SendError<BackgroundServiceMessage>> {
-        self.sender.send(BackgroundServiceMessage::Update)?;
+        self.notify(BackgroundServiceMessage::Update)
+    }
+
+    /// Reschedules the tasks after a new forecast has been ingested.
+    pub fn forecast_updated(&self) -> Result<(), SendError<BackgroundServiceMessage>> {
+        self.notify(BackgroundServiceMessage::ForecastUpdated)
+    }
+
+    fn notify(
+        &self,
+        message: BackgroundServiceMessage,
+    ) -> Result<(), SendError<BackgroundServiceMessage>> {
+        self.sender.send(message)?;
+        gauge!("scheduler_debounce_queue_depth").increment(1.0);
         Ok(())
     }
 }
@@ -55,6 +86,39 @@ struct Args {
     // Whether or not to run in simulator mode
     #[arg(long)]
     simulator: bool,
+    /// Path to a TOML configuration file
+    #[arg(long, env = "SCHEDULING_CONFIG")]
+    config: Option<PathBuf>,
+    /// The address to listen on, e.g. 127.0.0.1:3000
+    #[arg(long, env = "BIND_ADDRESS")]
+    bind_address: Option<String>,
+    #[arg(long, env = "DATABASE_URL")]
+    database_url: Option<String>,
+    /// The token of the admin endpoints, which are disabled without it
+    #[arg(long, env = "ADMIN_TOKEN")]
+    admin_token: Option<String>,
+    /// A JSON file with a carbon intensity series to ingest on startup
+    #[arg(long, env = "CARBON_INTENSITY_FILE")]
+    carbon_intensity_file: Option<PathBuf>,
+    /// The scheduling algorithm used by the background service
+    #[arg(long, value_enum, env = "SCHEDULING_ALGORITHM")]
+    algorithm: Option<AlgorithmName>,
+    /// Only for all-permutations: fall back to the global scheduler above this amount of tasks
+    #[arg(long, env = "SCHEDULING_MAX_TASKS")]
+    max_tasks: Option<usize>,
+    /// Seconds to wait for more changes before running the scheduler
+    #[arg(long, env = "SCHEDULING_DEBOUNCE_SECONDS")]
+    debounce_seconds: Option<u64>,
+    /// The longest a change waits for the scheduler while more changes keep arriving
+    #[arg(long, env = "SCHEDULING_MAX_DEBOUNCE_SECONDS")]
+    max_debounce_seconds: Option<u64>,
+    #[arg(long, env = "SCHEDULING_SLOT_RESOLUTION_MINUTES")]
+    slot_resolution_minutes: Option<i64>,
+    #[arg(long, env = "SCHEDULING_HORIZON_HOURS")]
+    horizon_hours: Option<i64>,
+    /// Minutes between runs of the scheduler when nothing changes
+    #[arg(long, env = "SCHEDULING_INTERVAL_MINUTES")]
+    interval_minutes: Option<u64>,
 }
 
 #[tokio::main]
@@ -65,6 +129,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let simulator_mode = args.simulator;
 
+    let config = Config::load(&args)?;
+
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
@@ -73,106 +139,181 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .with(tracing_subscriber::fmt::layer().pretty())
         .init();
 
-    let db_connection_string = std::env::var("DATABASE_URL")?;
+    let db_connection_string = config
+        .database_url
+        .as_deref()
+        .expect("The database URL is validated when loading the config");
 
-    let pool = SqlitePoolOptions::new()
-        .connect(&db_connection_string)
-        .await?;
+    let repository = repository::connect(db_connection_string).await?;
 
-    sqlx::migrate!("./migrations").run(&pool).await?;
+    if let Some(path) = &config.carbon_intensity_file {
+        ingest_carbon_intensity_file(repository.as_ref(), path).await?;
+        event!(target: "backend", Level::INFO, "Ingested the carbon intensity from {}", path.display());
+    }
 
-    let listener = TcpListener::bind("127.0.0.1:3000").await?;
+    let listener = TcpListener::bind(&config.bind_address).await?;
 
     let (sender, receiver) = unbounded_channel();
 
+    let algorithm = config.algorithm();
+    let scheduling_config = config.scheduling_config();
+
+    event!(target: "backend", Level::INFO, "Using the {:?} algorithm", config.algorithm);
+
     let state = MyState {
-        pool: pool.clone(),
+        repository: repository.clone(),
         sender,
+        algorithm: algorithm.clone(),
+        scheduling: scheduling_config,
+        metrics: metrics_handle(),
+        admin_token: config.admin_token.clone(),
     };
 
+    if config.carbon_intensity_file.is_some() {
+        state.forecast_updated()?;
+    }
+
     let app = app(state, simulator_mode);
 
     let background_task = if simulator_mode {
         event!(target: "backend", Level::INFO, "Running in simulator mode");
         tokio::spawn(simulator_background_service(
             receiver,
-            pool,
-            NaiveSchedulerAlgorithm::new,
+            repository.clone(),
+            config.algorithm.clone(),
         ))
     } else {
         tokio::spawn(background_service(
             receiver,
-            pool,
-            NaiveSchedulerAlgorithm::new,
+            repository.clone(),
+            scheduling_config,
+            config.algorithm.clone(),
         ))
     };
 
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
 
+    // The router and its senders are dropped, so the background service stops after its current run
     background_task.await?;
+    repository.close().await;
+
+    event!(target: "backend", Level::INFO, "Shut down");
 
     Ok(())
 }
 
+/// Completes on SIGINT or SIGTERM, after which in-flight requests are drained.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Could not install the SIGINT handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Could not install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    event!(target: "backend", Level::INFO, "Shutting down");
+}
+
 fn app(state: MyState, simulator_mode: bool) -> Router {
     let mut router = Router::new()
         .route("/tasks/all", get(get_all_tasks))
+        .route("/tasks/local", get(get_local_tasks))
         .route("/tasks/create", post(create_task))
+        .route("/tasks/create_local", post(create_local_tasks))
+        .route("/tasks/preview", post(preview_task))
         .route("/tasks/delete", delete(delete_task))
+        .route("/tasks/pin", post(pin_task))
+        .route("/tasks/exclude", post(exclude_timespan))
+        .route("/tasks/unpin", delete(unpin_task))
+        .route("/tasks/exclusions/clear", delete(clear_exclusions))
         .route("/devices/all", get(get_all_devices))
         .route("/devices/create", post(create_device))
         .route("/devices/delete", delete(delete_device))
         .route("/accounts/register", post(register_account))
         .route("/accounts/login", post(login_to_account))
+        .route("/accounts/settings", get(get_account_settings))
+        .route("/accounts/settings/update", post(update_account_settings))
+        .route("/accounts/baseline", get(get_baseline))
+        .route("/accounts/baseline/update", post(update_baseline))
+        .route("/accounts/baseline/delete", delete(delete_baseline))
         .route("/events/all", get(get_all_events))
-        .route("/events/get", get(get_device_event));
+        .route("/events/local", get(get_local_events))
+        .route("/events/get", get(get_device_event))
+        .route("/events/explanation", get(get_event_explanation))
+        .route("/calendar/events.ics", get(get_calendar))
+        .route("/calendar/feed.ics", get(get_calendar_feed))
+        .route("/calendar/feeds/all", get(get_calendar_feeds))
+        .route("/calendar/feeds/create", post(create_calendar_feed))
+        .route("/calendar/feeds/delete", delete(delete_calendar_feed))
+        .route("/reports/energy", get(get_energy_report))
+        .route("/reports/energy.csv", get(get_energy_report_csv))
+        .route("/carbon_intensity", get(get_carbon_intensity))
+        .route("/admin/carbon_intensity", put(set_carbon_intensity))
+        .route("/scheduling/algorithms", get(get_algorithms))
+        .route("/scheduling/runs", get(get_scheduling_runs))
+        .route("/scheduling/runs/diff", get(get_scheduling_run_diff))
+        .route("/metrics", get(get_metrics))
+        .route("/health", get(get_health))
+        .route("/ready", get(get_readiness));
 
     if simulator_mode {
         router = router.route("/scheduling/run", get(run_scheduling));
     }
 
-    router.layer(TraceLayer::new_for_http()).with_state(state)
+    router
+        .route_layer(middleware::from_fn(track_http_metrics))
+        .layer(TraceLayer::new_for_http())
+        .with_state(state)
 }
 
 #[debug_handler]
 async fn run_scheduling(
     State(state): State<MyState>,
-    Json(scheduling_glob): Json<SchedulingGlob>,
-) -> Result<Json<DiscreteGraph>, (StatusCode, String)> {
+    ApiJson(scheduling_glob): ApiJson<SchedulingGlob>,
+) -> Result<Json<DiscreteGraph>, ApiError> {
     let mut discrete_graph = scheduling_glob.get_discrete_graph().clone();
-    let _ = match scheduling_glob.get_alg() {
-        0 => {
-            run_algorithm(
-                &state.pool,
-                &mut NaiveSchedulerAlgorithm::new(),
-                &mut discrete_graph,
-            )
-            .await
-        }
-        1 => {
-            run_algorithm(
-                &state.pool,
-                &mut GlobalSchedulerAlgorithm::new(),
-                &mut discrete_graph,
-            )
-            .await
-        }
-        2 => {
-            run_algorithm(
-                &state.pool,
-                &mut AllPermutationsAlgorithm::new(),
-                &mut discrete_graph,
-            )
-            .await
-        }
-        _ => Ok(()), // Return error instead
-    };
+    run_algorithm(
+        state.repository.as_ref(),
+        scheduling_glob.get_algorithm(),
+        &mut discrete_graph,
+    )
+    .await
+    .map_err(ApiError::Internal)?;
     Ok(Json(discrete_graph))
 }
 
+#[debug_handler]
+async fn get_algorithms() -> Json<GetAlgorithmsResponse> {
+    Json(GetAlgorithmsResponse {
+        algorithms: available_algorithms(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::scheduling::event_creation::_create_event;
+    use std::str::FromStr;
+
+    use crate::{
+        data_model::{account::AccountId, postgres::PostgresRepository, sqlite::SqliteRepository},
+        scheduling::scheduler::NaiveSchedulerAlgorithm,
+    };
 
     use super::*;
     use axum::{
@@ -180,24 +321,135 @@ mod tests {
         http::{Method, Request, StatusCode},
         routing::RouterIntoService,
     };
-    use chrono::{Days, Duration, Utc};
+    use chrono::{Days, Duration, NaiveTime, Offset, SecondsFormat, SubsecRound, Utc};
     use http_body_util::BodyExt;
     use protocol::{
-        accounts::{AuthToken, RegisterOrLoginRequest, RegisterOrLoginResponse},
+        accounts::{AccountSettings, AuthToken, RegisterOrLoginRequest, RegisterOrLoginResponse},
+        baseline::{DailyProfile, GetBaselineResponse, SetBaselineRequest, StandardLoadProfile},
+        calendar::{
+            CreateCalendarFeedRequest, CreateCalendarFeedResponse, GetCalendarFeedsResponse,
+        },
+        carbon::{CarbonIntensitySeries, GetCarbonIntensityResponse},
         devices::{CreateDeviceRequest, CreateDeviceResponse, Device, GetDevicesResponse},
-        events::{GetDeviceEventRequest, GetEventResponse, GetEventsResponse},
-        tasks::{CreateTaskRequest, GetTasksResponse, Task},
+        errors::{ErrorCode, ErrorResponse},
+        events::{
+            GetDeviceEventRequest, GetEventExplanationRequest, GetEventExplanationResponse,
+            GetEventResponse, GetEventsResponse, GetLocalEventsResponse,
+        },
+        health::{ComponentStatus, ReadinessResponse},
+        reports::EnergyReport,
+        scheduling::{
+            Algorithm, EventChange, GetSchedulingRunDiffResponse, GetSchedulingRunsResponse,
+        },
+        tasks::{
+            CreateLocalTasksRequest, CreateLocalTasksResponse, CreateTaskRequest,
+            ExcludeTimespanRequest, GetLocalTasksResponse, GetTasksResponse, PinTaskRequest,
+            PreviewTaskResponse, Task,
+        },
         time::{DateTimeUtc, Timespan},
     };
+    use sqlx::{
+        postgres::{PgConnectOptions, PgPoolOptions},
+        Connection, Executor, PgConnection,
+    };
     use tower::{Service, ServiceExt};
 
-    async fn test_app() -> (Router, SqlitePool) {
-        let db_connection_string = "sqlite::memory:";
+    /// The database a test runs against.
+    #[derive(Clone)]
+    enum TestDatabase {
+        Sqlite,
+        /// A fresh database on the server given by `TEST_POSTGRES_URL`
+        Postgres {
+            server_url: String,
+            name: String,
+        },
+    }
 
-        let pool = SqlitePoolOptions::new()
-            .connect(db_connection_string)
-            .await
-            .unwrap();
+    impl TestDatabase {
+        /// Creates a fresh PostgreSQL database on the server given by `TEST_POSTGRES_URL`.
+        async fn postgres() -> TestDatabase {
+            let server_url = std::env::var("TEST_POSTGRES_URL")
+                .expect("TEST_POSTGRES_URL must be set to run the PostgreSQL tests");
+            let name = format!("test_{}", uuid::Uuid::new_v4().simple());
+
+            let mut connection = PgConnection::connect(&server_url).await.unwrap();
+            connection
+                .execute(format!(r#"CREATE DATABASE "{}""#, name).as_str())
+                .await
+                .unwrap();
+
+            TestDatabase::Postgres { server_url, name }
+        }
+
+        async fn connect(&self) -> Arc<dyn Repository> {
+            match self {
+                TestDatabase::Sqlite => Arc::new(SqliteRepository::in_memory().await.unwrap()),
+                TestDatabase::Postgres { server_url, name } => {
+                    let options = PgConnectOptions::from_str(server_url)
+                        .unwrap()
+                        .database(name);
+                    let pool = PgPoolOptions::new().connect_with(options).await.unwrap();
+                    let repository = PostgresRepository::new(pool);
+                    repository.migrate().await.unwrap();
+                    Arc::new(repository)
+                }
+            }
+        }
+
+        async fn drop_database(&self) {
+            if let TestDatabase::Postgres { server_url, name } = self {
+                let mut connection = PgConnection::connect(server_url).await.unwrap();
+                connection
+                    .execute(format!(r#"DROP DATABASE "{}" WITH (FORCE)"#, name).as_str())
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Runs every test against SQLite and against PostgreSQL. The PostgreSQL tests are ignored
+    /// by default, as they need a server in `TEST_POSTGRES_URL`.
+    macro_rules! database_tests {
+        ($($name:ident),* $(,)?) => {
+            mod sqlite {
+                $(
+                    #[tokio::test]
+                    async fn $name() {
+                        super::$name(super::TestDatabase::Sqlite).await;
+                    }
+                )*
+            }
+
+            mod postgres {
+                $(
+                    #[tokio::test]
+                    #[ignore = "needs a PostgreSQL server in TEST_POSTGRES_URL"]
+                    async fn $name() {
+                        let database = super::TestDatabase::postgres().await;
+                        super::$name(database.clone()).await;
+                        database.drop_database().await;
+                    }
+                )*
+            }
+        };
+    }
+
+    /// The current time, truncated to the microseconds that PostgreSQL stores.
+    fn now() -> DateTimeUtc {
+        Utc::now().trunc_subsecs(6)
+    }
+
+    const TEST_ADMIN_TOKEN: &str = "test-admin-token";
+
+    async fn test_app(database: &TestDatabase) -> (Router, Arc<dyn Repository>) {
+        test_app_with_mode(database, false).await
+    }
+
+    async fn test_app_with_mode(
+        database: &TestDatabase,
+        simulator_mode: bool,
+    ) -> (Router, Arc<dyn Repository>) {
+        let repository = database.connect().await;
 
         let (sender, receiver) = unbounded_channel();
 
@@ -206,13 +458,25 @@ mod tests {
         Box::leak(receiver);
 
         let state = MyState {
-            pool: pool.clone(),
+            repository: repository.clone(),
             sender,
+            algorithm: Arc::new(NaiveSchedulerAlgorithm::new()),
+            scheduling: SchedulingConfig::default(),
+            metrics: metrics_handle(),
+            admin_token: Some(TEST_ADMIN_TOKEN.to_owned()),
         };
 
-        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        (app(state, simulator_mode), repository)
+    }
 
-        (app(state, false), pool)
+    /// The id of the account that owns the auth token.
+    async fn account_id(repository: &Arc<dyn Repository>, auth_token: &str) -> AccountId {
+        let auth_token = AuthToken::try_parse(auth_token).unwrap();
+        repository
+            .account_for_auth_token(&auth_token)
+            .await
+            .unwrap()
+            .unwrap()
     }
 
     async fn get_account(app: &mut RouterIntoService<Body>, username: Option<String>) -> AuthToken {
@@ -291,6 +555,34 @@ mod tests {
         task
     }
 
+    async fn get_error(response: axum::response::Response) -> ErrorResponse {
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    async fn send_request(
+        app: &mut RouterIntoService<Body>,
+        method: Method,
+        uri: &str,
+        auth_token: String,
+        body: Body,
+    ) -> axum::response::Response {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Content-Type", "application/json")
+            .header("X-Auth-Token", auth_token)
+            .body(body)
+            .unwrap();
+
+        ServiceExt::<Request<Body>>::ready(app)
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap()
+    }
+
     async fn get_tasks(app: &mut RouterIntoService<Body>, auth_token: String) -> Vec<Task> {
         let request = Request::builder()
             .method(Method::GET)
@@ -428,17 +720,45 @@ mod tests {
         }
     }
 
-    #[tokio::test]
-    async fn register_account() {
-        let (router, _) = test_app().await;
+    async fn register_account(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        get_account(&mut app, None).await;
+    }
+
+    async fn register_account_twice_fails(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
         let mut app = router.into_service();
 
         get_account(&mut app, None).await;
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/accounts/register")
+            .header("Content-Type", "application/json")
+            .body(Body::from(
+                serde_json::to_vec(&RegisterOrLoginRequest {
+                    username: "test_user".to_string(),
+                    password: "test_password".to_string(),
+                })
+                .unwrap(),
+            ))
+            .unwrap();
+
+        let response = ServiceExt::<Request<Body>>::ready(&mut app)
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(get_error(response).await.code, ErrorCode::Conflict);
     }
 
-    #[tokio::test]
-    async fn login_to_account() {
-        let (router, _) = test_app().await;
+    async fn login_to_account(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
         let mut app = router.into_service();
 
         // Registers an account
@@ -475,9 +795,8 @@ mod tests {
         let _response: RegisterOrLoginResponse = serde_json::from_slice(&body).unwrap();
     }
 
-    #[tokio::test]
-    async fn create_task_test() {
-        let (router, _) = test_app().await;
+    async fn create_task_test(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
         let mut app = router.into_service();
 
         // Registers an account
@@ -490,8 +809,8 @@ mod tests {
             auth_token,
             Duration::hours(1),
             &device,
-            Utc::now(),
-            Utc::now().checked_add_days(Days::new(1)).unwrap(),
+            now(),
+            now().checked_add_days(Days::new(1)).unwrap(),
         )
         .await;
 
@@ -499,9 +818,8 @@ mod tests {
         assert_eq!(task.duration, Duration::hours(1).into());
     }
 
-    #[tokio::test]
-    async fn create_task_fails_with_invalid_device() {
-        let (router, _) = test_app().await;
+    async fn create_task_fails_with_invalid_device(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
         let mut app = router.into_service();
 
         // Register an account
@@ -515,10 +833,7 @@ mod tests {
             .header("X-Auth-Token", auth_token.clone())
             .body(Body::from(
                 serde_json::to_vec(&CreateTaskRequest {
-                    timespan: Timespan::new(
-                        Utc::now(),
-                        Utc::now().checked_add_days(Days::new(1)).unwrap(),
-                    ),
+                    timespan: Timespan::new(now(), now().checked_add_days(Days::new(1)).unwrap()),
                     duration: 3600.into(),
                     device_id: 99999.into(),
                 })
@@ -534,7 +849,8 @@ mod tests {
             .unwrap();
 
         // Cannot register task to non-existant device.
-        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get_error(response).await.code, ErrorCode::NotFound);
 
         let device = generate_device(&mut app, auth_token, "test".into(), 1234.0).await;
 
@@ -549,10 +865,7 @@ mod tests {
             .header("X-Auth-Token", auth_token.clone())
             .body(Body::from(
                 serde_json::to_vec(&CreateTaskRequest {
-                    timespan: Timespan::new(
-                        Utc::now(),
-                        Utc::now().checked_add_days(Days::new(1)).unwrap(),
-                    ),
+                    timespan: Timespan::new(now(), now().checked_add_days(Days::new(1)).unwrap()),
                     duration: 3600.into(),
                     device_id: device.id,
                 })
@@ -568,79 +881,119 @@ mod tests {
             .unwrap();
 
         // Cannot register task to a device not owned by the account.
-        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get_error(response).await.code, ErrorCode::NotFound);
     }
 
-    #[tokio::test]
-    async fn get_tasks_test() {
-        let (router, _) = test_app().await;
+    async fn create_task_fails_validation(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
         let mut app = router.into_service();
 
-        // Registers an account
         let auth_token = get_account(&mut app, None).await;
         let auth_token = auth_token.to_string();
 
         let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
-        let task = generate_task(
-            &mut app,
-            auth_token.clone(),
-            Duration::hours(1),
-            &device,
-            Utc::now(),
-            Utc::now().checked_add_days(Days::new(1)).unwrap(),
-        )
-        .await;
-        let all_tasks = get_tasks(&mut app, auth_token).await;
-
-        assert_eq!(all_tasks.first().unwrap(), &task);
-    }
-
-    #[tokio::test]
-    async fn delete_task_test() {
-        let (router, _) = test_app().await;
-        let mut app = router.into_service();
 
-        let auth_token = get_account(&mut app, None).await;
-        let auth_token = auth_token.to_string();
+        let now = now();
+        let invalid_requests = vec![
+            // Zero duration
+            (
+                Timespan::new(now, now + Duration::hours(2)),
+                Duration::zero(),
+                "duration",
+            ),
+            // Negative duration
+            (
+                Timespan::new(now, now + Duration::hours(2)),
+                Duration::hours(-1),
+                "duration",
+            ),
+            // End before start
+            (
+                Timespan {
+                    start: now + Duration::hours(2),
+                    end: now,
+                },
+                Duration::hours(1),
+                "timespan",
+            ),
+            // Duration longer than the timespan
+            (
+                Timespan::new(now, now + Duration::hours(2)),
+                Duration::hours(3),
+                "duration",
+            ),
+            // Timespan entirely in the past
+            (
+                Timespan::new(now - Duration::hours(3), now - Duration::hours(1)),
+                Duration::hours(1),
+                "timespan",
+            ),
+        ];
 
-        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
-        let task = generate_task(
-            &mut app,
-            auth_token.clone(),
-            Duration::hours(1),
-            &device,
-            Utc::now(),
-            Utc::now().checked_add_days(Days::new(1)).unwrap(),
-        )
-        .await;
-        delete_task(&mut app, auth_token.clone(), task).await;
+        for (timespan, duration, field) in invalid_requests {
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri("/tasks/create")
+                .header("Content-Type", "application/json")
+                .header("X-Auth-Token", auth_token.clone())
+                .body(Body::from(
+                    serde_json::to_vec(&CreateTaskRequest {
+                        timespan,
+                        duration: duration.into(),
+                        device_id: device.id,
+                    })
+                    .unwrap(),
+                ))
+                .unwrap();
+
+            let response = ServiceExt::<Request<Body>>::ready(&mut app)
+                .await
+                .unwrap()
+                .call(request)
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let error = get_error(response).await;
+            assert_eq!(error.code, ErrorCode::ValidationFailed);
+            assert_eq!(error.details.first().unwrap().field.as_deref(), Some(field));
+        }
 
         let all_tasks = get_tasks(&mut app, auth_token).await;
 
         assert!(all_tasks.is_empty());
     }
 
-    #[tokio::test]
-    async fn get_devices_test() {
-        let (router, _) = test_app().await;
+    async fn create_task_fails_with_malformed_body(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
         let mut app = router.into_service();
 
         let auth_token = get_account(&mut app, None).await;
         let auth_token = auth_token.to_string();
 
-        let created_devices = vec![
-            generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await,
-            generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await,
-        ];
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/tasks/create")
+            .header("Content-Type", "application/json")
+            .header("X-Auth-Token", auth_token)
+            .body(Body::from(r#"{"duration": "one hour"}"#))
+            .unwrap();
 
-        let all_devices = get_devices(&mut app, auth_token).await;
+        let response = ServiceExt::<Request<Body>>::ready(&mut app)
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap();
 
-        assert_eq!(all_devices, created_devices);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get_error(response).await.code, ErrorCode::BadRequest);
     }
 
-    #[tokio::test]
-    async fn create_device_test() {
-        let (router, _) = test_app().await;
+    async fn get_tasks_test(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
         let mut app = router.into_service();
 
         // Registers an account
@@ -648,29 +1001,22 @@ mod tests {
         let auth_token = auth_token.to_string();
 
         let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
+        let task = generate_task(
+            &mut app,
+            auth_token.clone(),
+            Duration::hours(1),
+            &device,
+            now(),
+            now().checked_add_days(Days::new(1)).unwrap(),
+        )
+        .await;
+        let all_tasks = get_tasks(&mut app, auth_token).await;
 
-        assert_eq!(device.effect, 1000.0);
-    }
-
-    #[tokio::test]
-    async fn delete_device_test() {
-        let (router, _) = test_app().await;
-        let mut app = router.into_service();
-
-        let auth_token = get_account(&mut app, None).await;
-        let auth_token = auth_token.to_string();
-
-        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
-        delete_device(&mut app, auth_token.clone(), device).await;
-
-        let all_devices = get_devices(&mut app, auth_token).await;
-
-        assert!(all_devices.is_empty());
+        assert_eq!(all_tasks.first().unwrap(), &task);
     }
 
-    #[tokio::test]
-    async fn get_all_events() {
-        let (router, pool) = test_app().await;
+    async fn delete_task_test(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
         let mut app = router.into_service();
 
         let auth_token = get_account(&mut app, None).await;
@@ -682,68 +1028,46 @@ mod tests {
             auth_token.clone(),
             Duration::hours(1),
             &device,
-            Utc::now(),
-            Utc::now().checked_add_days(Days::new(1)).unwrap(),
+            now(),
+            now().checked_add_days(Days::new(1)).unwrap(),
         )
         .await;
-        let event = _create_event(&pool, &task, Utc::now()).await.unwrap();
-
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/events/all")
-            .header("Content-Type", "application/json")
-            .header("X-Auth-Token", auth_token)
-            .body(Body::empty())
-            .unwrap();
-
-        let response = ServiceExt::<Request<Body>>::ready(&mut app)
-            .await
-            .unwrap()
-            .call(request)
-            .await
-            .unwrap();
-
-        if response.status() != StatusCode::OK {
-            let body = response.into_body().collect().await.unwrap().to_bytes();
-            let body = String::from_utf8_lossy(&body);
-            panic!("{}", body);
-        }
+        delete_task(&mut app, auth_token.clone(), task).await;
 
-        let body = response.into_body().collect().await.unwrap().to_bytes();
-        let response: GetEventsResponse = serde_json::from_slice(&body).unwrap();
+        let all_tasks = get_tasks(&mut app, auth_token).await;
 
-        assert_eq!(response.events.first().unwrap(), &event);
+        assert!(all_tasks.is_empty());
     }
 
-    #[tokio::test]
-    async fn get_all_valid_events() {
-        let (router, pool) = test_app().await;
+    async fn delete_task_of_other_account_fails(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
         let mut app = router.into_service();
 
         let auth_token = get_account(&mut app, None).await;
         let auth_token = auth_token.to_string();
 
         let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
-        let start = Utc::now() - Duration::hours(1);
-        let end = Utc::now() + Duration::hours(2);
         let task = generate_task(
             &mut app,
             auth_token.clone(),
             Duration::hours(1),
             &device,
-            start,
-            end,
+            now(),
+            now().checked_add_days(Days::new(1)).unwrap(),
         )
         .await;
-        let event1 = _create_event(&pool, &task, Utc::now() - Duration::minutes(30))
-            .await
-            .unwrap();
+
+        let other_auth_token = get_account(&mut app, Some("test_user_2".to_string())).await;
+        let other_auth_token = other_auth_token.to_string();
+
+        // The other account also owns a device, which must not grant access to the task
+        generate_device(&mut app, other_auth_token.clone(), "test".into(), 1000.0).await;
 
         let request = Request::builder()
-            .method(Method::GET)
-            .uri("/events/all")
+            .method(Method::DELETE)
+            .uri(format!("/tasks/delete?id={}", task.id))
             .header("Content-Type", "application/json")
-            .header("X-Auth-Token", auth_token)
+            .header("X-Auth-Token", other_auth_token)
             .body(Body::empty())
             .unwrap();
 
@@ -754,46 +1078,53 @@ mod tests {
             .await
             .unwrap();
 
-        if response.status() != StatusCode::OK {
-            let body = response.into_body().collect().await.unwrap().to_bytes();
-            let body = String::from_utf8_lossy(&body);
-            panic!("{}", body);
-        }
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get_error(response).await.code, ErrorCode::NotFound);
 
-        let body = response.into_body().collect().await.unwrap().to_bytes();
-        let response: GetEventsResponse = serde_json::from_slice(&body).unwrap();
+        let all_tasks = get_tasks(&mut app, auth_token).await;
 
-        assert_eq!(response.events.first().unwrap(), &event1);
+        assert_eq!(all_tasks, vec![task]);
     }
 
-    #[tokio::test]
-    async fn get_device_event() {
-        let (router, pool) = test_app().await;
+    async fn preview_task_test(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
         let mut app = router.into_service();
 
         let auth_token = get_account(&mut app, None).await;
         let auth_token = auth_token.to_string();
 
-        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 20.0).await;
-        let task = generate_task(
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
+        let existing_task = generate_task(
+            &mut app,
+            auth_token.clone(),
+            Duration::hours(1),
+            &device,
+            now(),
+            now() + Duration::hours(12),
+        )
+        .await;
+        // Tasks beyond the scheduling horizon do not prevent a preview
+        let later_task = generate_task(
             &mut app,
             auth_token.clone(),
             Duration::hours(1),
             &device,
-            Utc::now(),
-            Utc::now().checked_add_days(Days::new(1)).unwrap(),
+            now() + Duration::hours(48),
+            now() + Duration::hours(60),
         )
         .await;
-        let event_start = Utc::now() + Duration::seconds(10);
-        let event = _create_event(&pool, &task, event_start).await.unwrap();
 
+        let start = now();
+        let end = start + Duration::hours(12);
         let request = Request::builder()
-            .method(Method::GET)
-            .uri("/events/get")
+            .method(Method::POST)
+            .uri("/tasks/preview")
             .header("Content-Type", "application/json")
-            .header("X-Auth-Token", auth_token)
+            .header("X-Auth-Token", auth_token.clone())
             .body(Body::from(
-                serde_json::to_vec(&GetDeviceEventRequest {
+                serde_json::to_vec(&CreateTaskRequest {
+                    timespan: Timespan::new(start, end),
+                    duration: Duration::hours(2).into(),
                     device_id: device.id,
                 })
                 .unwrap(),
@@ -814,40 +1145,385 @@ mod tests {
         }
 
         let body = response.into_body().collect().await.unwrap().to_bytes();
-        let response: GetEventResponse = serde_json::from_slice(&body).unwrap();
+        let preview: PreviewTaskResponse = serde_json::from_slice(&body).unwrap();
 
-        assert_eq!(response.event, event.into());
+        assert!(preview.start_time >= start);
+        assert!(preview.start_time + Duration::hours(2) <= end);
+        assert!((0.0..=1.0).contains(&preview.renewable_coverage));
+
+        // Previewing must not create tasks or events
+        let all_tasks = get_tasks(&mut app, auth_token.clone()).await;
+        assert_eq!(all_tasks, vec![existing_task, later_task]);
+
+        let account_id = account_id(&repository, &auth_token).await;
+        let events = repository
+            .events_for_account(&account_id, now())
+            .await
+            .unwrap();
+        assert!(events.is_empty());
     }
 
-    #[tokio::test]
-    async fn get_device_event_none() {
-        let (router, pool) = test_app().await;
+    async fn pin_and_unpin_task(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
         let mut app = router.into_service();
 
         let auth_token = get_account(&mut app, None).await;
         let auth_token = auth_token.to_string();
 
-        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 20.0).await;
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
+        let start = now();
         let task = generate_task(
             &mut app,
             auth_token.clone(),
             Duration::hours(1),
             &device,
-            Utc::now(),
-            Utc::now().checked_add_days(Days::new(1)).unwrap(),
+            start,
+            start + Duration::hours(12),
         )
         .await;
-        let event_start = Utc::now() - Duration::seconds(10);
-        let _ = _create_event(&pool, &task, event_start).await.unwrap();
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/events/get")
-            .header("Content-Type", "application/json")
-            .header("X-Auth-Token", auth_token)
-            .body(Body::from(
-                serde_json::to_vec(&GetDeviceEventRequest {
-                    device_id: device.id,
+        // Pinning outside of the timespan is rejected
+        let response = send_request(
+            &mut app,
+            Method::POST,
+            "/tasks/pin",
+            auth_token.clone(),
+            Body::from(
+                serde_json::to_vec(&PinTaskRequest {
+                    id: task.id,
+                    start_time: start + Duration::hours(11) + Duration::minutes(30),
+                })
+                .unwrap(),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // Pinning in the past is rejected
+        let response = send_request(
+            &mut app,
+            Method::POST,
+            "/tasks/pin",
+            auth_token.clone(),
+            Body::from(
+                serde_json::to_vec(&PinTaskRequest {
+                    id: task.id,
+                    start_time: start - Duration::minutes(1),
+                })
+                .unwrap(),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let pinned_start = start + Duration::hours(2);
+        let response = send_request(
+            &mut app,
+            Method::POST,
+            "/tasks/pin",
+            auth_token.clone(),
+            Body::from(
+                serde_json::to_vec(&PinTaskRequest {
+                    id: task.id,
+                    start_time: pinned_start,
+                })
+                .unwrap(),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let excluded = Timespan::new(start, start + Duration::hours(1));
+        let response = send_request(
+            &mut app,
+            Method::POST,
+            "/tasks/exclude",
+            auth_token.clone(),
+            Body::from(
+                serde_json::to_vec(&ExcludeTimespanRequest {
+                    id: task.id,
+                    timespan: excluded.clone(),
+                })
+                .unwrap(),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let all_tasks = get_tasks(&mut app, auth_token.clone()).await;
+        assert_eq!(all_tasks[0].pinned_start, Some(pinned_start));
+        assert_eq!(all_tasks[0].exclusions, vec![excluded.clone()]);
+
+        // Pinning into an excluded timespan is rejected
+        let response = send_request(
+            &mut app,
+            Method::POST,
+            "/tasks/pin",
+            auth_token.clone(),
+            Body::from(
+                serde_json::to_vec(&PinTaskRequest {
+                    id: task.id,
+                    start_time: start + Duration::minutes(30),
+                })
+                .unwrap(),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // The scheduler never moves a pinned task
+        let mut graph = SchedulingConfig::default().production_graph(now());
+        run_algorithm(repository.as_ref(), &Algorithm::Naive, &mut graph)
+            .await
+            .unwrap();
+
+        let account_id = account_id(&repository, &auth_token).await;
+        let events = repository
+            .events_for_account(&account_id, now())
+            .await
+            .unwrap();
+        assert_eq!(events[0].start_time, pinned_start);
+
+        let response = send_request(
+            &mut app,
+            Method::DELETE,
+            &format!("/tasks/unpin?id={}", task.id),
+            auth_token.clone(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Unpinning keeps the exclusions
+        let all_tasks = get_tasks(&mut app, auth_token.clone()).await;
+        assert_eq!(all_tasks[0].pinned_start, None);
+        assert_eq!(all_tasks[0].exclusions, vec![excluded]);
+
+        let response = send_request(
+            &mut app,
+            Method::DELETE,
+            &format!("/tasks/exclusions/clear?id={}", task.id),
+            auth_token.clone(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let all_tasks = get_tasks(&mut app, auth_token).await;
+        assert!(all_tasks[0].exclusions.is_empty());
+    }
+
+    async fn get_devices_test(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await;
+        let auth_token = auth_token.to_string();
+
+        let created_devices = vec![
+            generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await,
+            generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await,
+        ];
+
+        let all_devices = get_devices(&mut app, auth_token).await;
+
+        assert_eq!(all_devices, created_devices);
+    }
+
+    async fn create_device_test(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        // Registers an account
+        let auth_token = get_account(&mut app, None).await;
+        let auth_token = auth_token.to_string();
+
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
+
+        assert_eq!(device.effect, 1000.0);
+    }
+
+    async fn create_device_fails_validation(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await;
+        let auth_token = auth_token.to_string();
+
+        for (name, effect, field) in [("test", -10.0, "effect"), ("", 1000.0, "name")] {
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri("/devices/create")
+                .header("Content-Type", "application/json")
+                .header("X-Auth-Token", auth_token.clone())
+                .body(Body::from(
+                    serde_json::to_vec(&CreateDeviceRequest {
+                        name: name.to_string(),
+                        effect,
+                    })
+                    .unwrap(),
+                ))
+                .unwrap();
+
+            let response = ServiceExt::<Request<Body>>::ready(&mut app)
+                .await
+                .unwrap()
+                .call(request)
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let error = get_error(response).await;
+            assert_eq!(error.code, ErrorCode::ValidationFailed);
+            assert_eq!(error.details.first().unwrap().field.as_deref(), Some(field));
+        }
+
+        let all_devices = get_devices(&mut app, auth_token).await;
+
+        assert!(all_devices.is_empty());
+    }
+
+    async fn delete_device_test(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await;
+        let auth_token = auth_token.to_string();
+
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
+        delete_device(&mut app, auth_token.clone(), device).await;
+
+        let all_devices = get_devices(&mut app, auth_token).await;
+
+        assert!(all_devices.is_empty());
+    }
+
+    async fn get_all_events(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await;
+        let auth_token = auth_token.to_string();
+
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
+        let task = generate_task(
+            &mut app,
+            auth_token.clone(),
+            Duration::hours(1),
+            &device,
+            now(),
+            now().checked_add_days(Days::new(1)).unwrap(),
+        )
+        .await;
+        let event = repository.create_event(task.id, now()).await.unwrap();
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/events/all")
+            .header("Content-Type", "application/json")
+            .header("X-Auth-Token", auth_token)
+            .body(Body::empty())
+            .unwrap();
+
+        let response = ServiceExt::<Request<Body>>::ready(&mut app)
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap();
+
+        if response.status() != StatusCode::OK {
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let body = String::from_utf8_lossy(&body);
+            panic!("{}", body);
+        }
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let response: GetEventsResponse = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(response.events.first().unwrap(), &event);
+    }
+
+    async fn get_all_valid_events(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await;
+        let auth_token = auth_token.to_string();
+
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
+        let start = now() - Duration::hours(1);
+        let end = now() + Duration::hours(2);
+        let task = generate_task(
+            &mut app,
+            auth_token.clone(),
+            Duration::hours(1),
+            &device,
+            start,
+            end,
+        )
+        .await;
+        let event1 = repository
+            .create_event(task.id, now() - Duration::minutes(30))
+            .await
+            .unwrap();
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/events/all")
+            .header("Content-Type", "application/json")
+            .header("X-Auth-Token", auth_token)
+            .body(Body::empty())
+            .unwrap();
+
+        let response = ServiceExt::<Request<Body>>::ready(&mut app)
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap();
+
+        if response.status() != StatusCode::OK {
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let body = String::from_utf8_lossy(&body);
+            panic!("{}", body);
+        }
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let response: GetEventsResponse = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(response.events.first().unwrap(), &event1);
+    }
+
+    async fn get_device_event(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await;
+        let auth_token = auth_token.to_string();
+
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 20.0).await;
+        let task = generate_task(
+            &mut app,
+            auth_token.clone(),
+            Duration::hours(1),
+            &device,
+            now(),
+            now().checked_add_days(Days::new(1)).unwrap(),
+        )
+        .await;
+        let event_start = now() + Duration::seconds(10);
+        let event = repository.create_event(task.id, event_start).await.unwrap();
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/events/get")
+            .header("Content-Type", "application/json")
+            .header("X-Auth-Token", auth_token)
+            .body(Body::from(
+                serde_json::to_vec(&GetDeviceEventRequest {
+                    device_id: device.id,
                 })
                 .unwrap(),
             ))
@@ -869,6 +1545,1140 @@ mod tests {
         let body = response.into_body().collect().await.unwrap().to_bytes();
         let response: GetEventResponse = serde_json::from_slice(&body).unwrap();
 
-        assert!(response.event.is_none());
+        assert_eq!(response.event, event.into());
     }
+
+    async fn get_device_event_none(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await;
+        let auth_token = auth_token.to_string();
+
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 20.0).await;
+        let task = generate_task(
+            &mut app,
+            auth_token.clone(),
+            Duration::hours(1),
+            &device,
+            now(),
+            now().checked_add_days(Days::new(1)).unwrap(),
+        )
+        .await;
+        let event_start = now() - Duration::seconds(10);
+        let _ = repository.create_event(task.id, event_start).await.unwrap();
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/events/get")
+            .header("Content-Type", "application/json")
+            .header("X-Auth-Token", auth_token)
+            .body(Body::from(
+                serde_json::to_vec(&GetDeviceEventRequest {
+                    device_id: device.id,
+                })
+                .unwrap(),
+            ))
+            .unwrap();
+
+        let response = ServiceExt::<Request<Body>>::ready(&mut app)
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap();
+
+        if response.status() != StatusCode::OK {
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let body = String::from_utf8_lossy(&body);
+            panic!("{}", body);
+        }
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let response: GetEventResponse = serde_json::from_slice(&body).unwrap();
+
+        assert!(response.event.is_none());
+    }
+
+    async fn get_event_explanation(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await.to_string();
+        let other_token = get_account(&mut app, Some("other_user".to_string()))
+            .await
+            .to_string();
+
+        let start = now();
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
+        let task = generate_task(
+            &mut app,
+            auth_token.clone(),
+            Duration::hours(1),
+            &device,
+            start,
+            start + Duration::hours(4),
+        )
+        .await;
+
+        let mut graph = DiscreteGraph::new(
+            vec![0.0, 500.0, 2000.0, 0.0, 0.0],
+            Duration::hours(1),
+            start,
+        );
+        run_algorithm(repository.as_ref(), &Algorithm::Global, &mut graph)
+            .await
+            .unwrap();
+
+        let account_id = account_id(&repository, &auth_token).await;
+        let events = repository
+            .events_for_account(&account_id, start)
+            .await
+            .unwrap();
+        let request = serde_json::to_vec(&GetEventExplanationRequest {
+            event_id: events[0].id,
+        })
+        .unwrap();
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/events/explanation",
+            auth_token.clone(),
+            Body::from(request.clone()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let explanation = serde_json::from_slice::<GetEventExplanationResponse>(&body)
+            .unwrap()
+            .explanation;
+        assert_eq!(explanation.chosen.start_time, events[0].start_time);
+        assert_eq!(explanation.chosen.start_time, start + Duration::hours(2));
+        assert_eq!(explanation.chosen.renewable_coverage, 1.0);
+        assert_eq!(
+            explanation.alternatives[0].start_time,
+            start + Duration::hours(1)
+        );
+        assert!(explanation.binding_constraints.is_empty());
+
+        // Other accounts cannot see why the event was scheduled
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/events/explanation",
+            other_token,
+            Body::from(request.clone()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get_error(response).await.code, ErrorCode::NotFound);
+
+        // A pinned event has no explanation, even though its start time is unchanged
+        let response = send_request(
+            &mut app,
+            Method::POST,
+            "/tasks/pin",
+            auth_token.clone(),
+            Body::from(
+                serde_json::to_vec(&PinTaskRequest {
+                    id: task.id,
+                    start_time: events[0].start_time,
+                })
+                .unwrap(),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut graph = DiscreteGraph::new(
+            vec![0.0, 500.0, 2000.0, 0.0, 0.0],
+            Duration::hours(1),
+            start,
+        );
+        run_algorithm(repository.as_ref(), &Algorithm::Global, &mut graph)
+            .await
+            .unwrap();
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/events/explanation",
+            auth_token,
+            Body::from(request.clone()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn rescheduled_event_keeps_its_id(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await.to_string();
+        let start = now();
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
+        generate_task(
+            &mut app,
+            auth_token.clone(),
+            Duration::hours(1),
+            &device,
+            start,
+            start + Duration::hours(4),
+        )
+        .await;
+
+        let account_id = account_id(&repository, &auth_token).await;
+        let mut events = Vec::new();
+        // The best hour moves from the third to the second
+        for values in [
+            vec![0.0, 500.0, 2000.0, 0.0, 0.0],
+            vec![0.0, 2000.0, 500.0, 0.0, 0.0],
+        ] {
+            let mut graph = DiscreteGraph::new(values, Duration::hours(1), start);
+            run_algorithm(repository.as_ref(), &Algorithm::Global, &mut graph)
+                .await
+                .unwrap();
+            let event = repository
+                .events_for_account(&account_id, start)
+                .await
+                .unwrap()
+                .remove(0);
+            events.push(event);
+        }
+
+        assert_eq!(events[0].start_time, start + Duration::hours(2));
+        assert_eq!(events[1].start_time, start + Duration::hours(1));
+        assert_eq!(events[1].id, events[0].id);
+
+        // The id the client already has still finds the explanation
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/events/explanation",
+            auth_token,
+            Body::from(
+                serde_json::to_vec(&GetEventExplanationRequest {
+                    event_id: events[0].id,
+                })
+                .unwrap(),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let explanation = serde_json::from_slice::<GetEventExplanationResponse>(&body)
+            .unwrap()
+            .explanation;
+        assert_eq!(explanation.chosen.start_time, events[1].start_time);
+    }
+
+    async fn get_algorithms_test(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/scheduling/algorithms",
+            String::new(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let response: GetAlgorithmsResponse = serde_json::from_slice(&body).unwrap();
+        let algorithms: Vec<Algorithm> = response
+            .algorithms
+            .into_iter()
+            .map(|info| info.algorithm)
+            .collect();
+
+        assert_eq!(
+            algorithms,
+            vec![
+                Algorithm::Naive,
+                Algorithm::Global,
+                Algorithm::AllPermutations { max_tasks: None }
+            ]
+        );
+    }
+
+    async fn run_scheduling_rejects_unknown_algorithm(database: TestDatabase) {
+        let (router, _) = test_app_with_mode(&database, true).await;
+        let mut app = router.into_service();
+
+        let graph = DiscreteGraph::new(vec![1.0, 2.0], Duration::hours(1), now());
+        let mut body = serde_json::to_value(SchedulingGlob {
+            discrete_graph: graph,
+            algorithm: Algorithm::Global,
+        })
+        .unwrap();
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/scheduling/run",
+            String::new(),
+            Body::from(body.to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        body["algorithm"] = serde_json::json!({ "name": "simulated_annealing" });
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/scheduling/run",
+            String::new(),
+            Body::from(body.to_string()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get_error(response).await.code, ErrorCode::BadRequest);
+    }
+
+    async fn calendar_feeds(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await.to_string();
+        let other_auth_token = get_account(&mut app, Some("other_user".to_string()))
+            .await
+            .to_string();
+
+        let washer = generate_device(&mut app, auth_token.clone(), "Washer".into(), 1000.0).await;
+        let dryer = generate_device(&mut app, auth_token.clone(), "Dryer".into(), 2000.0).await;
+        let start = now();
+        for device in [&washer, &dryer] {
+            let task = generate_task(
+                &mut app,
+                auth_token.clone(),
+                Duration::hours(1),
+                device,
+                start,
+                start + Duration::hours(12),
+            )
+            .await;
+            repository
+                .create_event(task.id, start + Duration::hours(2))
+                .await
+                .unwrap();
+        }
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/calendar/events.ics",
+            auth_token.clone(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let calendar = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 2);
+        assert!(calendar.contains("SUMMARY:Washer"));
+        assert!(calendar.contains("SUMMARY:Dryer"));
+
+        // Feeds can only be created for the account's own devices
+        let response = send_request(
+            &mut app,
+            Method::POST,
+            "/calendar/feeds/create",
+            other_auth_token,
+            Body::from(
+                serde_json::to_vec(&CreateCalendarFeedRequest {
+                    device_id: Some(washer.id),
+                })
+                .unwrap(),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = send_request(
+            &mut app,
+            Method::POST,
+            "/calendar/feeds/create",
+            auth_token.clone(),
+            Body::from(
+                serde_json::to_vec(&CreateCalendarFeedRequest {
+                    device_id: Some(washer.id),
+                })
+                .unwrap(),
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let feed = serde_json::from_slice::<CreateCalendarFeedResponse>(&body)
+            .unwrap()
+            .feed;
+
+        // The feed is read with the secret in the url instead of an auth token
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            &feed.path(),
+            String::new(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let calendar = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 1);
+        assert!(calendar.contains("SUMMARY:Washer"));
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/calendar/feeds/all",
+            auth_token.clone(),
+            Body::empty(),
+        )
+        .await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let feeds = serde_json::from_slice::<GetCalendarFeedsResponse>(&body)
+            .unwrap()
+            .feeds;
+        assert_eq!(feeds, vec![feed.clone()]);
+
+        let response = send_request(
+            &mut app,
+            Method::DELETE,
+            &format!("/calendar/feeds/delete?secret={}", feed.secret),
+            auth_token,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // A revoked feed can no longer be read
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            &feed.path(),
+            String::new(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn update_time_zone(
+        app: &mut RouterIntoService<Body>,
+        auth_token: String,
+        time_zone: &str,
+    ) -> axum::response::Response {
+        send_request(
+            app,
+            Method::POST,
+            "/accounts/settings/update",
+            auth_token,
+            Body::from(
+                serde_json::to_vec(&AccountSettings {
+                    time_zone: time_zone.to_owned(),
+                })
+                .unwrap(),
+            ),
+        )
+        .await
+    }
+
+    async fn account_time_zone_settings(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await.to_string();
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/accounts/settings",
+            auth_token.clone(),
+            Body::empty(),
+        )
+        .await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let settings = serde_json::from_slice::<AccountSettings>(&body).unwrap();
+        assert_eq!(settings.time_zone, "UTC");
+
+        let response = update_time_zone(&mut app, auth_token.clone(), "Mars/Olympus_Mons").await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let error = get_error(response).await;
+        assert_eq!(error.code, ErrorCode::ValidationFailed);
+        assert_eq!(
+            error.details.first().unwrap().field.as_deref(),
+            Some("time_zone")
+        );
+
+        let response = update_time_zone(&mut app, auth_token.clone(), "Europe/Copenhagen").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/accounts/settings",
+            auth_token,
+            Body::empty(),
+        )
+        .await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let settings = serde_json::from_slice::<AccountSettings>(&body).unwrap();
+        assert_eq!(settings.time_zone, "Europe/Copenhagen");
+    }
+
+    async fn update_baseline_request(
+        app: &mut RouterIntoService<Body>,
+        auth_token: String,
+        request: &SetBaselineRequest,
+    ) -> axum::response::Response {
+        send_request(
+            app,
+            Method::POST,
+            "/accounts/baseline/update",
+            auth_token,
+            Body::from(serde_json::to_vec(request).unwrap()),
+        )
+        .await
+    }
+
+    async fn get_baseline_profile(
+        app: &mut RouterIntoService<Body>,
+        auth_token: String,
+    ) -> Option<DailyProfile> {
+        let response = send_request(
+            app,
+            Method::GET,
+            "/accounts/baseline",
+            auth_token,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice::<GetBaselineResponse>(&body)
+            .unwrap()
+            .profile
+    }
+
+    async fn baseline_consumption(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await.to_string();
+        let other_auth_token = get_account(&mut app, Some("other_user".to_string()))
+            .await
+            .to_string();
+        assert_eq!(
+            get_baseline_profile(&mut app, auth_token.clone()).await,
+            None
+        );
+
+        let invalid = SetBaselineRequest::Profile {
+            profile: DailyProfile {
+                resolution_minutes: 60,
+                values: vec![100.0; 23],
+            },
+        };
+        let response = update_baseline_request(&mut app, auth_token.clone(), &invalid).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let error = get_error(response).await;
+        assert_eq!(
+            error.details.first().unwrap().field.as_deref(),
+            Some("values")
+        );
+
+        let standard = SetBaselineRequest::Standard {
+            load_profile: StandardLoadProfile::Household,
+            annual_kwh: 3000.0,
+        };
+        let response = update_baseline_request(&mut app, auth_token.clone(), &standard).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let generated = serde_json::from_slice::<DailyProfile>(&body).unwrap();
+        assert_eq!(generated.values.len(), 24);
+        assert_eq!(
+            get_baseline_profile(&mut app, auth_token.clone()).await,
+            Some(generated)
+        );
+
+        let uploaded = DailyProfile {
+            resolution_minutes: 720,
+            values: vec![150.0, 300.0],
+        };
+        let response = update_baseline_request(
+            &mut app,
+            auth_token.clone(),
+            &SetBaselineRequest::Profile {
+                profile: uploaded.clone(),
+            },
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            get_baseline_profile(&mut app, auth_token.clone()).await,
+            Some(uploaded)
+        );
+        // The baseline belongs to the account
+        assert_eq!(
+            get_baseline_profile(&mut app, other_auth_token.clone()).await,
+            None
+        );
+
+        for (auth_token, status) in [
+            (other_auth_token, StatusCode::NOT_FOUND),
+            (auth_token.clone(), StatusCode::OK),
+            (auth_token.clone(), StatusCode::NOT_FOUND),
+        ] {
+            let response = send_request(
+                &mut app,
+                Method::DELETE,
+                "/accounts/baseline/delete",
+                auth_token,
+                Body::empty(),
+            )
+            .await;
+            assert_eq!(response.status(), status);
+        }
+        assert_eq!(get_baseline_profile(&mut app, auth_token).await, None);
+    }
+
+    async fn local_tasks_and_events(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await.to_string();
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
+        let response = update_time_zone(&mut app, auth_token.clone(), "America/New_York").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let time_zone = chrono_tz::America::New_York;
+        let tomorrow = Utc::now().with_timezone(&time_zone).date_naive() + Days::new(1);
+        let evening = NaiveTime::from_hms_opt(22, 0, 0).unwrap();
+        let morning = NaiveTime::from_hms_opt(6, 0, 0).unwrap();
+        let create_request = |days| CreateLocalTasksRequest {
+            device_id: device.id,
+            duration: Duration::hours(1).into(),
+            date: tomorrow,
+            start: evening,
+            end: morning,
+            days,
+        };
+
+        let response = send_request(
+            &mut app,
+            Method::POST,
+            "/tasks/create_local",
+            auth_token.clone(),
+            Body::from(serde_json::to_vec(&create_request(0)).unwrap()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = send_request(
+            &mut app,
+            Method::POST,
+            "/tasks/create_local",
+            auth_token.clone(),
+            Body::from(serde_json::to_vec(&create_request(2)).unwrap()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let created = serde_json::from_slice::<CreateLocalTasksResponse>(&body)
+            .unwrap()
+            .tasks;
+        assert_eq!(created.len(), 2);
+        for (day, task) in created.iter().enumerate() {
+            let date = tomorrow + Days::new(day as u64);
+            assert_eq!(task.timespan.start.date_naive(), date);
+            assert_eq!(task.timespan.start.time(), evening);
+            assert_eq!(task.timespan.end.date_naive(), date + Days::new(1));
+            assert_eq!(task.timespan.end.time(), morning);
+        }
+
+        // The tasks are stored in UTC
+        let tasks = get_tasks(&mut app, auth_token.clone()).await;
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].timespan.start, created[0].timespan.start);
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/tasks/local",
+            auth_token.clone(),
+            Body::empty(),
+        )
+        .await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let local_tasks = serde_json::from_slice::<GetLocalTasksResponse>(&body).unwrap();
+        assert_eq!(local_tasks.time_zone, "America/New_York");
+        assert_eq!(local_tasks.tasks, created);
+
+        let start_time = tasks[0].timespan.start + Duration::hours(1);
+        repository
+            .create_event(tasks[0].id, start_time)
+            .await
+            .unwrap();
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/events/local",
+            auth_token,
+            Body::empty(),
+        )
+        .await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let local_events = serde_json::from_slice::<GetLocalEventsResponse>(&body).unwrap();
+        assert_eq!(local_events.events.len(), 1);
+        let local_start = local_events.events[0].start_time;
+        assert_eq!(local_start, start_time);
+        assert_eq!(
+            local_start.offset(),
+            &start_time.with_timezone(&time_zone).offset().fix()
+        );
+    }
+
+    fn energy_report_uri(path: &str, start: DateTimeUtc, end: DateTimeUtc) -> String {
+        format!(
+            "{}?start={}&end={}&price_per_kwh=2",
+            path,
+            start.to_rfc3339_opts(SecondsFormat::Secs, true),
+            end.to_rfc3339_opts(SecondsFormat::Secs, true)
+        )
+    }
+
+    async fn energy_report(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await.to_string();
+        let other_auth_token = get_account(&mut app, Some("other_user".to_string()))
+            .await
+            .to_string();
+
+        let device = generate_device(&mut app, auth_token.clone(), "Washer".into(), 1000.0).await;
+        let start = now();
+        let task = generate_task(
+            &mut app,
+            auth_token.clone(),
+            Duration::hours(1),
+            &device,
+            start,
+            start + Duration::hours(12),
+        )
+        .await;
+        repository
+            .create_event(task.id, start + Duration::hours(2))
+            .await
+            .unwrap();
+
+        let from = start - Duration::hours(1);
+        let to = start + Duration::days(1);
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            &energy_report_uri("/reports/energy", from, to),
+            auth_token.clone(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let report = serde_json::from_slice::<EnergyReport>(&body).unwrap();
+        assert_eq!(report.devices.len(), 1);
+        assert_eq!(report.devices[0].device_name, "Washer");
+        assert_eq!(report.total.runs, 1);
+        assert_eq!(report.total.energy_kwh, 1.0);
+        assert!((0.0..=1.0).contains(&report.total.renewable_share));
+        // JSON does not round-trip every float exactly
+        let cost_savings = report.total.cost_savings.unwrap();
+        assert!((cost_savings - report.total.saved_kwh * 2.0).abs() < 1e-9);
+        assert_eq!(report.total.co2_savings_kg, None);
+
+        // Other accounts' runs are not included
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            &energy_report_uri("/reports/energy", from, to),
+            other_auth_token,
+            Body::empty(),
+        )
+        .await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let report = serde_json::from_slice::<EnergyReport>(&body).unwrap();
+        assert!(report.devices.is_empty());
+        assert_eq!(report.total.runs, 0);
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            &energy_report_uri("/reports/energy.csv", from, to),
+            auth_token.clone(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-type"],
+            "text/csv; charset=utf-8"
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        let lines: Vec<&str> = csv.split_terminator("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with(&format!("{},Washer,1,1,", device.id)));
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            &energy_report_uri("/reports/energy", to, from),
+            auth_token,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let error = get_error(response).await;
+        assert_eq!(error.details.first().unwrap().field.as_deref(), Some("end"));
+    }
+
+    async fn put_carbon_intensity(
+        app: &mut RouterIntoService<Body>,
+        admin_token: Option<&str>,
+        series: &CarbonIntensitySeries,
+    ) -> axum::response::Response {
+        let mut request = Request::builder()
+            .method(Method::PUT)
+            .uri("/admin/carbon_intensity")
+            .header("Content-Type", "application/json");
+        if let Some(admin_token) = admin_token {
+            request = request.header("X-Admin-Token", admin_token);
+        }
+        let request = request
+            .body(Body::from(serde_json::to_vec(series).unwrap()))
+            .unwrap();
+
+        ServiceExt::<Request<Body>>::ready(app)
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap()
+    }
+
+    async fn get_carbon_intensity_series(
+        app: &mut RouterIntoService<Body>,
+        auth_token: String,
+    ) -> Option<CarbonIntensitySeries> {
+        let response = send_request(
+            app,
+            Method::GET,
+            "/carbon_intensity",
+            auth_token,
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice::<GetCarbonIntensityResponse>(&body)
+            .unwrap()
+            .series
+    }
+
+    async fn carbon_intensity_ingestion(database: TestDatabase) {
+        let (router, _) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await.to_string();
+        assert_eq!(
+            get_carbon_intensity_series(&mut app, auth_token.clone()).await,
+            None
+        );
+
+        let series = CarbonIntensitySeries {
+            start_time: now(),
+            resolution_minutes: 30,
+            values: vec![120.0, 80.5, 200.0],
+        };
+
+        // Only admins can ingest a series
+        for admin_token in [None, Some("wrong")] {
+            let response = put_carbon_intensity(&mut app, admin_token, &series).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        let invalid = CarbonIntensitySeries {
+            values: vec![-1.0],
+            ..series.clone()
+        };
+        let response = put_carbon_intensity(&mut app, Some(TEST_ADMIN_TOKEN), &invalid).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let error = get_error(response).await;
+        assert_eq!(
+            error.details.first().unwrap().field.as_deref(),
+            Some("values")
+        );
+
+        let response = put_carbon_intensity(&mut app, Some(TEST_ADMIN_TOKEN), &series).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            get_carbon_intensity_series(&mut app, auth_token).await,
+            Some(series)
+        );
+    }
+
+    async fn send_admin_request(
+        app: &mut RouterIntoService<Body>,
+        uri: &str,
+        admin_token: Option<&str>,
+    ) -> axum::response::Response {
+        let mut request = Request::builder().method(Method::GET).uri(uri);
+        if let Some(admin_token) = admin_token {
+            request = request.header("X-Admin-Token", admin_token);
+        }
+
+        ServiceExt::<Request<Body>>::ready(app)
+            .await
+            .unwrap()
+            .call(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn scheduling_runs_and_diff(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let auth_token = get_account(&mut app, None).await.to_string();
+        let other_auth_token = get_account(&mut app, Some("other_user".to_string()))
+            .await
+            .to_string();
+
+        let device = generate_device(&mut app, auth_token.clone(), "test".into(), 1000.0).await;
+        let other_device =
+            generate_device(&mut app, other_auth_token.clone(), "test".into(), 1000.0).await;
+        let start = now();
+        let mut tasks = Vec::new();
+        for (auth_token, device) in [(&auth_token, &device), (&other_auth_token, &other_device)] {
+            let task = generate_task(
+                &mut app,
+                auth_token.clone(),
+                Duration::hours(1),
+                device,
+                start,
+                start + Duration::hours(12),
+            )
+            .await;
+            tasks.push(task);
+        }
+
+        // The second run does not move the events, so it has no changes
+        for _ in 0..2 {
+            let mut graph = SchedulingConfig::default().production_graph(start);
+            run_algorithm(repository.as_ref(), &Algorithm::Naive, &mut graph)
+                .await
+                .unwrap();
+        }
+
+        // The runs contain the data of every account, so accounts cannot see them
+        for uri in ["/scheduling/runs", "/scheduling/runs/diff?id=1"] {
+            let response = send_request(
+                &mut app,
+                Method::GET,
+                uri,
+                auth_token.clone(),
+                Body::empty(),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            let response = send_admin_request(&mut app, uri, Some("wrong")).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        let response =
+            send_admin_request(&mut app, "/scheduling/runs", Some(TEST_ADMIN_TOKEN)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let runs = serde_json::from_slice::<GetSchedulingRunsResponse>(&body)
+            .unwrap()
+            .runs;
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].algorithm, Algorithm::Naive);
+        assert_eq!(runs[0].task_count, 2);
+        assert!(runs[0].cost.is_some());
+        assert!(runs[0].error.is_none());
+
+        let mut expected = Vec::new();
+        for (auth_token, task) in [(&auth_token, &tasks[0]), (&other_auth_token, &tasks[1])] {
+            let account_id = account_id(&repository, auth_token).await;
+            let start_time = repository
+                .events_for_account(&account_id, start)
+                .await
+                .unwrap()[0]
+                .start_time;
+            expected.push(EventChange {
+                task_id: task.id,
+                previous_start_time: None,
+                start_time,
+            });
+        }
+
+        let response = send_admin_request(
+            &mut app,
+            "/scheduling/runs/diff?id=1",
+            Some(TEST_ADMIN_TOKEN),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let diff: GetSchedulingRunDiffResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(diff.run, runs[1]);
+        assert_eq!(diff.changes, expected);
+
+        let response = send_admin_request(
+            &mut app,
+            "/scheduling/runs/diff?id=2",
+            Some(TEST_ADMIN_TOKEN),
+        )
+        .await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let diff: GetSchedulingRunDiffResponse = serde_json::from_slice(&body).unwrap();
+        assert!(diff.changes.is_empty());
+
+        let response = send_admin_request(
+            &mut app,
+            "/scheduling/runs/diff?id=3",
+            Some(TEST_ADMIN_TOKEN),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn metrics_test(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/scheduling/algorithms",
+            String::new(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut graph = SchedulingConfig::default().production_graph(now());
+        run_algorithm(repository.as_ref(), &Algorithm::Global, &mut graph)
+            .await
+            .unwrap();
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/metrics",
+            String::new(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let metrics = String::from_utf8(body.to_vec()).unwrap();
+        assert!(metrics.contains(
+            r#"http_requests_total{method="GET",path="/scheduling/algorithms",status="200"}"#
+        ));
+        assert!(metrics.contains("http_request_duration_seconds_bucket"));
+        assert!(metrics.contains(r#"scheduler_runs_total{algorithm="global"}"#));
+        assert!(metrics.contains("scheduler_run_duration_seconds_bucket"));
+        assert!(metrics.contains("scheduler_tasks_per_run_bucket"));
+        assert!(metrics.contains("db_pool_connections"));
+    }
+
+    async fn health_and_readiness(database: TestDatabase) {
+        let (router, repository) = test_app(&database).await;
+        let mut app = router.into_service();
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/health",
+            String::new(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/ready",
+            String::new(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        repository.close().await;
+
+        let response = send_request(
+            &mut app,
+            Method::GET,
+            "/ready",
+            String::new(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let response: ReadinessResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            response,
+            ReadinessResponse {
+                database: ComponentStatus::Unavailable,
+                background_service: ComponentStatus::Ok,
+            }
+        );
+    }
+
+    database_tests!(
+        register_account,
+        register_account_twice_fails,
+        login_to_account,
+        create_task_test,
+        create_task_fails_with_invalid_device,
+        create_task_fails_validation,
+        create_task_fails_with_malformed_body,
+        get_tasks_test,
+        delete_task_test,
+        delete_task_of_other_account_fails,
+        preview_task_test,
+        pin_and_unpin_task,
+        get_devices_test,
+        create_device_test,
+        create_device_fails_validation,
+        delete_device_test,
+        get_all_events,
+        get_all_valid_events,
+        get_device_event,
+        get_device_event_none,
+        get_event_explanation,
+        rescheduled_event_keeps_its_id,
+        get_algorithms_test,
+        run_scheduling_rejects_unknown_algorithm,
+        scheduling_runs_and_diff,
+        metrics_test,
+        health_and_readiness,
+        calendar_feeds,
+        account_time_zone_settings,
+        local_tasks_and_events,
+        energy_report,
+        carbon_intensity_ingestion,
+        baseline_consumption,
+    );
 }
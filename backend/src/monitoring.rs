@@ -0,0 +1,77 @@
+use std::{sync::OnceLock, time::Instant};
+
+use axum::{
+    debug_handler,
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::MyState;
+
+const SECONDS_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+const TASKS_BUCKETS: &[f64] = &[0.0, 1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+
+/// The handle of the global Prometheus recorder.
+///
+/// The recorder is installed on the first call, as it can only be installed once per process.
+pub fn metrics_handle() -> PrometheusHandle {
+    static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+    HANDLE
+        .get_or_init(|| {
+            PrometheusBuilder::new()
+                .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), SECONDS_BUCKETS)
+                .and_then(|builder| {
+                    builder.set_buckets_for_metric(
+                        Matcher::Full("scheduler_tasks_per_run".to_string()),
+                        TASKS_BUCKETS,
+                    )
+                })
+                .expect("The buckets are not empty")
+                .install_recorder()
+                .expect("Could not install the Prometheus recorder")
+        })
+        .clone()
+}
+
+/// Counts the requests and measures their latency per route.
+pub async fn track_http_metrics(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    // The matched route is used instead of the uri, to not create a metric per query string
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".to_owned());
+    let method = request.method().to_string();
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("path", path),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    counter!("http_requests_total", &labels).increment(1);
+    histogram!("http_request_duration_seconds", &labels).record(start.elapsed().as_secs_f64());
+
+    response
+}
+
+/// All metrics in the Prometheus text format.
+#[debug_handler]
+pub async fn get_metrics(State(state): State<MyState>) -> impl IntoResponse {
+    let pool_status = state.repository.pool_status();
+    gauge!("db_pool_connections").set(pool_status.size as f64);
+    gauge!("db_pool_idle_connections").set(pool_status.idle as f64);
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
@@ -1,5 +1,9 @@
 pub mod background_service;
-pub mod event_creation;
+pub mod baseline;
+pub mod carbon;
+pub mod coverage;
+pub mod energy_report;
+pub mod registry;
 pub mod scheduler;
 pub mod task_for_scheduler;
 pub mod unpublished_event;
@@ -1,74 +1,243 @@
+use std::{collections::HashMap, sync::Arc};
+
 use anyhow::Result;
-use chrono::{Duration, Utc};
+use chrono::{Duration, DurationRound, Utc};
+use metrics::{counter, gauge, histogram};
 use protocol::{
-    graph::DiscreteGraph,
-    tasks::TaskId,
-    time::{Milliseconds, Timespan},
+    graph::{DiscreteGraph, ResampleMode},
+    scheduling::Algorithm,
+    time::{DateTimeUtc, Milliseconds, Timespan},
+};
+use tokio::{
+    select,
+    sync::mpsc::UnboundedReceiver,
+    task::spawn_blocking,
+    time::{interval_at, sleep_until, Instant, MissedTickBehavior},
 };
-use sqlx::SqlitePool;
-use tokio::{select, sync::mpsc::UnboundedReceiver, time::sleep};
+use tokio_util::sync::CancellationToken;
 use tracing::{event, Level};
 
-use super::{scheduler::SchedulerAlgorithm, task_for_scheduler::TaskForScheduler};
+use super::{
+    baseline::subtract_baselines,
+    carbon::carbon_cost,
+    coverage::total_renewable_coverage,
+    registry::create_algorithm,
+    scheduler::{explain_event, graph_cost, remove_fixed_event_from_graph, RunControl},
+    task_for_scheduler::TaskForScheduler,
+    unpublished_event::UnpublishedEvent,
+};
+use crate::data_model::repository::{NewSchedulingRun, PinnedTask, Repository};
 
 pub enum BackgroundServiceMessage {
+    /// Tasks, devices or baselines have changed
     Update,
+    /// A new forecast has been ingested
+    ForecastUpdated,
     // Only use this in simulator mode!
     RunScheduler,
 }
 
-pub async fn background_service<F, TAlg>(
-    mut receiver: UnboundedReceiver<BackgroundServiceMessage>,
-    pool: SqlitePool,
-    algorithm_constructor: F,
-) where
-    F: FnOnce() -> TAlg,
-    TAlg: SchedulerAlgorithm,
-{
-    let mut algorithm = algorithm_constructor();
-    loop {
-        // Wait until we receive a message.
-        let msg = receiver.recv().await;
-        if msg.is_none() {
-            break;
+impl BackgroundServiceMessage {
+    /// The reason for a run, as reported in the `scheduler_wakeups_total` metric.
+    fn reason(&self) -> &'static str {
+        match self {
+            BackgroundServiceMessage::Update => "update",
+            BackgroundServiceMessage::ForecastUpdated => "forecast",
+            BackgroundServiceMessage::RunScheduler => "simulator",
         }
+    }
+}
 
-        let debounce = sleep(std::time::Duration::from_secs(5 * 60));
+/// The parameters of the background service, which are set through the configuration file.
+#[derive(Clone, Copy, Debug)]
+pub struct SchedulingConfig {
+    /// How long to wait for more changes before running the algorithm
+    pub debounce: std::time::Duration,
+    /// The longest a change waits for the algorithm, even when more changes keep arriving
+    pub max_debounce: std::time::Duration,
+    /// The length of each timeslot in the production graph
+    pub slot_resolution: Duration,
+    /// How far into the future tasks are scheduled
+    pub horizon: Duration,
+    /// How often the algorithm runs without any changes, so the schedule follows the horizon
+    pub interval: std::time::Duration,
+}
+
+impl Default for SchedulingConfig {
+    fn default() -> Self {
+        SchedulingConfig {
+            debounce: std::time::Duration::from_secs(5 * 60),
+            max_debounce: std::time::Duration::from_secs(15 * 60),
+            slot_resolution: Duration::minutes(1),
+            horizon: Duration::hours(24),
+            interval: std::time::Duration::from_secs(60 * 60),
+        }
+    }
+}
 
-        let values = vec![
+impl SchedulingConfig {
+    /// The expected available energy for the scheduling horizon from `start`.
+    pub fn production_graph(&self, start: DateTimeUtc) -> DiscreteGraph {
+        self.production_graph_for(start, self.horizon)
+    }
+
+    /// The expected available energy for `length` from the first timeslot boundary at or after `start`,
+    /// rounded down to whole timeslots.
+    ///
+    /// The timeslots are counted from the start of the hour, so with a resolution that divides an hour
+    /// the graphs of consecutive runs share their boundaries and unchanged events keep their start times.
+    pub fn production_graph_for(&self, start: DateTimeUtc, length: Duration) -> DiscreteGraph {
+        let hourly_values = [
             0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 28.0, 200.0, 484.0, 829.0, 1186.0, 1407.0, 1475.0,
             1455.0, 1393.0, 1271.0, 1044.0, 754.0, 445.0, 154.0, 10.0, 0.0, 0.0, 0.0,
-        ]
-        .into_iter()
-        .flat_map(|v| vec![v; 60])
-        .collect();
+        ];
 
-        let mut discrete_graph = DiscreteGraph::new(values, Duration::minutes(1), Utc::now());
+        // The graph starts at the hour and covers the timeslot that starts last, up to an hour after `start`
+        let hour_start = start.duration_trunc(Duration::hours(1)).unwrap_or(start);
+        let hours = (length + self.slot_resolution).num_hours() + 2;
+        let graph = DiscreteGraph::new(
+            (0..hours)
+                .map(|hour| hourly_values[hour as usize % hourly_values.len()])
+                .collect(),
+            Duration::hours(1),
+            hour_start,
+        )
+        .resample(self.slot_resolution, ResampleMode::Mean);
 
-        select! {
-            _ = debounce => {
-                if let Err(error) = run_algorithm(&pool, &mut algorithm, &mut discrete_graph).await {
-                    println!("Algorithm error!: {}", error);
+        let resolution = self.slot_resolution.num_milliseconds();
+        let slots = length.num_milliseconds() / resolution;
+        let first_slot = graph.time_at(
+            graph
+                .slots_within(&Timespan::new(start, graph.get_timespan().end))
+                .start,
+        );
+        graph.sub_graph(&Timespan::new(
+            first_slot,
+            first_slot + self.slot_resolution * slots as i32,
+        ))
+    }
+}
+
+/// Runs the algorithm after changes, after forecast updates and every [SchedulingConfig::interval].
+///
+/// Every reason to run waits for the debounce, and they are combined into a single run.
+/// The run starts at the latest [SchedulingConfig::max_debounce] after the first of them.
+/// A message during a run cancels it, and the next run starts after the debounce.
+/// Once the first unpublished change is older than the max debounce, runs are no longer cancelled,
+/// so a steady stream of changes still leads to published schedules.
+/// When the channel is closed the current run is finished before the service stops.
+pub async fn background_service(
+    mut receiver: UnboundedReceiver<BackgroundServiceMessage>,
+    repository: Arc<dyn Repository>,
+    config: SchedulingConfig,
+    algorithm: Algorithm,
+) {
+    let mut periodic = interval_at(Instant::now() + config.interval, config.interval);
+    periodic.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    // When the first change arrived that is not part of a published schedule yet
+    let mut pending_since: Option<Instant> = None;
+    // Whether a change arrived during the previous run, so the next one is already due
+    let mut changed = false;
+    loop {
+        // Wait until we receive a message, or it is time for a periodic run.
+        if !changed {
+            select! {
+                msg = receiver.recv() => match msg {
+                    Some(msg) => {
+                        counter!("scheduler_wakeups_total", "reason" => msg.reason()).increment(1);
+                        pending_since.get_or_insert_with(Instant::now);
+                    }
+                    None => break,
+                },
+                _ = periodic.tick() => {
+                    counter!("scheduler_wakeups_total", "reason" => "interval").increment(1);
                 }
             }
-            msg = receiver.recv() => {
-                if msg.is_none() {
-                    break;
+        }
+
+        // Wait until nothing has happened for the debounce, so a burst of changes leads to a single run.
+        // A steady stream of changes would postpone the run forever, so the wait is capped from the first change.
+        let deadline = pending_since.unwrap_or_else(Instant::now) + config.max_debounce;
+        let debounce = sleep_until((Instant::now() + config.debounce).min(deadline));
+        tokio::pin!(debounce);
+        loop {
+            select! {
+                _ = &mut debounce => break,
+                msg = receiver.recv() => match msg {
+                    Some(msg) => {
+                        counter!("scheduler_wakeups_total", "reason" => msg.reason()).increment(1);
+                        pending_since.get_or_insert_with(Instant::now);
+                        debounce
+                            .as_mut()
+                            .reset((Instant::now() + config.debounce).min(deadline));
+                    }
+                    None => {
+                        event!(target: "backend", Level::WARN, "Stopping with changes that have not been scheduled");
+                        return;
+                    }
+                },
+                _ = periodic.tick() => {
+                    counter!("scheduler_wakeups_total", "reason" => "interval").increment(1);
                 }
             }
+        }
+
+        gauge!("scheduler_debounce_queue_depth").set(0.0);
+        let mut discrete_graph = config.production_graph(Utc::now());
+        let cancellation = CancellationToken::new();
+        let run = run_algorithm_with_control(
+            repository.as_ref(),
+            &algorithm,
+            &mut discrete_graph,
+            RunControl::new(cancellation.clone()),
+        );
+        tokio::pin!(run);
+
+        // A change during the run makes its schedule outdated, so the run is cancelled and a new one follows.
+        // When its changes have already waited for the max debounce, the run is left to finish instead,
+        // as a slightly outdated schedule is better than none. On shutdown the run is also left to finish.
+        let run_pending_since = pending_since.take();
+        changed = false;
+        let mut stopping = false;
+        let result = loop {
+            select! {
+                result = &mut run => break result,
+                msg = receiver.recv(), if !changed && !stopping => match msg {
+                    Some(msg) => {
+                        counter!("scheduler_wakeups_total", "reason" => msg.reason()).increment(1);
+                        changed = true;
+                        let now = Instant::now();
+                        match run_pending_since {
+                            Some(since) if now - since >= config.max_debounce => {
+                                pending_since = Some(now);
+                            }
+                            since => {
+                                pending_since = Some(since.unwrap_or(now));
+                                cancellation.cancel();
+                            }
+                        }
+                    }
+                    None => stopping = true,
+                },
+            }
         };
+        if let Err(error) = result {
+            event!(target: "backend", Level::ERROR, "The scheduling run failed: {:#}", error);
+        }
+        if stopping {
+            return;
+        }
+        // The run has taken the latest changes into account, so the next periodic run is a whole interval later
+        periodic.reset();
     }
 }
 
-pub async fn simulator_background_service<F, TAlg>(
+pub async fn simulator_background_service(
     mut receiver: UnboundedReceiver<BackgroundServiceMessage>,
-    pool: SqlitePool,
-    algorithm_constructor: F,
-) where
-    F: FnOnce() -> TAlg,
-    TAlg: SchedulerAlgorithm,
-{
-    let mut algorithm = algorithm_constructor();
+    repository: Arc<dyn Repository>,
+    algorithm: Algorithm,
+) {
     loop {
         // Wait until we receive a message.
         let msg = receiver.recv().await;
@@ -85,64 +254,762 @@ pub async fn simulator_background_service<F, TAlg>(
         );
 
         match msg {
-            BackgroundServiceMessage::Update => {}
+            BackgroundServiceMessage::Update | BackgroundServiceMessage::ForecastUpdated => {}
             BackgroundServiceMessage::RunScheduler => {
-                if let Err(error) = run_algorithm(&pool, &mut algorithm, &mut discrete_graph).await
+                gauge!("scheduler_debounce_queue_depth").set(0.0);
+                if let Err(error) =
+                    run_algorithm(repository.as_ref(), &algorithm, &mut discrete_graph).await
                 {
-                    println!("Algorithm error!: {}", error);
+                    event!(target: "backend", Level::ERROR, "The scheduling run failed: {:#}", error);
                 }
             }
         }
     }
 }
 
+/// Schedules all tasks and publishes the events, recording the run in the repository.
+///
+/// The baseline consumption of the accounts is subtracted from the graph first,
+/// and tasks that cannot run within the graph are left for a later run.
+/// When a carbon intensity series has been ingested, the emissions of the imported energy are minimized and recorded.
 pub async fn run_algorithm(
-    pool: &SqlitePool,
-    algorithm: &mut impl SchedulerAlgorithm,
+    repository: &dyn Repository,
+    algorithm: &Algorithm,
     graph: &mut DiscreteGraph,
 ) -> Result<()> {
-    let now = graph.get_start_time();
-    // TODO: Filter out tasks that have a started event
-    let tasks = sqlx::query!(
-        r#"
-        SELECT Tasks.id as "id: TaskId", Tasks.timespan_start, Tasks.timespan_end, Tasks.duration as "duration: Milliseconds", Devices.effect as "effect: f64"
-        FROM Tasks
-        JOIN Devices ON Tasks.device_id == Devices.id
-        WHERE Tasks.timespan_end >= ? AND ((julianday(Tasks.timespan_end, 'utc') - julianday(?, 'utc')) * 24 * 60 * 60 * 1000) >= duration
-        "#,
-        now,
-        now)
-        .fetch_all(pool)
+    run_algorithm_with_control(repository, algorithm, graph, RunControl::default()).await
+}
+
+/// Runs the algorithm like [run_algorithm], on the blocking thread pool so it never stalls the runtime.
+///
+/// A run that is cancelled through `control` is recorded, but its schedule is not published,
+/// as it was made for tasks that have changed since and the next run replaces it.
+pub async fn run_algorithm_with_control(
+    repository: &dyn Repository,
+    algorithm: &Algorithm,
+    graph: &mut DiscreteGraph,
+    control: RunControl,
+) -> Result<()> {
+    subtract_baselines(repository, graph).await?;
+    let pinned_events = remove_pinned_tasks_from_graph(repository, graph).await?;
+    let mut tasks = repository
+        .tasks_for_scheduling(graph.get_start_time())
         .await?;
+    let deferred = defer_tasks_beyond_graph(graph, &mut tasks);
+    let carbon_intensity = repository.carbon_intensity().await?;
 
-    let tasks: Vec<_> = tasks
-        .into_iter()
-        .map(|t| {
-            TaskForScheduler::new(
-                t.id,
-                Timespan::new_from_naive(t.timespan_start, t.timespan_end),
-                t.duration,
-                t.effect,
-            )
+    event!(target: "backend", Level::INFO, "Running algorithm on {} tasks, deferring {}", tasks.len(), deferred);
+    gauge!("scheduler_deferred_tasks").set(deferred as f64);
+
+    let input_graph = graph.clone();
+    let task_count = tasks.len() as i64;
+    let started_at = Utc::now();
+    let timer = Instant::now();
+
+    let name = algorithm.name();
+    let scheduler = create_algorithm(algorithm);
+    let control = control.with_progress(move |progress| {
+        gauge!("scheduler_run_progress_steps", "algorithm" => name).set(progress.steps as f64);
+    });
+    let (result, scheduled_graph) = {
+        let mut graph = graph.clone();
+        let tasks = tasks.clone();
+        let carbon_intensity = carbon_intensity.clone();
+        spawn_blocking(move || {
+            let result = scheduler.schedule_cancellable(
+                &mut graph,
+                tasks,
+                carbon_intensity.as_ref(),
+                &control,
+            );
+            (result, graph)
         })
-        .collect();
+        .await?
+    };
+    *graph = scheduled_graph;
+
+    let elapsed = timer.elapsed();
+    counter!("scheduler_runs_total", "algorithm" => name).increment(1);
+    histogram!("scheduler_run_duration_seconds", "algorithm" => name).record(elapsed.as_secs_f64());
+    histogram!("scheduler_tasks_per_run", "algorithm" => name).record(task_count as f64);
+
+    let (cost, emissions, error) = match &result {
+        Ok(schedule) if schedule.cancelled => {
+            counter!("scheduler_cancelled_runs_total", "algorithm" => name).increment(1);
+            (None, None, Some("The run was cancelled".to_owned()))
+        }
+        Ok(schedule) => {
+            let cost = graph_cost(graph);
+            gauge!("scheduler_objective_cost", "algorithm" => name).set(cost);
+            match total_renewable_coverage(graph, &tasks, &schedule.events) {
+                Ok(coverage) => {
+                    gauge!("scheduler_renewable_coverage_ratio", "algorithm" => name).set(coverage)
+                }
+                Err(error) => {
+                    event!(target: "backend", Level::WARN, "Could not compute the renewable coverage: {}", error)
+                }
+            }
+            let emissions = carbon_intensity
+                .as_ref()
+                .map(|carbon_intensity| carbon_cost(graph, carbon_intensity));
+            if let Some(emissions) = emissions {
+                gauge!("scheduler_estimated_emissions_grams", "algorithm" => name).set(emissions);
+            }
+            (Some(cost), emissions, None)
+        }
+        Err(error) => {
+            counter!("scheduler_failed_runs_total", "algorithm" => name).increment(1);
+            (None, None, Some(format!("{:#}", error)))
+        }
+    };
+
+    let run = NewSchedulingRun {
+        algorithm: algorithm.clone(),
+        input_graph,
+        task_count,
+        cost,
+        emissions,
+        started_at,
+        duration: Milliseconds::from(Duration::from_std(elapsed)?),
+        error,
+    };
+
+    let run_id = repository.record_scheduling_run(&run).await?;
+
+    let schedule = result?;
+    if schedule.cancelled {
+        event!(target: "backend", Level::INFO, "The run was cancelled, keeping the published schedule");
+        return Ok(());
+    }
 
-    event!(target: "backend", Level::INFO, "Running algorithm on {} tasks", tasks.len());
+    let mut events = schedule.events;
+    explain_events(
+        &run.input_graph,
+        graph,
+        &tasks,
+        &mut events,
+        carbon_intensity.as_ref(),
+    );
+    events.extend(pinned_events);
 
-    let events = algorithm.schedule(graph, tasks.clone())?;
+    // The events are written in one transaction, so a shutdown never leaves a half-published schedule
+    repository.upsert_events(&events, Some(run_id)).await?;
 
+    Ok(())
+}
+
+/// Adds an explanation to every scheduled event, see [explain_event].
+///
+/// An event that cannot be explained is still published, just without an explanation.
+fn explain_events(
+    input_graph: &DiscreteGraph,
+    graph: &DiscreteGraph,
+    tasks: &[TaskForScheduler],
+    events: &mut [UnpublishedEvent],
+    carbon_intensity: Option<&DiscreteGraph>,
+) {
+    let tasks: HashMap<_, _> = tasks.iter().map(|task| (task.id, task)).collect();
     for event in events {
-        sqlx::query!(
-            r#"
-            INSERT OR REPLACE INTO Events (task_id, start_time)
-            VALUES ((SELECT id FROM Tasks WHERE id == ? LIMIT 1), ?)
-            "#,
-            event.task_id,
-            event.start_time,
+        let Some(task) = tasks.get(&event.task_id) else {
+            continue;
+        };
+        match explain_event(input_graph, graph, task, event, carbon_intensity) {
+            Ok(explanation) => event.explanation = Some(explanation),
+            Err(error) => {
+                event!(target: "backend", Level::WARN, "Could not explain the event for task with id: {}: {}", event.task_id, error)
+            }
+        }
+    }
+}
+
+/// Removes the tasks that cannot run within the graph, and returns how many there were.
+///
+/// They are scheduled by a later run, once enough of their timespan lies within the scheduling horizon.
+pub fn defer_tasks_beyond_graph(graph: &DiscreteGraph, tasks: &mut Vec<TaskForScheduler>) -> usize {
+    let count = tasks.len();
+    tasks.retain(|task| task.fits_within(graph));
+    count - tasks.len()
+}
+
+/// Removes the energy used by pinned tasks from the graph, as they are fixed loads.
+/// Returns the events of the pinned tasks, which must never be moved.
+pub async fn remove_pinned_tasks_from_graph(
+    repository: &dyn Repository,
+    graph: &mut DiscreteGraph,
+) -> Result<Vec<UnpublishedEvent>> {
+    let now = graph.get_start_time();
+    let pinned_tasks = repository.pinned_tasks(now).await?;
+
+    let mut events = Vec::new();
+    for PinnedTask { task, start_time } in pinned_tasks {
+        if start_time + Duration::from(task.duration) <= now {
+            continue;
+        }
+
+        remove_fixed_event_from_graph(graph, &task, start_time)?;
+
+        events.push(UnpublishedEvent {
+            task_id: task.id,
+            start_time,
+            explanation: None,
+        });
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Timelike;
+    use protocol::baseline::{DailyProfile, MINUTES_PER_DAY};
+    use tokio::time::sleep;
+
+    use super::*;
+    use crate::data_model::{
+        repository::{
+            AccountRepository, BaselineRepository, CarbonIntensityRepository, DeviceRepository,
+            EventRepository, SchedulingRunRepository, TaskRepository,
+        },
+        sqlite::SqliteRepository,
+    };
+
+    #[test]
+    fn production_graph_follows_resolution_and_horizon() {
+        let start = Utc::now().duration_trunc(Duration::hours(1)).unwrap();
+        let config = SchedulingConfig {
+            slot_resolution: Duration::minutes(30),
+            horizon: Duration::hours(48),
+            ..Default::default()
+        };
+
+        let graph = config.production_graph(start);
+
+        assert_eq!(graph.get_values().len(), 96);
+        assert_eq!(graph.get_time_delta(), Duration::minutes(30));
+        // The daily profile repeats after 24 hours
+        assert_eq!(graph.get_values()[16], 484.0);
+        assert_eq!(graph.get_values()[48 + 16], 484.0);
+    }
+
+    #[test]
+    fn production_graph_starts_at_the_next_timeslot_boundary() {
+        let config = SchedulingConfig {
+            slot_resolution: Duration::minutes(15),
+            ..Default::default()
+        };
+        let hour_start = Utc::now().duration_trunc(Duration::hours(1)).unwrap();
+
+        // Runs within the same timeslot plan on the same timeslots
+        let graph = config.production_graph(hour_start + Duration::minutes(32));
+        let later = config.production_graph(hour_start + Duration::minutes(44));
+
+        assert_eq!(graph.get_start_time(), hour_start + Duration::minutes(45));
+        assert_eq!(later.get_start_time(), graph.get_start_time());
+        assert_eq!(graph.get_values().len(), 96);
+        assert_eq!(graph.get_time_delta(), Duration::minutes(15));
+
+        // Every timeslot has the whole value of its hour, from 45 minutes after the hour
+        assert_eq!(graph.get_values()[25..29], [200.0; 4]);
+        assert_eq!(graph.get_values()[29..33], [484.0; 4]);
+    }
+
+    #[tokio::test]
+    async fn run_algorithm_keeps_pinned_tasks_and_records_the_run() {
+        let repository = SqliteRepository::in_memory().await.unwrap();
+        let account_id = repository
+            .create_account("owner", "hash")
+            .await
+            .unwrap()
+            .unwrap();
+        let device = repository
+            .create_device(&account_id, "test", 1000.0)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let timespan = Timespan::new(now, now + Duration::hours(12));
+        let mut tasks = Vec::new();
+        for _ in 0..2 {
+            let task = repository
+                .create_task(&account_id, device.id, &timespan, Duration::hours(1).into())
+                .await
+                .unwrap()
+                .unwrap();
+            tasks.push(task);
+        }
+        let pinned_start = now + Duration::hours(2);
+        repository
+            .pin_task(&account_id, tasks[1].id, pinned_start)
+            .await
+            .unwrap();
+
+        let mut graph = SchedulingConfig::default().production_graph(now);
+        run_algorithm(&repository, &Algorithm::Global, &mut graph)
+            .await
+            .unwrap();
+
+        let events = repository
+            .events_for_account(&account_id, now)
+            .await
+            .unwrap();
+        let start_time = |task_id| {
+            events
+                .iter()
+                .find(|event| event.task_id == task_id)
+                .unwrap()
+                .start_time
+        };
+        assert_eq!(start_time(tasks[1].id), pinned_start);
+        assert!(start_time(tasks[0].id) >= timespan.start);
+        assert!(start_time(tasks[0].id) + Duration::hours(1) <= timespan.end);
+
+        let runs = repository.scheduling_runs(10).await.unwrap();
+        assert_eq!(runs.len(), 1);
+        // Pinned tasks are not scheduled
+        assert_eq!(runs[0].task_count, 1);
+        assert!(runs[0].error.is_none());
+        assert!(runs[0].emissions.is_none());
+    }
+
+    #[tokio::test]
+    async fn events_never_start_before_the_run() {
+        let repository = SqliteRepository::in_memory().await.unwrap();
+        let account_id = repository
+            .create_account("owner", "hash")
+            .await
+            .unwrap()
+            .unwrap();
+        let device = repository
+            .create_device(&account_id, "test", 1000.0)
+            .await
+            .unwrap();
+
+        // Between two timeslot boundaries, with tasks whose timespans have already started
+        let now = Utc::now().duration_trunc(Duration::hours(1)).unwrap() + Duration::minutes(37);
+        for _ in 0..3 {
+            repository
+                .create_task(
+                    &account_id,
+                    device.id,
+                    &Timespan::new(now - Duration::hours(1), now + Duration::hours(2)),
+                    Duration::minutes(30).into(),
+                )
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        let config = SchedulingConfig {
+            slot_resolution: Duration::minutes(15),
+            ..Default::default()
+        };
+        let mut graph = config.production_graph(now);
+        run_algorithm(&repository, &Algorithm::Global, &mut graph)
+            .await
+            .unwrap();
+
+        let events = repository
+            .events_for_account(&account_id, now - Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(|event| event.start_time >= now));
+    }
+
+    #[tokio::test]
+    async fn cancelled_runs_are_recorded_but_not_published() {
+        let repository = SqliteRepository::in_memory().await.unwrap();
+        let account_id = repository
+            .create_account("owner", "hash")
+            .await
+            .unwrap()
+            .unwrap();
+        let device = repository
+            .create_device(&account_id, "test", 1000.0)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        repository
+            .create_task(
+                &account_id,
+                device.id,
+                &Timespan::new(now, now + Duration::hours(12)),
+                Duration::hours(1).into(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        let mut graph = SchedulingConfig::default().production_graph(now);
+        run_algorithm_with_control(
+            &repository,
+            &Algorithm::AllPermutations { max_tasks: None },
+            &mut graph,
+            RunControl::new(cancellation),
         )
-        .execute(pool)
-        .await?;
+        .await
+        .unwrap();
+
+        let events = repository
+            .events_for_account(&account_id, now)
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+        let runs = repository.scheduling_runs(10).await.unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].error.as_deref(), Some("The run was cancelled"));
+        assert!(runs[0].cost.is_none());
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn run_algorithm_defers_tasks_beyond_the_horizon() {
+        let repository = SqliteRepository::in_memory().await.unwrap();
+        let account_id = repository
+            .create_account("owner", "hash")
+            .await
+            .unwrap()
+            .unwrap();
+        let device = repository
+            .create_device(&account_id, "test", 1000.0)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let mut tasks = Vec::new();
+        for timespan in [
+            Timespan::new(now, now + Duration::hours(12)),
+            // Only its first half hour lies within the horizon
+            Timespan::new(
+                now + Duration::minutes(23 * 60 + 30),
+                now + Duration::hours(30),
+            ),
+            Timespan::new(now + Duration::hours(48), now + Duration::hours(60)),
+        ] {
+            let task = repository
+                .create_task(&account_id, device.id, &timespan, Duration::hours(1).into())
+                .await
+                .unwrap()
+                .unwrap();
+            tasks.push(task);
+        }
+
+        let mut graph = SchedulingConfig::default().production_graph(now);
+        run_algorithm(&repository, &Algorithm::Global, &mut graph)
+            .await
+            .unwrap();
+
+        let events = repository
+            .events_for_account(&account_id, now)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].task_id, tasks[0].id);
+        let runs = repository.scheduling_runs(10).await.unwrap();
+        assert_eq!(runs[0].task_count, 1);
+
+        // A longer horizon reaches the deferred tasks
+        let config = SchedulingConfig {
+            horizon: Duration::hours(72),
+            ..Default::default()
+        };
+        let mut graph = config.production_graph(now);
+        run_algorithm(&repository, &Algorithm::Global, &mut graph)
+            .await
+            .unwrap();
+
+        let events = repository
+            .events_for_account(&account_id, now)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 3);
+        let start_time = |task_id| {
+            events
+                .iter()
+                .find(|event| event.task_id == task_id)
+                .unwrap()
+                .start_time
+        };
+        assert!(start_time(tasks[2].id) >= now + Duration::hours(48));
+    }
+
+    #[tokio::test]
+    async fn background_service_runs_periodically() {
+        let repository: Arc<dyn Repository> =
+            Arc::new(SqliteRepository::in_memory().await.unwrap());
+        let account_id = repository
+            .create_account("owner", "hash")
+            .await
+            .unwrap()
+            .unwrap();
+        let device = repository
+            .create_device(&account_id, "test", 1000.0)
+            .await
+            .unwrap();
+        let now = Utc::now();
+        repository
+            .create_task(
+                &account_id,
+                device.id,
+                &Timespan::new(now, now + Duration::hours(4)),
+                Duration::hours(1).into(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        let config = SchedulingConfig {
+            debounce: std::time::Duration::from_millis(10),
+            interval: std::time::Duration::from_millis(50),
+            ..Default::default()
+        };
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let service = tokio::spawn(background_service(
+            receiver,
+            repository.clone(),
+            config,
+            Algorithm::Global,
+        ));
+
+        // No message is sent, but the task is scheduled anyway
+        sleep(std::time::Duration::from_millis(500)).await;
+        drop(sender);
+        service.await.unwrap();
+
+        let events = repository
+            .events_for_account(&account_id, now)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(!repository.scheduling_runs(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn background_service_coalesces_changes_and_forecasts() {
+        let repository: Arc<dyn Repository> =
+            Arc::new(SqliteRepository::in_memory().await.unwrap());
+        let config = SchedulingConfig {
+            debounce: std::time::Duration::from_millis(100),
+            interval: std::time::Duration::from_secs(60 * 60),
+            ..Default::default()
+        };
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let service = tokio::spawn(background_service(
+            receiver,
+            repository.clone(),
+            config,
+            Algorithm::Global,
+        ));
+
+        // A burst of changes and a new forecast lead to a single run
+        for _ in 0..3 {
+            sender.send(BackgroundServiceMessage::Update).unwrap();
+            sleep(std::time::Duration::from_millis(20)).await;
+        }
+        sender
+            .send(BackgroundServiceMessage::ForecastUpdated)
+            .unwrap();
+        sleep(std::time::Duration::from_millis(400)).await;
+        assert_eq!(repository.scheduling_runs(10).await.unwrap().len(), 1);
+
+        // A forecast on its own also leads to a run
+        sender
+            .send(BackgroundServiceMessage::ForecastUpdated)
+            .unwrap();
+        sleep(std::time::Duration::from_millis(400)).await;
+        assert_eq!(repository.scheduling_runs(10).await.unwrap().len(), 2);
+
+        drop(sender);
+        service.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn background_service_runs_within_the_max_debounce() {
+        let repository: Arc<dyn Repository> =
+            Arc::new(SqliteRepository::in_memory().await.unwrap());
+        let config = SchedulingConfig {
+            debounce: std::time::Duration::from_millis(100),
+            max_debounce: std::time::Duration::from_millis(300),
+            interval: std::time::Duration::from_secs(60 * 60),
+            ..Default::default()
+        };
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let service = tokio::spawn(background_service(
+            receiver,
+            repository.clone(),
+            config,
+            Algorithm::Global,
+        ));
+
+        // Changes keep arriving within the debounce, but the run is not postponed beyond the max debounce
+        for _ in 0..20 {
+            sender.send(BackgroundServiceMessage::Update).unwrap();
+            sleep(std::time::Duration::from_millis(50)).await;
+        }
+        assert!(!repository.scheduling_runs(10).await.unwrap().is_empty());
+
+        drop(sender);
+        service.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn background_service_publishes_during_a_stream_of_changes() {
+        let repository: Arc<dyn Repository> =
+            Arc::new(SqliteRepository::in_memory().await.unwrap());
+        let account_id = repository
+            .create_account("owner", "hash")
+            .await
+            .unwrap()
+            .unwrap();
+        let device = repository
+            .create_device(&account_id, "test", 1000.0)
+            .await
+            .unwrap();
+        let now = Utc::now();
+        // Enough tasks that every run takes longer than the time between two changes
+        for _ in 0..4 {
+            repository
+                .create_task(
+                    &account_id,
+                    device.id,
+                    &Timespan::new(now, now + Duration::hours(12)),
+                    Duration::hours(1).into(),
+                )
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        let config = SchedulingConfig {
+            debounce: std::time::Duration::from_millis(50),
+            max_debounce: std::time::Duration::from_millis(200),
+            interval: std::time::Duration::from_secs(60 * 60),
+            ..Default::default()
+        };
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let service = tokio::spawn(background_service(
+            receiver,
+            repository.clone(),
+            config,
+            Algorithm::AllPermutations { max_tasks: None },
+        ));
+
+        // A change arrives during every run, but once the changes have waited for the max debounce a run is published
+        let started = Instant::now();
+        let mut published = false;
+        while !published && started.elapsed() < std::time::Duration::from_secs(10) {
+            sender.send(BackgroundServiceMessage::Update).unwrap();
+            sleep(std::time::Duration::from_millis(10)).await;
+            published = !repository
+                .events_for_account(&account_id, now)
+                .await
+                .unwrap()
+                .is_empty();
+        }
+        assert!(published);
+
+        drop(sender);
+        service.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn run_algorithm_records_the_emissions() {
+        let repository = SqliteRepository::in_memory().await.unwrap();
+        let account_id = repository
+            .create_account("owner", "hash")
+            .await
+            .unwrap()
+            .unwrap();
+        let device = repository
+            .create_device(&account_id, "test", 1000.0)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let timespan = Timespan::new(now, now + Duration::hours(4));
+        repository
+            .create_task(&account_id, device.id, &timespan, Duration::hours(1).into())
+            .await
+            .unwrap()
+            .unwrap();
+        repository
+            .set_carbon_intensity(&DiscreteGraph::new(
+                vec![300.0, 100.0, 200.0, 400.0, 400.0],
+                Duration::hours(1),
+                now,
+            ))
+            .await
+            .unwrap();
+
+        // Nothing is produced, so everything is imported
+        let mut graph = DiscreteGraph::new(vec![0.0; 5], Duration::hours(1), now);
+        run_algorithm(&repository, &Algorithm::Global, &mut graph)
+            .await
+            .unwrap();
+
+        let events = repository
+            .events_for_account(&account_id, now)
+            .await
+            .unwrap();
+        assert_eq!(events[0].start_time, now + Duration::hours(1));
+
+        let runs = repository.scheduling_runs(10).await.unwrap();
+        assert_eq!(runs[0].emissions, Some(100.0));
+    }
+
+    #[tokio::test]
+    async fn run_algorithm_schedules_around_the_baseline() {
+        let repository = SqliteRepository::in_memory().await.unwrap();
+        let account_id = repository
+            .create_account("owner", "hash")
+            .await
+            .unwrap()
+            .unwrap();
+        let device = repository
+            .create_device(&account_id, "test", 1000.0)
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let timespan = Timespan::new(now, now + Duration::hours(4));
+        repository
+            .create_task(&account_id, device.id, &timespan, Duration::hours(1).into())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // The household uses 2000 W, except in the third hour from now
+        let minute_now = i64::from(now.num_seconds_from_midnight() / 60);
+        let profile = DailyProfile {
+            resolution_minutes: 1,
+            values: (0..MINUTES_PER_DAY)
+                .map(
+                    |minute| match (minute - minute_now).rem_euclid(MINUTES_PER_DAY) {
+                        120..180 => 0.0,
+                        _ => 2000.0,
+                    },
+                )
+                .collect(),
+        };
+        repository
+            .set_baseline(&account_id, &profile)
+            .await
+            .unwrap();
+
+        let mut graph = DiscreteGraph::new(vec![1500.0; 4], Duration::hours(1), now);
+        run_algorithm(&repository, &Algorithm::Global, &mut graph)
+            .await
+            .unwrap();
+
+        let events = repository
+            .events_for_account(&account_id, now)
+            .await
+            .unwrap();
+        assert_eq!(events[0].start_time, now + Duration::hours(2));
+
+        // The baseline and the task have been subtracted from the graph, and the baseline leaves no less than nothing
+        assert_eq!(graph.get_values(), &vec![0.0, 0.0, 500.0, 0.0]);
+    }
 }
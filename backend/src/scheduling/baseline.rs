@@ -0,0 +1,171 @@
+use anyhow::Result;
+use chrono::{Duration, Timelike};
+use protocol::{
+    baseline::{DailyProfile, StandardLoadProfile},
+    graph::{DiscreteGraph, Overlap},
+};
+
+use crate::data_model::repository::{AccountBaseline, Repository};
+
+const HOURS_PER_YEAR: f64 = 365.0 * 24.0;
+const WATTS_PER_KILOWATT: f64 = 1000.0;
+
+/// The relative load in each hour of the day, from midnight.
+const HOUSEHOLD_SHAPE: [f64; 24] = [
+    0.70, 0.55, 0.50, 0.47, 0.47, 0.52, 0.75, 1.05, 1.20, 1.25, 1.25, 1.35, 1.55, 1.50, 1.30, 1.15,
+    1.15, 1.35, 1.75, 1.90, 1.80, 1.60, 1.35, 1.00,
+];
+
+/// An hourly profile with the shape of `load_profile`, which uses `annual_kwh` over a year.
+pub fn standard_profile(load_profile: StandardLoadProfile, annual_kwh: f64) -> DailyProfile {
+    let shape = match load_profile {
+        StandardLoadProfile::Household => &HOUSEHOLD_SHAPE,
+    };
+    let average_watts = annual_kwh * WATTS_PER_KILOWATT / HOURS_PER_YEAR;
+    let shape_average = shape.iter().sum::<f64>() / shape.len() as f64;
+
+    DailyProfile {
+        resolution_minutes: 60,
+        values: shape
+            .iter()
+            .map(|factor| factor / shape_average * average_watts)
+            .collect(),
+    }
+}
+
+/// The load of `baseline` in every timeslot of `graph`.
+///
+/// Each timeslot gets the average of the profile over its minutes in the account's local time,
+/// so the load follows the local clock across daylight saving time changes.
+pub fn baseline_graph(graph: &DiscreteGraph, baseline: &AccountBaseline) -> DiscreteGraph {
+    let samples = graph.get_time_delta().num_minutes().max(1);
+    let values = graph
+        .slots()
+        .map(|(slot_start, _)| {
+            let total: f64 = (0..samples)
+                .map(|minute| {
+                    let local =
+                        (slot_start + Duration::minutes(minute)).with_timezone(&baseline.time_zone);
+                    baseline
+                        .profile
+                        .value_at(i64::from(local.num_seconds_from_midnight() / 60))
+                })
+                .sum();
+            total / samples as f64
+        })
+        .collect();
+
+    DiscreteGraph::new(values, graph.get_time_delta(), graph.get_start_time())
+}
+
+/// Subtracts the baseline consumption of every account from the graph,
+/// so the scheduler only sees the energy that is left for the scheduled devices.
+///
+/// Timeslots where the baselines use more than is produced are left with nothing, never less.
+pub async fn subtract_baselines(
+    repository: &dyn Repository,
+    graph: &mut DiscreteGraph,
+) -> Result<()> {
+    for baseline in repository.baselines().await? {
+        *graph = graph.sub(&baseline_graph(graph, &baseline), Overlap::Exact)?;
+    }
+
+    let nothing = DiscreteGraph::new(
+        vec![0.0; graph.get_values().len()],
+        graph.get_time_delta(),
+        graph.get_start_time(),
+    );
+    *graph = graph.max(&nothing, Overlap::Exact)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::{Europe::Copenhagen, Tz, UTC};
+
+    use super::*;
+    use crate::data_model::{
+        repository::{AccountRepository, BaselineRepository},
+        sqlite::SqliteRepository,
+    };
+
+    fn baseline(time_zone: Tz, profile: DailyProfile) -> AccountBaseline {
+        AccountBaseline { time_zone, profile }
+    }
+
+    #[test]
+    fn standard_profile_uses_the_yearly_consumption() {
+        let profile = standard_profile(StandardLoadProfile::Household, 3504.0);
+
+        assert!(profile.validate().is_ok());
+        let daily_kwh = profile.values.iter().sum::<f64>() / WATTS_PER_KILOWATT;
+        assert!((daily_kwh * 365.0 - 3504.0).abs() < 1e-6);
+        // Less is used at night than in the evening
+        assert!(profile.values[3] < profile.values[19]);
+    }
+
+    #[test]
+    fn baseline_follows_the_local_clock() {
+        let mut values = vec![100.0; 24];
+        values[18] = 500.0;
+        let profile = DailyProfile {
+            resolution_minutes: 60,
+            values,
+        };
+        // 18:00 in Copenhagen is 16:00 UTC in the summer
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let graph = DiscreteGraph::new(vec![0.0; 24], Duration::hours(1), start);
+
+        let local = baseline_graph(&graph, &baseline(Copenhagen, profile.clone()));
+        assert_eq!(local.get_values()[16], 500.0);
+        assert_eq!(local.get_values()[18], 100.0);
+
+        let utc = baseline_graph(&graph, &baseline(UTC, profile));
+        assert_eq!(utc.get_values()[18], 500.0);
+    }
+
+    #[test]
+    fn timeslots_average_the_profile() {
+        let profile = DailyProfile {
+            resolution_minutes: 30,
+            values: (0..48)
+                .map(|half_hour| (half_hour % 2) as f64 * 100.0)
+                .collect(),
+        };
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let graph = DiscreteGraph::new(vec![0.0; 2], Duration::hours(1), start);
+
+        let baseline = baseline_graph(&graph, &baseline(UTC, profile));
+
+        assert_eq!(baseline.get_values(), &vec![50.0, 50.0]);
+    }
+
+    #[tokio::test]
+    async fn baselines_larger_than_production_leave_nothing() {
+        let repository = SqliteRepository::in_memory().await.unwrap();
+        for name in ["first", "second"] {
+            let account_id = repository
+                .create_account(name, "hash")
+                .await
+                .unwrap()
+                .unwrap();
+            let profile = DailyProfile {
+                resolution_minutes: 60,
+                values: vec![1000.0; 24],
+            };
+            repository
+                .set_baseline(&account_id, &profile)
+                .await
+                .unwrap();
+        }
+
+        let start = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let mut graph = DiscreteGraph::new(vec![1500.0, 2500.0], Duration::hours(1), start);
+        subtract_baselines(&repository, &mut graph).await.unwrap();
+
+        // The first hour produces less than the two accounts use together
+        assert_eq!(graph.get_values(), &vec![0.0, 500.0]);
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::Duration;
+use protocol::{graph::DiscreteGraph, time::DateTimeUtc};
+
+const MILLISECONDS_PER_HOUR: f64 = 3_600_000.0;
+const WATTS_PER_KILOWATT: f64 = 1000.0;
+
+/// The carbon intensity in gCO2/kWh at `time`, `None` if the series is empty.
+///
+/// Times before or after the series use its first or last value.
+pub fn intensity_at(carbon_intensity: &DiscreteGraph, time: DateTimeUtc) -> Option<f64> {
+    let last = carbon_intensity.get_values().len().checked_sub(1)?;
+    let index = match carbon_intensity.index_at(time) {
+        Some(index) => index,
+        None if time < carbon_intensity.get_start_time() => 0,
+        None => last,
+    };
+    carbon_intensity.get_values().get(index).copied()
+}
+
+/// The grams of CO2 emitted by importing `effect` from the grid for `duration`.
+pub fn import_emissions(effect: f64, duration: Duration, intensity: f64) -> f64 {
+    effect.max(0.0) * duration.num_milliseconds() as f64
+        / MILLISECONDS_PER_HOUR
+        / WATTS_PER_KILOWATT
+        * intensity
+}
+
+/// The estimated grams of CO2 of the energy missing from `graph`, which has to be imported from the grid.
+///
+/// Every timeslot is charged by the carbon intensity at its start.
+pub fn carbon_cost(graph: &DiscreteGraph, carbon_intensity: &DiscreteGraph) -> f64 {
+    graph
+        .slots()
+        .filter(|(_, value)| *value < 0.0)
+        .map(|(time, value)| {
+            let intensity = intensity_at(carbon_intensity, time).unwrap_or(0.0);
+            import_emissions(-value, graph.get_time_delta(), intensity)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn start() -> DateTimeUtc {
+        Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn intensity_is_looked_up_by_time() {
+        let intensity = DiscreteGraph::new(vec![100.0, 200.0, 300.0], Duration::hours(1), start());
+
+        assert_eq!(intensity_at(&intensity, start()), Some(100.0));
+        assert_eq!(
+            intensity_at(&intensity, start() + Duration::minutes(90)),
+            Some(200.0)
+        );
+        // Outside the series the nearest value is used
+        assert_eq!(
+            intensity_at(&intensity, start() - Duration::hours(5)),
+            Some(100.0)
+        );
+        assert_eq!(
+            intensity_at(&intensity, start() + Duration::days(1)),
+            Some(300.0)
+        );
+
+        let empty = DiscreteGraph::new(vec![], Duration::hours(1), start());
+        assert_eq!(intensity_at(&empty, start()), None);
+    }
+
+    #[test]
+    fn only_imported_energy_is_charged() {
+        let intensity = DiscreteGraph::new(vec![100.0, 400.0], Duration::hours(1), start());
+        // A surplus in the first hour, and 500 W imported in the second
+        let graph = DiscreteGraph::new(
+            vec![300.0, 300.0, -500.0, -500.0],
+            Duration::minutes(30),
+            start(),
+        );
+
+        assert_eq!(carbon_cost(&graph, &intensity), 0.5 * 400.0);
+    }
+}
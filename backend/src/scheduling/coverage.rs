@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use protocol::graph::DiscreteGraph;
+
+use super::{task_for_scheduler::TaskForScheduler, unpublished_event::UnpublishedEvent};
+
+/// The share, between 0 and 1, of the energy used by `event` that is covered by renewable energy.
+///
+/// `graph` must be the graph after every scheduled event, including `event`, has been removed from it.
+pub fn renewable_coverage(
+    graph: &DiscreteGraph,
+    task: &TaskForScheduler,
+    event: &UnpublishedEvent,
+) -> Result<f64> {
+    let Some(timeslot) = graph
+        .index_at(event.start_time)
+        .filter(|timeslot| graph.time_at(*timeslot) == event.start_time)
+    else {
+        bail!(
+            "The event for task with id: {} does not start on a timeslot of the graph",
+            task.id
+        );
+    };
+
+    let time_delta = graph.get_time_delta().num_milliseconds();
+    let duration = usize::try_from(i64::from(task.duration))?.div_ceil(time_delta.try_into()?);
+    let Some(values) = graph.get_values().get(timeslot..timeslot + duration) else {
+        bail!(
+            "The event for task with id: {} ends after the graph",
+            task.id
+        );
+    };
+
+    // The event has already been removed from the graph, so its own effect is added back
+    let available: Vec<f64> = values.iter().map(|value| value + task.effect).collect();
+
+    Ok(window_coverage(&available, task.effect))
+}
+
+/// The share, between 0 and 1, of `effect` in every timeslot that is covered by the `available` energy.
+pub fn window_coverage(available: &[f64], effect: f64) -> f64 {
+    if effect <= 0.0 || available.is_empty() {
+        return 1.0;
+    }
+
+    let covered: f64 = available.iter().map(|value| value.clamp(0.0, effect)).sum();
+
+    covered / (effect * available.len() as f64)
+}
+
+/// The share of the energy used by all `events` that is covered by renewable energy,
+/// where each event is weighted by the energy it uses.
+///
+/// `graph` must be the graph after every event has been removed from it, see [renewable_coverage].
+pub fn total_renewable_coverage(
+    graph: &DiscreteGraph,
+    tasks: &[TaskForScheduler],
+    events: &[UnpublishedEvent],
+) -> Result<f64> {
+    let tasks: HashMap<_, _> = tasks.iter().map(|task| (task.id, task)).collect();
+
+    let mut covered = 0.0;
+    let mut total = 0.0;
+    for event in events {
+        let Some(task) = tasks.get(&event.task_id) else {
+            bail!("No task with id: {} for the event", event.task_id);
+        };
+        let energy = task.effect * i64::from(task.duration) as f64;
+        covered += renewable_coverage(graph, task, event)? * energy;
+        total += energy;
+    }
+
+    if total <= 0.0 {
+        return Ok(1.0);
+    }
+    Ok(covered / total)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use protocol::{graph::DiscreteGraph, time::Timespan};
+
+    use super::{renewable_coverage, total_renewable_coverage};
+    use crate::scheduling::{
+        task_for_scheduler::TaskForScheduler, unpublished_event::UnpublishedEvent,
+    };
+
+    #[test]
+    fn partially_covered_event() {
+        let start = Utc::now();
+        let task = TaskForScheduler {
+            id: 0.into(),
+            timespan: Timespan::new(start, start + Duration::seconds(4)),
+            duration: Duration::seconds(2).into(),
+            effect: 4.0,
+            exclusions: Vec::new(),
+        };
+        let event = UnpublishedEvent {
+            task_id: task.id,
+            start_time: start + Duration::seconds(1),
+            explanation: None,
+        };
+        // Before the event was removed the graph was [4.0, 4.0, 2.0, 0.0]
+        let graph = DiscreteGraph::new(vec![4.0, 0.0, -2.0, 0.0], Duration::seconds(1), start);
+
+        let coverage = renewable_coverage(&graph, &task, &event).unwrap();
+
+        assert_eq!(coverage, 0.75);
+    }
+
+    #[test]
+    fn event_outside_graph() {
+        let start = Utc::now();
+        let task = TaskForScheduler {
+            id: 0.into(),
+            timespan: Timespan::new(start, start + Duration::seconds(4)),
+            duration: Duration::seconds(2).into(),
+            effect: 4.0,
+            exclusions: Vec::new(),
+        };
+        let event = UnpublishedEvent {
+            task_id: task.id,
+            start_time: start + Duration::seconds(3),
+            explanation: None,
+        };
+        let graph = DiscreteGraph::new(vec![4.0, 0.0, -2.0, 0.0], Duration::seconds(1), start);
+
+        assert!(renewable_coverage(&graph, &task, &event).is_err());
+    }
+
+    #[test]
+    fn total_coverage_is_weighted_by_energy() {
+        let start = Utc::now();
+        let tasks = vec![
+            TaskForScheduler {
+                id: 0.into(),
+                timespan: Timespan::new(start, start + Duration::seconds(4)),
+                duration: Duration::seconds(1).into(),
+                effect: 4.0,
+                exclusions: Vec::new(),
+            },
+            TaskForScheduler {
+                id: 1.into(),
+                timespan: Timespan::new(start, start + Duration::seconds(4)),
+                duration: Duration::seconds(1).into(),
+                effect: 12.0,
+                exclusions: Vec::new(),
+            },
+        ];
+        let events = vec![
+            UnpublishedEvent {
+                task_id: 0.into(),
+                start_time: start,
+                explanation: None,
+            },
+            UnpublishedEvent {
+                task_id: 1.into(),
+                start_time: start + Duration::seconds(1),
+                explanation: None,
+            },
+        ];
+        // The first event is fully covered and the second is not covered at all
+        let graph = DiscreteGraph::new(vec![0.0, -12.0], Duration::seconds(1), start);
+
+        let coverage = total_renewable_coverage(&graph, &tasks, &events).unwrap();
+
+        assert_eq!(coverage, 0.25);
+    }
+}
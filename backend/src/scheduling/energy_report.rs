@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, DurationRound};
+use protocol::{
+    devices::DeviceId,
+    graph::DiscreteGraph,
+    reports::{DeviceEnergyReport, EnergyReport, EnergyReportRequest, EnergyUsage},
+    time::{DateTimeUtc, Timespan},
+};
+
+use super::background_service::SchedulingConfig;
+use crate::data_model::repository::ReportEntry;
+
+const MILLISECONDS_PER_HOUR: f64 = 3_600_000.0;
+const WATTS_PER_KILOWATT: f64 = 1000.0;
+const GRAMS_PER_KILOGRAM: f64 = 1000.0;
+
+/// A device running from `start` for `duration`.
+struct Run {
+    start: DateTimeUtc,
+    duration: Duration,
+    effect: f64,
+}
+
+impl Run {
+    fn end(&self) -> DateTimeUtc {
+        self.start + self.duration
+    }
+
+    fn energy_kwh(&self) -> f64 {
+        self.effect * self.duration.num_milliseconds() as f64
+            / MILLISECONDS_PER_HOUR
+            / WATTS_PER_KILOWATT
+    }
+}
+
+/// Reports the energy used by the runs in `entries` and how much of it is covered by renewable surplus,
+/// compared to a baseline where every task runs at the start of its timespan.
+///
+/// The surplus is the expected production of `config`, which follows its daily profile from midnight UTC,
+/// shared between the account's own runs.
+pub fn energy_report(
+    config: &SchedulingConfig,
+    request: &EnergyReportRequest,
+    entries: &[ReportEntry],
+) -> EnergyReport {
+    let scheduled: Vec<Run> = entries
+        .iter()
+        .map(|entry| Run {
+            start: entry.start_time,
+            duration: entry.duration.into(),
+            effect: entry.effect,
+        })
+        .collect();
+    let baseline: Vec<Run> = entries
+        .iter()
+        .map(|entry| Run {
+            start: entry.timespan_start,
+            duration: entry.duration.into(),
+            effect: entry.effect,
+        })
+        .collect();
+
+    let graph = availability_graph(config, request, scheduled.iter().chain(&baseline));
+    let renewable = covered_energy(&graph, &scheduled);
+    let baseline_renewable = covered_energy(&graph, &baseline);
+
+    let mut devices: Vec<DeviceEnergyReport> = Vec::new();
+    let mut total = EnergyUsage::default();
+    for (index, entry) in entries.iter().enumerate() {
+        let energy = scheduled[index].energy_kwh();
+        let device = match devices
+            .iter_mut()
+            .position(|device| device.device_id == entry.device_id)
+        {
+            Some(position) => &mut devices[position],
+            None => {
+                devices.push(DeviceEnergyReport {
+                    device_id: entry.device_id,
+                    device_name: entry.device_name.clone(),
+                    usage: EnergyUsage::default(),
+                });
+                devices.last_mut().unwrap()
+            }
+        };
+
+        for usage in [&mut device.usage, &mut total] {
+            usage.runs += 1;
+            usage.energy_kwh += energy;
+            usage.renewable_kwh += renewable[index];
+            usage.baseline_renewable_kwh += baseline_renewable[index];
+        }
+    }
+
+    devices.sort_by_key(|device| i64::from(device.device_id));
+    for usage in devices
+        .iter_mut()
+        .map(|device| &mut device.usage)
+        .chain([&mut total])
+    {
+        complete_usage(usage, request);
+    }
+
+    EnergyReport {
+        timespan: Timespan::new(request.start, request.end),
+        devices,
+        total,
+    }
+}
+
+/// The expected production from midnight UTC before the first run or the report starts,
+/// until every run and the report has ended.
+fn availability_graph<'a>(
+    config: &SchedulingConfig,
+    request: &EnergyReportRequest,
+    runs: impl Iterator<Item = &'a Run> + Clone,
+) -> DiscreteGraph {
+    let start = runs
+        .clone()
+        .map(|run| run.start)
+        .fold(request.start, DateTimeUtc::min);
+    let end = runs.map(Run::end).fold(request.end, DateTimeUtc::max);
+
+    let start = start.duration_trunc(Duration::days(1)).unwrap_or(start);
+    // One extra timeslot, as the graph is rounded down to whole timeslots
+    config.production_graph_for(start, end - start + config.slot_resolution)
+}
+
+/// The energy in kWh of each run that is covered by the graph.
+///
+/// Runs that overlap share the energy produced while they all run in proportion to their effect.
+fn covered_energy(graph: &DiscreteGraph, runs: &[Run]) -> Vec<f64> {
+    let time_delta = graph.get_time_delta().num_milliseconds();
+
+    // The start and end of each run in milliseconds from the start of the graph
+    let bounds: Vec<(i64, i64)> = runs
+        .iter()
+        .map(|run| {
+            let start = (run.start - graph.get_start_time()).num_milliseconds();
+            (start, start + run.duration.num_milliseconds())
+        })
+        .collect();
+
+    // The runs in each timeslot
+    let mut slots: HashMap<i64, Vec<usize>> = HashMap::new();
+    for (index, &(start, end)) in bounds.iter().enumerate() {
+        if end > start {
+            for slot in start.div_euclid(time_delta)..=(end - 1).div_euclid(time_delta) {
+                slots.entry(slot).or_default().push(index);
+            }
+        }
+    }
+
+    let mut covered = vec![0.0; runs.len()];
+    for (slot, indices) in slots {
+        let available = usize::try_from(slot)
+            .ok()
+            .and_then(|slot| graph.get_values().get(slot))
+            .map_or(0.0, |value| value.max(0.0));
+        let slot_start = slot * time_delta;
+        let slot_end = slot_start + time_delta;
+
+        // The value is constant within the timeslot, but the runs using it change where runs start or end
+        let mut points: Vec<i64> = indices
+            .iter()
+            .flat_map(|&index| [bounds[index].0, bounds[index].1])
+            .chain([slot_start, slot_end])
+            .filter(|point| (slot_start..=slot_end).contains(point))
+            .collect();
+        points.sort_unstable();
+        points.dedup();
+
+        for (&from, &to) in points.iter().zip(&points[1..]) {
+            let running: Vec<usize> = indices
+                .iter()
+                .copied()
+                .filter(|&index| bounds[index].0 <= from && to <= bounds[index].1)
+                .collect();
+            let load: f64 = running.iter().map(|&index| runs[index].effect).sum();
+            let share = if load > 0.0 {
+                (available / load).min(1.0)
+            } else {
+                1.0
+            };
+            for index in running {
+                covered[index] += runs[index].effect * (to - from) as f64 * share;
+            }
+        }
+    }
+
+    covered
+        .into_iter()
+        .map(|covered| covered / MILLISECONDS_PER_HOUR / WATTS_PER_KILOWATT)
+        .collect()
+}
+
+/// Fills in the shares and savings from the summed energy.
+fn complete_usage(usage: &mut EnergyUsage, request: &EnergyReportRequest) {
+    // Like the renewable coverage, no energy used is fully covered
+    let share = |renewable: f64| {
+        if usage.energy_kwh > 0.0 {
+            renewable / usage.energy_kwh
+        } else {
+            1.0
+        }
+    };
+    usage.renewable_share = share(usage.renewable_kwh);
+    usage.baseline_renewable_share = share(usage.baseline_renewable_kwh);
+    usage.saved_kwh = usage.renewable_kwh - usage.baseline_renewable_kwh;
+    usage.cost_savings = request.price_per_kwh.map(|price| usage.saved_kwh * price);
+    usage.co2_savings_kg = request
+        .co2_grams_per_kwh
+        .map(|grams| usage.saved_kwh * grams / GRAMS_PER_KILOGRAM);
+}
+
+/// The report as CSV, with a row per device followed by a row with the totals.
+pub fn energy_report_csv(report: &EnergyReport) -> String {
+    let mut csv = String::from(
+        "device_id,device_name,runs,energy_kwh,renewable_kwh,baseline_renewable_kwh,\
+         renewable_share,baseline_renewable_share,saved_kwh,cost_savings,co2_savings_kg\r\n",
+    );
+
+    let rows = report
+        .devices
+        .iter()
+        .map(|device| {
+            (
+                Some(device.device_id),
+                device.device_name.as_str(),
+                &device.usage,
+            )
+        })
+        .chain([(None::<DeviceId>, "Total", &report.total)]);
+
+    for (device_id, name, usage) in rows {
+        let optional =
+            |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+        let fields = [
+            device_id.map(|id| id.to_string()).unwrap_or_default(),
+            escape_field(name),
+            usage.runs.to_string(),
+            usage.energy_kwh.to_string(),
+            usage.renewable_kwh.to_string(),
+            usage.baseline_renewable_kwh.to_string(),
+            usage.renewable_share.to_string(),
+            usage.baseline_renewable_share.to_string(),
+            usage.saved_kwh.to_string(),
+            optional(usage.cost_savings),
+            optional(usage.co2_savings_kg),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+
+    csv
+}
+
+/// Quotes the field if it contains a separator, quote or line break, as in RFC 4180.
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn config() -> SchedulingConfig {
+        SchedulingConfig {
+            slot_resolution: Duration::hours(1),
+            ..Default::default()
+        }
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTimeUtc {
+        Utc.with_ymd_and_hms(2024, 6, 1, hour, minute, 0).unwrap()
+    }
+
+    fn request() -> EnergyReportRequest {
+        EnergyReportRequest {
+            start: at(0, 0),
+            end: at(23, 0),
+            price_per_kwh: None,
+            co2_grams_per_kwh: None,
+        }
+    }
+
+    fn entry(device_id: i64, timespan_start: DateTimeUtc, start_time: DateTimeUtc) -> ReportEntry {
+        ReportEntry {
+            device_id: device_id.into(),
+            device_name: format!("device {}", device_id),
+            effect: 1000.0,
+            timespan_start,
+            start_time,
+            duration: Duration::hours(1).into(),
+        }
+    }
+
+    #[test]
+    fn scheduling_into_the_surplus_saves_grid_energy() {
+        let request = EnergyReportRequest {
+            price_per_kwh: Some(2.0),
+            co2_grams_per_kwh: Some(100.0),
+            ..request()
+        };
+        // Nothing is produced at midnight, and more than the device uses at noon
+        let entries = [entry(1, at(0, 0), at(12, 0))];
+
+        let report = energy_report(&config(), &request, &entries);
+
+        let usage = &report.total;
+        assert_eq!(usage.runs, 1);
+        assert_eq!(usage.energy_kwh, 1.0);
+        assert_eq!(usage.renewable_kwh, 1.0);
+        assert_eq!(usage.baseline_renewable_kwh, 0.0);
+        assert_eq!(usage.renewable_share, 1.0);
+        assert_eq!(usage.baseline_renewable_share, 0.0);
+        assert_eq!(usage.saved_kwh, 1.0);
+        assert_eq!(usage.cost_savings, Some(2.0));
+        assert_eq!(usage.co2_savings_kg, Some(0.1));
+        assert_eq!(report.devices[0].usage, report.total);
+    }
+
+    #[test]
+    fn overlapping_runs_share_the_surplus() {
+        // 200 W is produced from 07:00 to 08:00
+        let entries = [entry(1, at(7, 0), at(7, 0)), entry(2, at(7, 0), at(7, 0))];
+
+        let report = energy_report(&config(), &request(), &entries);
+
+        assert_eq!(report.devices.len(), 2);
+        for device in &report.devices {
+            assert!((device.usage.renewable_kwh - 0.1).abs() < 1e-9);
+        }
+        assert!((report.total.renewable_share - 0.1).abs() < 1e-9);
+        assert_eq!(report.total.saved_kwh, 0.0);
+        assert_eq!(report.total.cost_savings, None);
+    }
+
+    #[test]
+    fn runs_are_split_over_the_timeslots_they_overlap() {
+        // Half an hour of 200 W and half an hour of 484 W
+        let entries = [entry(1, at(7, 30), at(7, 30))];
+
+        let report = energy_report(&config(), &request(), &entries);
+
+        assert!((report.total.renewable_kwh - (0.1 + 0.242)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn runs_of_a_device_are_summed() {
+        let entries = [entry(1, at(0, 0), at(12, 0)), entry(1, at(1, 0), at(13, 0))];
+
+        let report = energy_report(&config(), &request(), &entries);
+
+        assert_eq!(report.devices.len(), 1);
+        assert_eq!(report.devices[0].usage.runs, 2);
+        assert_eq!(report.devices[0].usage.energy_kwh, 2.0);
+    }
+
+    #[test]
+    fn csv_has_a_row_per_device_and_a_total() {
+        let mut entries = [entry(1, at(0, 0), at(12, 0)), entry(2, at(0, 0), at(13, 0))];
+        entries[1].device_name = "Washer, \"upstairs\"".to_owned();
+        let request = EnergyReportRequest {
+            price_per_kwh: Some(2.0),
+            ..request()
+        };
+
+        let csv = energy_report_csv(&energy_report(&config(), &request, &entries));
+
+        let lines: Vec<&str> = csv.split_terminator("\r\n").collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("device_id,device_name,runs,"));
+        assert!(lines[2].starts_with("2,\"Washer, \"\"upstairs\"\"\",1,1,"));
+        assert!(lines[3].starts_with(",Total,2,2,"));
+        assert!(lines[3].ends_with(",2,4,"));
+    }
+}
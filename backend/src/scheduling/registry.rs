@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use protocol::scheduling::{Algorithm, AlgorithmInfo};
+
+use super::scheduler::{
+    AllPermutationsAlgorithm, GlobalSchedulerAlgorithm, NaiveSchedulerAlgorithm, SchedulerAlgorithm,
+};
+
+/// Creates the [SchedulerAlgorithm] selected by `algorithm` with its parameters.
+pub fn create_algorithm(algorithm: &Algorithm) -> Arc<dyn SchedulerAlgorithm + Send + Sync> {
+    match algorithm {
+        Algorithm::Naive => Arc::new(NaiveSchedulerAlgorithm::new()),
+        Algorithm::Global => Arc::new(GlobalSchedulerAlgorithm::new()),
+        Algorithm::AllPermutations { max_tasks: None } => Arc::new(AllPermutationsAlgorithm::new()),
+        Algorithm::AllPermutations {
+            max_tasks: Some(max_tasks),
+        } => Arc::new(AllPermutationsAlgorithm::with_max_tasks(*max_tasks)),
+    }
+}
+
+/// All algorithms that can be created with [create_algorithm], with their default parameters.
+pub fn available_algorithms() -> Vec<AlgorithmInfo> {
+    vec![
+        AlgorithmInfo {
+            algorithm: Algorithm::Naive,
+            description: "Schedules each task at its best time, ignoring the other tasks"
+                .to_string(),
+        },
+        AlgorithmInfo {
+            algorithm: Algorithm::Global,
+            description:
+                "Schedules the tasks one at a time, taking the already scheduled tasks into account"
+                    .to_string(),
+        },
+        AlgorithmInfo {
+            algorithm: Algorithm::AllPermutations { max_tasks: None },
+            description:
+                "Runs the global algorithm on every order of the tasks and keeps the best schedule"
+                    .to_string(),
+        },
+    ]
+}
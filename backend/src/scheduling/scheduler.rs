@@ -1,10 +1,28 @@
-use std::cmp::min;
-
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use super::carbon::{import_emissions, intensity_at};
+use super::coverage::window_coverage;
 use super::task_for_scheduler::TaskForScheduler;
 use super::unpublished_event::UnpublishedEvent;
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
+use chrono::Duration;
 use itertools::Itertools;
-use protocol::graph::DiscreteGraph;
+use protocol::{
+    events::{BindingConstraint, EventExplanation, WindowScore, MAX_EXPLANATION_ALTERNATIVES},
+    graph::DiscreteGraph,
+    time::{DateTimeUtc, Timespan},
+};
+use rayon::prelude::*;
+use tokio_util::sync::CancellationToken;
 
 pub trait SchedulerAlgorithm {
     fn schedule(
@@ -12,21 +30,173 @@ pub trait SchedulerAlgorithm {
         graph: &mut DiscreteGraph,
         tasks: Vec<TaskForScheduler>,
     ) -> Result<Vec<UnpublishedEvent>>;
+
+    /// Schedules the tasks like [SchedulerAlgorithm::schedule], but first minimizes the CO2 of the energy
+    /// imported from the grid, given its carbon intensity in gCO2/kWh.
+    ///
+    /// Algorithms that do not support this ignore the carbon intensity.
+    fn schedule_with_carbon_intensity(
+        &self,
+        graph: &mut DiscreteGraph,
+        tasks: Vec<TaskForScheduler>,
+        _carbon_intensity: &DiscreteGraph,
+    ) -> Result<Vec<UnpublishedEvent>> {
+        self.schedule(graph, tasks)
+    }
+
+    /// Schedules the tasks like [SchedulerAlgorithm::schedule_with_carbon_intensity] when a carbon intensity is given,
+    /// and otherwise like [SchedulerAlgorithm::schedule], while reporting the progress to `control`.
+    ///
+    /// When `control` is cancelled the run stops early and returns the best schedule found so far.
+    /// Algorithms that do not support this are only cancelled before they start, and report no progress.
+    fn schedule_cancellable(
+        &self,
+        graph: &mut DiscreteGraph,
+        tasks: Vec<TaskForScheduler>,
+        carbon_intensity: Option<&DiscreteGraph>,
+        control: &RunControl,
+    ) -> Result<Schedule> {
+        if control.is_cancelled() {
+            return Ok(Schedule::cancelled(Vec::new()));
+        }
+
+        let events = match carbon_intensity {
+            Some(carbon_intensity) => {
+                self.schedule_with_carbon_intensity(graph, tasks, carbon_intensity)?
+            }
+            None => self.schedule(graph, tasks)?,
+        };
+        Ok(Schedule::completed(events))
+    }
+}
+
+impl<T: SchedulerAlgorithm + ?Sized> SchedulerAlgorithm for Arc<T> {
+    fn schedule(
+        &self,
+        graph: &mut DiscreteGraph,
+        tasks: Vec<TaskForScheduler>,
+    ) -> Result<Vec<UnpublishedEvent>> {
+        (**self).schedule(graph, tasks)
+    }
+
+    fn schedule_with_carbon_intensity(
+        &self,
+        graph: &mut DiscreteGraph,
+        tasks: Vec<TaskForScheduler>,
+        carbon_intensity: &DiscreteGraph,
+    ) -> Result<Vec<UnpublishedEvent>> {
+        (**self).schedule_with_carbon_intensity(graph, tasks, carbon_intensity)
+    }
+
+    fn schedule_cancellable(
+        &self,
+        graph: &mut DiscreteGraph,
+        tasks: Vec<TaskForScheduler>,
+        carbon_intensity: Option<&DiscreteGraph>,
+        control: &RunControl,
+    ) -> Result<Schedule> {
+        (**self).schedule_cancellable(graph, tasks, carbon_intensity, control)
+    }
+}
+
+/// The events of a run of a [SchedulerAlgorithm].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    pub events: Vec<UnpublishedEvent>,
+    /// Whether the run was cancelled, in which case the events are the best found so far
+    /// and may leave out tasks
+    pub cancelled: bool,
+}
+
+impl Schedule {
+    fn completed(events: Vec<UnpublishedEvent>) -> Self {
+        Schedule {
+            events,
+            cancelled: false,
+        }
+    }
+
+    fn cancelled(events: Vec<UnpublishedEvent>) -> Self {
+        Schedule {
+            events,
+            cancelled: true,
+        }
+    }
+}
+
+/// How far a run of a [SchedulerAlgorithm] has come.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SchedulingProgress {
+    /// How many tasks have been scheduled, or complete schedules compared, so far
+    pub steps: usize,
+    /// The [graph_cost] of the best complete schedule so far, for algorithms that compare schedules
+    pub best_cost: Option<f64>,
+}
+
+/// Lets the caller of [SchedulerAlgorithm::schedule_cancellable] cancel the run and follow its progress.
+#[derive(Clone, Default)]
+pub struct RunControl {
+    cancellation: CancellationToken,
+    /// When the run is cancelled by itself
+    deadline: Option<Instant>,
+    progress: Option<Arc<dyn Fn(SchedulingProgress) + Send + Sync>>,
+}
+
+impl RunControl {
+    /// A run that stops when `cancellation` is cancelled.
+    pub fn new(cancellation: CancellationToken) -> Self {
+        RunControl {
+            cancellation,
+            deadline: None,
+            progress: None,
+        }
+    }
+
+    /// Also cancels the run at `deadline`.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Calls `progress` whenever the run reports its progress, which may be from several threads.
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(SchedulingProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn report(&self, progress: SchedulingProgress) {
+        if let Some(report) = &self.progress {
+            report(progress);
+        }
+    }
+}
+
+pub struct AllPermutationsAlgorithm {
+    max_tasks: Option<usize>,
 }
-pub struct AllPermutationsAlgorithm;
 pub struct GlobalSchedulerAlgorithm;
 pub struct NaiveSchedulerAlgorithm;
 
 #[allow(clippy::new_without_default)]
 impl AllPermutationsAlgorithm {
     pub fn new() -> Self {
-        AllPermutationsAlgorithm
+        AllPermutationsAlgorithm { max_tasks: None }
     }
-    fn weight(val: &f64) -> f64 {
-        if *val < 0.0 {
-            val.powi(3).abs()
-        } else {
-            val.powi(2)
+    /// Falls back to the [GlobalSchedulerAlgorithm] when there are more than `max_tasks` tasks,
+    /// as the amount of permutations grows factorially.
+    pub fn with_max_tasks(max_tasks: usize) -> Self {
+        AllPermutationsAlgorithm {
+            max_tasks: Some(max_tasks),
         }
     }
 }
@@ -51,32 +221,352 @@ impl SchedulerAlgorithm for AllPermutationsAlgorithm {
         graph: &mut DiscreteGraph,
         tasks: Vec<TaskForScheduler>,
     ) -> Result<Vec<UnpublishedEvent>> {
-        let scheudler = GlobalSchedulerAlgorithm::new();
+        self.schedule_permutations(graph, tasks, None, &RunControl::default())
+            .map(|schedule| schedule.events)
+    }
+
+    fn schedule_with_carbon_intensity(
+        &self,
+        graph: &mut DiscreteGraph,
+        tasks: Vec<TaskForScheduler>,
+        carbon_intensity: &DiscreteGraph,
+    ) -> Result<Vec<UnpublishedEvent>> {
+        self.schedule_permutations(graph, tasks, Some(carbon_intensity), &RunControl::default())
+            .map(|schedule| schedule.events)
+    }
+
+    fn schedule_cancellable(
+        &self,
+        graph: &mut DiscreteGraph,
+        tasks: Vec<TaskForScheduler>,
+        carbon_intensity: Option<&DiscreteGraph>,
+        control: &RunControl,
+    ) -> Result<Schedule> {
+        self.schedule_permutations(graph, tasks, carbon_intensity, control)
+    }
+}
 
+impl AllPermutationsAlgorithm {
+    /// Keeps the schedule with the least emissions, if the carbon intensity is known, and then the lowest [graph_cost].
+    ///
+    /// The orders are searched depth first, so orders with a common prefix share the graph the prefix
+    /// leaves behind, and the first levels of the search are explored in parallel.
+    /// A prefix is abandoned when a lower bound of its cost is worse than the best schedule found so far,
+    /// or when another order already scheduled the same tasks at the same times.
+    /// A cancelled search returns the best complete schedule it has found, if any.
+    fn schedule_permutations(
+        &self,
+        graph: &mut DiscreteGraph,
+        tasks: Vec<TaskForScheduler>,
+        carbon_intensity: Option<&DiscreteGraph>,
+        control: &RunControl,
+    ) -> Result<Schedule> {
         let len = tasks.len();
-        let permutaions = tasks.into_iter().permutations(len);
+        if self.max_tasks.is_some_and(|max_tasks| len > max_tasks) {
+            return schedule_global(graph, &tasks, carbon_intensity, control);
+        }
+        // Every order costs the same when the graph is not finite, so the first one is kept
+        if graph.get_values().iter().any(|value| !value.is_finite()) {
+            return schedule_global(graph, &tasks, carbon_intensity, control);
+        }
 
-        let (best_graph, _, best_schedule) = permutaions
-            .map(|permutation| {
-                let mut temp_graph = graph.clone();
-                let res = scheudler.schedule(&mut temp_graph, permutation);
-                (temp_graph, res)
-            })
-            .map(|(graph, schedule)| {
-                let graph_sum = graph
-                    .get_values()
-                    .iter()
-                    .map(|val: &f64| Self::weight(val))
-                    .sum::<f64>();
-                (graph, graph_sum, schedule)
-            })
-            .min_by(|(_, graph1_sum, _), (_, graph2_sum, _)| {
-                graph1_sum.partial_cmp(graph2_sum).unwrap()
+        PermutationSearch::new(graph, &tasks, carbon_intensity, control)?.run(graph)
+    }
+}
+
+/// How many times the level of the [fluid_cost_bound] is bisected.
+const FLUID_BOUND_ITERATIONS: usize = 64;
+/// The share of the [fluid_cost_bound] that is left out, to cover rounding errors.
+const FLUID_BOUND_MARGIN: f64 = 1e-9;
+
+/// How many complete schedules a [PermutationSearch] compares between progress reports,
+/// besides reporting every better schedule.
+const PROGRESS_INTERVAL: usize = 1000;
+
+/// How many levels of the search over task orders are explored in parallel.
+///
+/// Deeper levels are explored on the thread that reached them, as there is enough work to go around by then.
+const PARALLEL_DEPTH: usize = 2;
+
+/// The emissions and [graph_cost] of a schedule, which are compared in that order.
+#[derive(Clone, Copy, Debug)]
+struct Score {
+    emissions: f64,
+    cost: f64,
+}
+
+impl Score {
+    /// NaN is treated as the worst possible score, so it never wins a comparison.
+    fn new(emissions: f64, cost: f64) -> Self {
+        let not_nan = |value: f64| if value.is_nan() { f64::INFINITY } else { value };
+        Score {
+            emissions: not_nan(emissions),
+            cost: not_nan(cost),
+        }
+    }
+
+    fn compare(&self, other: &Score) -> Ordering {
+        self.emissions
+            .total_cmp(&other.emissions)
+            .then(self.cost.total_cmp(&other.cost))
+    }
+}
+
+/// The tasks scheduled so far in a [PermutationSearch], and the graph they left behind.
+struct Prefix {
+    /// A lower bound of the score of every schedule that starts with this prefix
+    score: Score,
+    /// The timeslot each task starts in, by the index of the task
+    starts: Vec<Option<usize>>,
+    /// The event of each task, by the index of the task
+    events: Vec<Option<UnpublishedEvent>>,
+    graph: DiscreteGraph,
+}
+
+impl Prefix {
+    fn remaining(&self) -> impl Iterator<Item = usize> + '_ {
+        self.starts.iter().positions(Option::is_none)
+    }
+}
+
+/// A branch and bound search over the orders in which [schedule_global] can schedule the tasks.
+struct PermutationSearch<'a> {
+    tasks: &'a [TaskForScheduler],
+    carbon_intensity: Option<&'a DiscreteGraph>,
+    /// The timeslots each task can run in
+    task_slots: Vec<Range<usize>>,
+    /// The energy each task removes from the graph, in the graph's unit times timeslots
+    task_energy: Vec<f64>,
+    /// The carbon intensity at the start of every timeslot of the graph, 0 if it is unknown
+    intensities: Vec<f64>,
+    time_delta: Duration,
+    /// The start times of every prefix that has been reached,
+    /// as the orders that schedule the same tasks at the same times continue in the same way
+    reached: Mutex<HashSet<Vec<Option<usize>>>>,
+    /// The best complete schedule found so far.
+    ///
+    /// Ties are won by the schedule that starts the first tasks earliest, so the result does not depend on the threads.
+    best: Mutex<Option<Prefix>>,
+    /// The first error scheduling a task, which is returned if no order can be scheduled
+    error: Mutex<Option<anyhow::Error>>,
+    control: &'a RunControl,
+    /// How many complete schedules have been compared
+    compared: AtomicUsize,
+    /// Whether the search stopped before every order was tried
+    interrupted: AtomicBool,
+}
+
+impl<'a> PermutationSearch<'a> {
+    fn new(
+        graph: &DiscreteGraph,
+        tasks: &'a [TaskForScheduler],
+        carbon_intensity: Option<&'a DiscreteGraph>,
+        control: &'a RunControl,
+    ) -> Result<Self> {
+        let mut task_slots = Vec::with_capacity(tasks.len());
+        let mut task_energy = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            let (start, end, duration) = get_task_as_timeslots(task, graph)?;
+            task_slots.push(start..end);
+            task_energy.push(task.effect * duration as f64);
+        }
+        let intensities = graph
+            .slots()
+            .map(|(time, _)| {
+                carbon_intensity
+                    .and_then(|carbon_intensity| intensity_at(carbon_intensity, time))
+                    .unwrap_or(0.0)
             })
-            .unwrap();
+            .collect();
+
+        Ok(PermutationSearch {
+            tasks,
+            carbon_intensity,
+            task_slots,
+            task_energy,
+            intensities,
+            time_delta: graph.get_time_delta(),
+            reached: Mutex::new(HashSet::new()),
+            best: Mutex::new(None),
+            error: Mutex::new(None),
+            control,
+            compared: AtomicUsize::new(0),
+            interrupted: AtomicBool::new(false),
+        })
+    }
 
-        *graph = best_graph;
-        best_schedule
+    /// Finds the best schedule and leaves the graph of it behind,
+    /// or returns the first error if no order can be scheduled.
+    fn run(self, graph: &mut DiscreteGraph) -> Result<Schedule> {
+        let starts = vec![None; self.tasks.len()];
+        let remaining: Vec<usize> = (0..self.tasks.len()).collect();
+        self.explore(Prefix {
+            score: self.lower_bound(graph, &remaining),
+            starts,
+            events: vec![None; self.tasks.len()],
+            graph: graph.clone(),
+        });
+
+        let interrupted = self.interrupted.into_inner();
+        match self.best.into_inner().unwrap() {
+            Some(best) => {
+                *graph = best.graph;
+                let events = best.events.into_iter().flatten().collect();
+                Ok(Schedule {
+                    events,
+                    cancelled: interrupted,
+                })
+            }
+            None if interrupted => Ok(Schedule::cancelled(Vec::new())),
+            None => Err(self
+                .error
+                .into_inner()
+                .unwrap()
+                .unwrap_or_else(|| anyhow!("No order of the tasks could be scheduled"))),
+        }
+    }
+
+    /// Tries every order that starts with the prefix, the most promising ones first.
+    fn explore(&self, prefix: Prefix) {
+        if self.control.is_cancelled() {
+            self.interrupted.store(true, AtomicOrdering::Relaxed);
+            return;
+        }
+        if self.is_pruned(&prefix) {
+            return;
+        }
+        let remaining: Vec<usize> = prefix.remaining().collect();
+        if remaining.is_empty() {
+            self.offer(prefix);
+            return;
+        }
+
+        let extend = |&task: &usize| match self.extend(&prefix, task) {
+            Ok(child) => child,
+            Err(error) => {
+                self.error.lock().unwrap().get_or_insert(error);
+                None
+            }
+        };
+
+        if self.tasks.len() - remaining.len() < PARALLEL_DEPTH {
+            let children: Vec<Prefix> = remaining.par_iter().filter_map(extend).collect();
+            children
+                .into_par_iter()
+                .for_each(|child| self.explore(child));
+        } else {
+            let mut children: Vec<Prefix> = remaining.iter().filter_map(extend).collect();
+            children.sort_by(|child1, child2| child1.score.compare(&child2.score));
+            children.into_iter().for_each(|child| self.explore(child));
+        }
+    }
+
+    /// Schedules the task after the prefix, `None` if another order already reached the same prefix.
+    fn extend(&self, prefix: &Prefix, task: usize) -> Result<Option<Prefix>> {
+        let timeslot = find_best_event(&self.tasks[task], &prefix.graph, self.carbon_intensity)?;
+
+        let mut starts = prefix.starts.clone();
+        starts[task] = Some(timeslot);
+        if !self.reached.lock().unwrap().insert(starts.clone()) {
+            return Ok(None);
+        }
+
+        let mut graph = prefix.graph.clone();
+        let duration_in_timeslots = duration_as_timeslots(&self.tasks[task], &graph)?;
+        let event = make_unpublished_event_and_remove_from_graph(
+            &mut graph,
+            &self.tasks[task],
+            timeslot,
+            duration_in_timeslots,
+        )?;
+        let mut events = prefix.events.clone();
+        events[task] = Some(event);
+        let remaining: Vec<usize> = starts.iter().positions(Option::is_none).collect();
+
+        Ok(Some(Prefix {
+            score: self.lower_bound(&graph, &remaining),
+            starts,
+            events,
+            graph,
+        }))
+    }
+
+    /// A lower bound of the score of every schedule that continues from `graph` with the `remaining` tasks.
+    ///
+    /// Without remaining tasks this is the score of `graph` itself.
+    fn lower_bound(&self, graph: &DiscreteGraph, remaining: &[usize]) -> Score {
+        let values = graph.get_values();
+        if remaining.is_empty() {
+            let emissions = values
+                .iter()
+                .zip(&self.intensities)
+                .filter(|(value, _)| **value < 0.0)
+                .map(|(value, intensity)| import_emissions(-value, self.time_delta, *intensity))
+                .sum();
+            return Score::new(emissions, graph_cost(graph));
+        }
+
+        // Each timeslot can at most lose or gain the effects of the remaining tasks that can run in it
+        let mut lowest = values.clone();
+        let mut highest = values.clone();
+        let mut energy = 0.0;
+        for &task in remaining {
+            let effect = self.tasks[task].effect;
+            let slots = self.task_slots[task].clone();
+            if effect > 0.0 {
+                lowest[slots].iter_mut().for_each(|value| *value -= effect);
+            } else {
+                highest[slots].iter_mut().for_each(|value| *value -= effect);
+            }
+            energy += self.task_energy[task];
+        }
+
+        // Importing energy only gets worse when more tasks are scheduled
+        let emissions = highest
+            .iter()
+            .zip(&self.intensities)
+            .filter(|(value, _)| **value < 0.0)
+            .map(|(value, intensity)| import_emissions(-value, self.time_delta, *intensity))
+            .sum();
+
+        Score::new(
+            emissions,
+            fluid_cost_bound(values, &lowest, &highest, energy),
+        )
+    }
+
+    /// Whether no schedule that starts with the prefix can beat the best schedule.
+    fn is_pruned(&self, prefix: &Prefix) -> bool {
+        self.best
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|best| prefix.score.compare(&best.score).is_gt())
+    }
+
+    fn offer(&self, schedule: Prefix) {
+        let compared = self.compared.fetch_add(1, AtomicOrdering::Relaxed) + 1;
+        let (is_better, best_cost) = {
+            let mut best = self.best.lock().unwrap();
+            let is_better = best.as_ref().is_none_or(|best| {
+                schedule
+                    .score
+                    .compare(&best.score)
+                    .then(schedule.starts.cmp(&best.starts))
+                    .is_lt()
+            });
+            if is_better {
+                *best = Some(schedule);
+            }
+            (is_better, best.as_ref().map(|best| best.score.cost))
+        };
+
+        if is_better || compared.is_multiple_of(PROGRESS_INTERVAL) {
+            self.control.report(SchedulingProgress {
+                steps: compared,
+                best_cost,
+            });
+        }
     }
 }
 
@@ -86,19 +576,32 @@ impl SchedulerAlgorithm for GlobalSchedulerAlgorithm {
         graph: &mut DiscreteGraph,
         tasks: Vec<TaskForScheduler>,
     ) -> Result<Vec<UnpublishedEvent>> {
-        let mut scheduled_events: Vec<UnpublishedEvent> = Vec::new();
-        for task in &tasks {
-            let temp_graph = graph.clone();
-            let best_event = make_unpublished_event_and_remove_from_graph(
-                graph,
-                task,
-                find_best_event(task, &temp_graph)?,
-                duration_as_timeslots(task, &temp_graph)?,
-            )?;
-            scheduled_events.push(best_event);
-        }
+        schedule_global(graph, &tasks, None, &RunControl::default()).map(|schedule| schedule.events)
+    }
 
-        Ok(scheduled_events)
+    fn schedule_with_carbon_intensity(
+        &self,
+        graph: &mut DiscreteGraph,
+        tasks: Vec<TaskForScheduler>,
+        carbon_intensity: &DiscreteGraph,
+    ) -> Result<Vec<UnpublishedEvent>> {
+        schedule_global(
+            graph,
+            &tasks,
+            Some(carbon_intensity),
+            &RunControl::default(),
+        )
+        .map(|schedule| schedule.events)
+    }
+
+    fn schedule_cancellable(
+        &self,
+        graph: &mut DiscreteGraph,
+        tasks: Vec<TaskForScheduler>,
+        carbon_intensity: Option<&DiscreteGraph>,
+        control: &RunControl,
+    ) -> Result<Schedule> {
+        schedule_global(graph, &tasks, carbon_intensity, control)
     }
 }
 impl SchedulerAlgorithm for NaiveSchedulerAlgorithm {
@@ -107,40 +610,355 @@ impl SchedulerAlgorithm for NaiveSchedulerAlgorithm {
         graph: &mut DiscreteGraph,
         tasks: Vec<TaskForScheduler>,
     ) -> Result<Vec<UnpublishedEvent>> {
-        let mut scheduled_events: Vec<UnpublishedEvent> = Vec::new();
-        let initial_graph = graph.clone();
-        for task in &tasks {
-            scheduled_events.push(make_unpublished_event_and_remove_from_graph(
-                graph,
-                task,
-                find_best_event(task, &initial_graph)?,
-                duration_as_timeslots(task, &initial_graph)?,
-            )?);
+        schedule_naive(graph, &tasks, None)
+    }
+
+    fn schedule_with_carbon_intensity(
+        &self,
+        graph: &mut DiscreteGraph,
+        tasks: Vec<TaskForScheduler>,
+        carbon_intensity: &DiscreteGraph,
+    ) -> Result<Vec<UnpublishedEvent>> {
+        schedule_naive(graph, &tasks, Some(carbon_intensity))
+    }
+}
+
+/// Schedules the tasks one at a time, so a cancelled run returns the tasks scheduled so far.
+fn schedule_global(
+    graph: &mut DiscreteGraph,
+    tasks: &[TaskForScheduler],
+    carbon_intensity: Option<&DiscreteGraph>,
+    control: &RunControl,
+) -> Result<Schedule> {
+    let mut scheduled_events: Vec<UnpublishedEvent> = Vec::new();
+    for task in tasks {
+        if control.is_cancelled() {
+            return Ok(Schedule::cancelled(scheduled_events));
         }
+        scheduled_events.push(schedule_task(graph, task, carbon_intensity)?);
+        control.report(SchedulingProgress {
+            steps: scheduled_events.len(),
+            best_cost: None,
+        });
+    }
 
-        Ok(scheduled_events)
+    Ok(Schedule::completed(scheduled_events))
+}
+
+/// Schedules a task at its best start time in the graph, and removes its energy from the graph.
+fn schedule_task(
+    graph: &mut DiscreteGraph,
+    task: &TaskForScheduler,
+    carbon_intensity: Option<&DiscreteGraph>,
+) -> Result<UnpublishedEvent> {
+    let timeslot = find_best_event(task, graph, carbon_intensity)?;
+    let duration_in_timeslots = duration_as_timeslots(task, graph)?;
+    make_unpublished_event_and_remove_from_graph(graph, task, timeslot, duration_in_timeslots)
+}
+
+fn schedule_naive(
+    graph: &mut DiscreteGraph,
+    tasks: &[TaskForScheduler],
+    carbon_intensity: Option<&DiscreteGraph>,
+) -> Result<Vec<UnpublishedEvent>> {
+    let mut scheduled_events: Vec<UnpublishedEvent> = Vec::new();
+    let initial_graph = graph.clone();
+    for task in tasks {
+        scheduled_events.push(make_unpublished_event_and_remove_from_graph(
+            graph,
+            task,
+            find_best_event(task, &initial_graph, carbon_intensity)?,
+            duration_as_timeslots(task, &initial_graph)?,
+        )?);
     }
+
+    Ok(scheduled_events)
 }
 
-fn find_best_event(task: &TaskForScheduler, graph: &DiscreteGraph) -> Result<usize> {
+/// Finds the timeslot where the task should start.
+///
+/// With a carbon intensity the window with the least emissions from the grid is chosen first,
+/// and then the one with the most energy available.
+fn find_best_event(
+    task: &TaskForScheduler,
+    graph: &DiscreteGraph,
+    carbon_intensity: Option<&DiscreteGraph>,
+) -> Result<usize> {
     let (timeslot_start, timeslot_end, timeslot_duration) = get_task_as_timeslots(task, graph)?;
 
     // The set of values I for task T
-    let task_interval = &graph.get_values()[timeslot_start..=timeslot_end];
+    let task_interval = &graph.get_values()[timeslot_start..timeslot_end];
 
     // The set P(d') created using I
-    let mapped_graph = make_p_from_duration_in_timeslots(timeslot_duration, task_interval);
+    let mut mapped_graph = make_p_from_duration_in_timeslots(timeslot_duration, task_interval);
+
+    // The grams of CO2 imported from the grid when the task starts in each window
+    let mut emissions = match carbon_intensity {
+        Some(carbon_intensity) => {
+            let imported: Vec<f64> = task_interval
+                .iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    let time = graph.time_at(timeslot_start + index);
+                    let intensity = intensity_at(carbon_intensity, time).unwrap_or(0.0);
+                    let missing = task.effect - value.clamp(0.0, task.effect);
+                    import_emissions(missing, graph.get_time_delta(), intensity)
+                })
+                .collect();
+            make_p_from_duration_in_timeslots(timeslot_duration, &imported)
+        }
+        None => vec![0.0; mapped_graph.len()],
+    };
 
-    // Getting the max value for P(d'),
+    // Windows overlapping an excluded timespan can never be chosen
+    let excluded = excluded_windows(
+        task,
+        graph,
+        timeslot_start,
+        timeslot_duration,
+        mapped_graph.len(),
+    );
+    for (index, value) in mapped_graph.iter_mut().enumerate() {
+        if excluded[index] {
+            *value = f64::NEG_INFINITY;
+            emissions[index] = f64::INFINITY;
+        }
+    }
+
+    // Getting the max value for P(d') among the windows with the least emissions,
     // then finding the timeslot in which the event should begin
-    let greatest_index = mapped_graph
-        .iter()
-        .position_max_by(|x, y| x.total_cmp(y))
+    let greatest_index = (0..mapped_graph.len())
+        .position_max_by(|&x, &y| {
+            emissions[y]
+                .total_cmp(&emissions[x])
+                .then(mapped_graph[x].total_cmp(&mapped_graph[y]))
+        })
         .unwrap();
 
+    if mapped_graph[greatest_index] == f64::NEG_INFINITY {
+        bail!(
+            "Unschedulable task provided, because every start time overlaps an exclusion, task: {:?}",
+            task
+        );
+    }
+
     Ok(timeslot_start + greatest_index)
 }
 
+/// Whether each of the first `windows` windows of `timeslot_duration` timeslots from `timeslot_start`
+/// overlaps a timespan the task is excluded from.
+fn excluded_windows(
+    task: &TaskForScheduler,
+    graph: &DiscreteGraph,
+    timeslot_start: usize,
+    timeslot_duration: usize,
+    windows: usize,
+) -> Vec<bool> {
+    (0..windows)
+        .map(|index| {
+            let window_start = graph.time_at(timeslot_start + index);
+            let window_end = graph.time_at(timeslot_start + index + timeslot_duration);
+            task.exclusions
+                .iter()
+                .any(|exclusion| window_start < exclusion.end && exclusion.start < window_end)
+        })
+        .collect()
+}
+
+/// Explains why `event` starts when it does, by scoring every window of the task like [find_best_event].
+///
+/// `input_graph` is the graph the scheduler was given, and `graph` the graph after every event,
+/// including `event`, has been removed from it. The windows are scored with the energy left for the task
+/// by the other tasks, which is `graph` with the task's own effect added back.
+pub fn explain_event(
+    input_graph: &DiscreteGraph,
+    graph: &DiscreteGraph,
+    task: &TaskForScheduler,
+    event: &UnpublishedEvent,
+    carbon_intensity: Option<&DiscreteGraph>,
+) -> Result<EventExplanation> {
+    let (timeslot_start, timeslot_end, timeslot_duration) = get_task_as_timeslots(task, graph)?;
+    let windows = timeslot_end - timeslot_start + 1 - timeslot_duration;
+    let chosen = graph
+        .index_at(event.start_time)
+        .filter(|timeslot| graph.time_at(*timeslot) == event.start_time)
+        .and_then(|timeslot| timeslot.checked_sub(timeslot_start))
+        .filter(|window| *window < windows)
+        .ok_or_else(|| {
+            anyhow!(
+                "The event for task with id: {} does not start in a window of its timespan",
+                task.id
+            )
+        })?;
+
+    let values = graph.get_values();
+    let mut available = values[timeslot_start..timeslot_end].to_vec();
+    for value in &mut available[chosen..chosen + timeslot_duration] {
+        *value += task.effect;
+    }
+    if available.iter().any(|value| !value.is_finite()) {
+        bail!(
+            "The graph is not finite within the timespan of task with id: {}",
+            task.id
+        );
+    }
+
+    let scores = make_p_from_duration_in_timeslots(timeslot_duration, &available);
+    let excluded = excluded_windows(task, graph, timeslot_start, timeslot_duration, windows);
+    let window_score = |window: usize| WindowScore {
+        start_time: graph.time_at(timeslot_start + window),
+        score: scores[window],
+        renewable_coverage: window_coverage(
+            &available[window..window + timeslot_duration],
+            task.effect,
+        ),
+    };
+
+    // Windows a timeslot apart are nearly the same,
+    // so only the best windows that share no timeslot with the chosen one or each other are listed
+    let mut alternatives: Vec<usize> = Vec::new();
+    for window in (0..windows)
+        .filter(|window| !excluded[*window])
+        .sorted_by(|x, y| scores[*y].total_cmp(&scores[*x]).then(x.cmp(y)))
+    {
+        if alternatives.len() == MAX_EXPLANATION_ALTERNATIVES {
+            break;
+        }
+        if std::iter::once(chosen)
+            .chain(alternatives.iter().copied())
+            .all(|other| other.abs_diff(window) >= timeslot_duration)
+        {
+            alternatives.push(window);
+        }
+    }
+
+    let chosen_score = window_score(chosen);
+    let mut binding_constraints = Vec::new();
+    // Moving a timeslot out of the timespan would swap the first or last timeslot of the window
+    // for one with more energy
+    if chosen == 0
+        && timeslot_start > 0
+        && values[timeslot_start - 1] > available[timeslot_duration - 1]
+    {
+        binding_constraints.push(BindingConstraint::TimespanStart);
+    }
+    if chosen + 1 == windows
+        && timeslot_end < values.len()
+        && values[timeslot_end] > available[chosen]
+    {
+        binding_constraints.push(BindingConstraint::TimespanEnd);
+    }
+    if chosen_score.renewable_coverage < 1.0 {
+        binding_constraints.push(BindingConstraint::Capacity);
+    }
+    let preferred = find_best_event(task, input_graph, carbon_intensity)?;
+    if preferred != timeslot_start + chosen {
+        binding_constraints.push(BindingConstraint::OtherTasks);
+    }
+    if carbon_intensity.is_some() && find_best_event(task, input_graph, None)? != preferred {
+        binding_constraints.push(BindingConstraint::CarbonIntensity);
+    }
+
+    Ok(EventExplanation {
+        chosen: chosen_score,
+        alternatives: alternatives.into_iter().map(window_score).collect(),
+        binding_constraints,
+    })
+}
+
+/// The cost of the energy left in the graph after scheduling, lower is better.
+///
+/// Leftover energy is squared, while missing energy is cubed, as buying energy is worse than not using it.
+pub fn graph_cost(graph: &DiscreteGraph) -> f64 {
+    graph
+        .get_values()
+        .iter()
+        .map(|val| timeslot_cost(*val))
+        .sum()
+}
+
+/// The part of [graph_cost] for a timeslot with `val` energy left.
+fn timeslot_cost(val: f64) -> f64 {
+    if val < 0.0 {
+        val.powi(3).abs()
+    } else {
+        val.powi(2)
+    }
+}
+
+/// The derivative of [timeslot_cost].
+fn timeslot_cost_slope(val: f64) -> f64 {
+    if val < 0.0 {
+        -3.0 * val.powi(2)
+    } else {
+        2.0 * val
+    }
+}
+
+/// A lower bound of [graph_cost] after removing `energy` from `values`,
+/// when every value ends up between `lowest` and `highest`.
+///
+/// The energy is treated as a fluid that may be spread over the timeslots in any way.
+/// As the cost is convex, it is cheapest to level the values at some `level`,
+/// and the Lagrangian dual at that level bounds the cost even if the level is not found exactly.
+fn fluid_cost_bound(values: &[f64], lowest: &[f64], highest: &[f64], energy: f64) -> f64 {
+    let removed_at = |level: f64| -> f64 {
+        values
+            .iter()
+            .zip(lowest.iter().zip(highest))
+            .map(|(value, (lowest, highest))| value - level.clamp(*lowest, *highest))
+            .sum()
+    };
+
+    if values.is_empty() {
+        return 0.0;
+    }
+    if lowest.iter().chain(highest).any(|value| !value.is_finite()) {
+        // The values of the finished schedule are not finite either
+        return f64::INFINITY;
+    }
+
+    let mut low = lowest.iter().copied().fold(f64::INFINITY, f64::min);
+    let mut high = highest.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    // Less energy is removed the higher the level is
+    for _ in 0..FLUID_BOUND_ITERATIONS {
+        let middle = (low + high) / 2.0;
+        if removed_at(middle) > energy {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    let slope = timeslot_cost_slope(high);
+    let cost: f64 = lowest
+        .iter()
+        .zip(highest)
+        .map(|(lowest, highest)| timeslot_cost(high.clamp(*lowest, *highest)))
+        .sum();
+    let bound = cost + slope * (removed_at(high) - energy);
+    // Less a margin for rounding errors, so a schedule is never pruned by a bound above its cost
+    bound - bound.abs() * FLUID_BOUND_MARGIN
+}
+
+/// Removes the energy used by a task that starts at a fixed time from the [DiscreteGraph].values
+///
+/// Only the part of the task that overlaps the graph is removed.
+pub fn remove_fixed_event_from_graph(
+    graph: &mut DiscreteGraph,
+    task: &TaskForScheduler,
+    start_time: DateTimeUtc,
+) -> Result<()> {
+    let graph_timespan = graph.get_timespan();
+    let start = start_time.max(graph_timespan.start);
+    let end = (start_time + Duration::from(task.duration)).min(graph_timespan.end);
+    if start < end {
+        graph.checked_sub(&Timespan::new(start, end), task.effect)?;
+    }
+
+    Ok(())
+}
+
 /// # Example
 /// ```ignore
 /// let timeslots = 2;
@@ -164,57 +982,47 @@ fn make_unpublished_event_and_remove_from_graph(
     timeslot: usize,
     duration_in_timeslots: usize,
 ) -> Result<UnpublishedEvent> {
-    graph.sub_values(timeslot, task.effect, duration_in_timeslots);
+    let start_time = graph.time_at(timeslot);
+    let end_time = graph.time_at(timeslot + duration_in_timeslots);
+    graph.checked_sub(&Timespan::new(start_time, end_time), task.effect)?;
 
     Ok(UnpublishedEvent {
         task_id: task.id,
-        start_time: graph.get_start_time() + graph.get_time_delta() * timeslot.try_into()?,
+        start_time,
+        explanation: None,
     })
 }
 
+/// The first timeslot the task can start in, the timeslot after the last one it can run in,
+/// and its duration in timeslots.
 fn get_task_as_timeslots(
     task: &TaskForScheduler,
     graph: &DiscreteGraph,
 ) -> Result<(usize, usize, usize)> {
-    // time_delta represents delta t
-    let time_delta: usize = graph.get_time_delta().num_milliseconds().try_into()?;
-    // Defining ranges for the task's timespan
-    let start_time = min(task.timespan.start, graph.get_start_time());
-    let start_offset: usize = (task.timespan.start - start_time)
-        .num_milliseconds()
-        .try_into()?;
-
-    let end_time = min(task.timespan.end, graph.get_end_time());
-    let end_offset: usize = (end_time - graph.get_start_time())
-        .num_milliseconds()
-        .try_into()?;
-
-    // The timeslots of when the task can start and end
-    let timeslot_start: usize = start_offset.div_ceil(time_delta);
-    let timeslot_end: usize = end_offset / time_delta;
-
-    if timeslot_start >= timeslot_end {
+    // The timeslots that lie entirely within the task's timespan
+    let slots = graph.slots_within(&task.timespan);
+    if slots.is_empty() {
         bail!(
             "Invalid timespan timeslot_start: {} timeslot_end: {} for task with id: {:?}",
-            timeslot_start,
-            timeslot_end,
+            slots.start,
+            slots.end,
             task
         );
     }
 
     // timeslots represent d'
     let timeslots = duration_as_timeslots(task, graph)?;
-    if timeslot_end - timeslot_start < timeslots {
+    if slots.len() < timeslots {
         bail!(
             "Unschedulable task provided, because the duration is larger than the task after truncating, task: {:?} timespan start in timeslots: {}, timespan end in timeslots: {}, duration in timeslots: {}",
             task,
-            timeslot_start,
-            timeslot_end,
+            slots.start,
+            slots.end,
             timeslots
         );
     }
 
-    Ok((timeslot_start, timeslot_end, timeslots))
+    Ok((slots.start, slots.end, timeslots))
 }
 
 fn duration_as_timeslots(task: &TaskForScheduler, graph: &DiscreteGraph) -> Result<usize> {
@@ -225,16 +1033,29 @@ fn duration_as_timeslots(task: &TaskForScheduler, graph: &DiscreteGraph) -> Resu
 
 #[cfg(test)]
 mod tests {
-    use super::SchedulerAlgorithm;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Instant,
+    };
+
+    use super::{
+        graph_cost, remove_fixed_event_from_graph, schedule_global, RunControl, SchedulerAlgorithm,
+        SchedulingProgress,
+    };
+    use crate::scheduling::carbon::carbon_cost;
     use crate::scheduling::scheduler::{
-        AllPermutationsAlgorithm, GlobalSchedulerAlgorithm, NaiveSchedulerAlgorithm,
+        explain_event, AllPermutationsAlgorithm, GlobalSchedulerAlgorithm, NaiveSchedulerAlgorithm,
     };
     use crate::scheduling::task_for_scheduler::TaskForScheduler as Task;
     use crate::scheduling::unpublished_event::UnpublishedEvent;
     use chrono::{DateTime, Duration, Utc};
+    use itertools::Itertools;
+    use protocol::events::BindingConstraint;
     use protocol::graph::DiscreteGraph;
     use protocol::tasks::TaskId;
     use protocol::time::{Milliseconds, Timespan};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use tokio_util::sync::CancellationToken;
 
     struct TaskFactory {
         task_id: TaskId,
@@ -267,6 +1088,7 @@ mod tests {
                     },
                     duration,
                     effect: effect.unwrap_or_default(),
+                    exclusions: Vec::new(),
                 });
             }
             res
@@ -283,6 +1105,7 @@ mod tests {
                     vec.push(UnpublishedEvent {
                         task_id: __id.into(),
                         start_time: $start_time + Duration::seconds($offset),
+                        explanation: None,
                     });
                     __id += 1;
                 )*
@@ -301,6 +1124,7 @@ mod tests {
                     vec.push(UnpublishedEvent {
                         task_id: __id.into(),
                         start_time: $start_time + Duration::hours($offset),
+                        explanation: None,
                     });
                     __id += 1;
                 )*
@@ -311,7 +1135,7 @@ mod tests {
 
     #[test]
     fn all_permutations_scheduler_simple_reordered() {
-        let scheduler = AllPermutationsAlgorithm;
+        let scheduler = AllPermutationsAlgorithm::new();
         let start = Utc::now();
 
         let tasks = vec![
@@ -319,33 +1143,72 @@ mod tests {
                 id: 0.into(),
                 timespan: Timespan {
                     start,
-                    end: start + Duration::seconds(2),
+                    end: start + Duration::seconds(3),
                 },
                 duration: Duration::seconds(2).into(),
                 effect: 3.0,
+                exclusions: Vec::new(),
             },
             Task {
                 id: 1.into(),
                 timespan: Timespan {
                     start,
-                    end: start + Duration::seconds(2),
+                    end: start + Duration::seconds(3),
                 },
                 duration: Duration::seconds(1).into(),
                 effect: 4.0,
+                exclusions: Vec::new(),
             },
         ];
 
         let mut graph = DiscreteGraph::new(vec![4.0, 3.0, 3.0], Duration::seconds(1), start);
 
         let mut events = scheduler.schedule(&mut graph, tasks).unwrap();
+        // Only running the shorter task first fits both within the available energy
         let expected = make_expected_unpublished_events!(start, 1, 0);
 
         events.sort_by_key(|event| event.task_id);
         assert_eq!(events, expected)
     }
     #[test]
+    fn all_permutations_scheduler_falls_back_to_global() {
+        let scheduler = AllPermutationsAlgorithm::with_max_tasks(1);
+        let start = Utc::now();
+
+        let tasks = vec![
+            Task {
+                id: 0.into(),
+                timespan: Timespan {
+                    start,
+                    end: start + Duration::seconds(3),
+                },
+                duration: Duration::seconds(2).into(),
+                effect: 3.0,
+                exclusions: Vec::new(),
+            },
+            Task {
+                id: 1.into(),
+                timespan: Timespan {
+                    start,
+                    end: start + Duration::seconds(3),
+                },
+                duration: Duration::seconds(1).into(),
+                effect: 4.0,
+                exclusions: Vec::new(),
+            },
+        ];
+
+        let mut graph = DiscreteGraph::new(vec![4.0, 3.0, 3.0], Duration::seconds(1), start);
+
+        let events = scheduler.schedule(&mut graph, tasks).unwrap();
+        // The same result as the global scheduler, see global_scheduler_simple_reorder
+        let expected = make_expected_unpublished_events!(start, 0, 2);
+
+        assert_eq!(events, expected)
+    }
+    #[test]
     fn all_permutations_scheduler_simple() {
-        let scheduler = AllPermutationsAlgorithm;
+        let scheduler = AllPermutationsAlgorithm::new();
         let start = Utc::now();
 
         let tasks = vec![
@@ -357,6 +1220,7 @@ mod tests {
                 },
                 duration: Duration::seconds(1).into(),
                 effect: 4.0,
+                exclusions: Vec::new(),
             },
             Task {
                 id: 1.into(),
@@ -366,6 +1230,7 @@ mod tests {
                 },
                 duration: Duration::seconds(2).into(),
                 effect: 3.0,
+                exclusions: Vec::new(),
             },
         ];
 
@@ -376,6 +1241,262 @@ mod tests {
 
         assert_eq!(events, expected)
     }
+    /// Tasks of 1 to 4 hours that can run within a day, and a day of hourly values around 0.
+    fn random_day(seed: u64, amount: usize, start: DateTime<Utc>) -> (DiscreteGraph, Vec<Task>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let tasks = (0..amount as i64)
+            .map(|id| {
+                let duration = rng.gen_range(1..=4);
+                let timespan_start = rng.gen_range(0..=24 - duration);
+                let timespan_end = rng.gen_range(timespan_start + duration..=24);
+                Task {
+                    id: id.into(),
+                    timespan: Timespan {
+                        start: start + Duration::hours(timespan_start),
+                        end: start + Duration::hours(timespan_end),
+                    },
+                    duration: Duration::hours(duration).into(),
+                    effect: rng.gen_range(100.0..1000.0),
+                    exclusions: Vec::new(),
+                }
+            })
+            .collect();
+        let values = (0..24).map(|_| rng.gen_range(-500.0..2000.0)).collect();
+
+        (DiscreteGraph::new(values, Duration::hours(1), start), tasks)
+    }
+
+    /// Schedules every permutation of the tasks in turn and keeps the best one.
+    fn schedule_every_permutation(
+        graph: &mut DiscreteGraph,
+        tasks: Vec<Task>,
+        carbon_intensity: &DiscreteGraph,
+    ) -> Vec<UnpublishedEvent> {
+        let len = tasks.len();
+        let (best_graph, best_events) = tasks
+            .into_iter()
+            .permutations(len)
+            .map(|permutation| {
+                let mut graph = graph.clone();
+                let schedule = schedule_global(
+                    &mut graph,
+                    &permutation,
+                    Some(carbon_intensity),
+                    &RunControl::default(),
+                );
+                (graph, schedule.unwrap().events)
+            })
+            .min_by(|(graph1, _), (graph2, _)| {
+                carbon_cost(graph1, carbon_intensity)
+                    .total_cmp(&carbon_cost(graph2, carbon_intensity))
+                    .then(graph_cost(graph1).total_cmp(&graph_cost(graph2)))
+            })
+            .unwrap();
+
+        *graph = best_graph;
+        best_events
+    }
+
+    #[test]
+    fn all_permutations_scheduler_finds_the_best_permutation() {
+        let start = Utc::now();
+        for seed in 0..5 {
+            let (graph, tasks) = random_day(seed, 6, start);
+            let carbon_intensity = DiscreteGraph::new(
+                (0..24)
+                    .map(|hour| 100.0 + (hour % 7) as f64 * 50.0)
+                    .collect(),
+                Duration::hours(1),
+                start,
+            );
+
+            let mut expected_graph = graph.clone();
+            let mut expected =
+                schedule_every_permutation(&mut expected_graph, tasks.clone(), &carbon_intensity);
+
+            let mut actual_graph = graph.clone();
+            let mut actual = AllPermutationsAlgorithm::new()
+                .schedule_with_carbon_intensity(&mut actual_graph, tasks, &carbon_intensity)
+                .unwrap();
+
+            actual.sort_by_key(|event| event.task_id);
+            expected.sort_by_key(|event| event.task_id);
+            assert_eq!(actual, expected, "seed {seed}");
+            assert_eq!(actual_graph.get_values(), expected_graph.get_values());
+        }
+    }
+    #[test]
+    fn all_permutations_scheduler_handles_nan() {
+        let start = Utc::now();
+        let tasks = TaskFactory::new().make_tasks(
+            3,
+            start,
+            Duration::seconds(1).into(),
+            Duration::seconds(3),
+            None,
+            Some(1.0),
+        );
+        let mut graph = DiscreteGraph::new(vec![4.0, f64::NAN, 3.0], Duration::seconds(1), start);
+
+        let events = AllPermutationsAlgorithm::new()
+            .schedule(&mut graph, tasks.clone())
+            .unwrap();
+        assert_eq!(events.len(), 3);
+
+        let mut graph = DiscreteGraph::new(vec![4.0, -3.0, 3.0], Duration::seconds(1), start);
+        let carbon_intensity =
+            DiscreteGraph::new(vec![100.0, f64::NAN, 100.0], Duration::seconds(1), start);
+        let events = AllPermutationsAlgorithm::new()
+            .schedule_with_carbon_intensity(&mut graph, tasks, &carbon_intensity)
+            .unwrap();
+        assert_eq!(events.len(), 3);
+    }
+    #[test]
+    fn all_permutations_scheduler_schedules_twelve_tasks() {
+        let start = Utc::now();
+        let (graph, tasks) = random_day(0, 12, start);
+
+        let mut global_graph = graph.clone();
+        GlobalSchedulerAlgorithm
+            .schedule(&mut global_graph, tasks.clone())
+            .unwrap();
+
+        let mut all_permutations_graph = graph.clone();
+        let events = AllPermutationsAlgorithm::new()
+            .schedule(&mut all_permutations_graph, tasks)
+            .unwrap();
+
+        assert_eq!(events.len(), 12);
+        // The order the global scheduler uses is one of the permutations
+        assert!(graph_cost(&all_permutations_graph) <= graph_cost(&global_graph));
+    }
+    #[test]
+    fn schedulers_stop_when_cancelled() {
+        let start = Utc::now();
+        let schedulers: Vec<Box<dyn SchedulerAlgorithm>> = vec![
+            Box::new(NaiveSchedulerAlgorithm),
+            Box::new(GlobalSchedulerAlgorithm),
+            Box::new(AllPermutationsAlgorithm::new()),
+        ];
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        let control = RunControl::new(cancellation);
+
+        for scheduler in schedulers {
+            let (input_graph, tasks) = random_day(0, 4, start);
+            let mut graph = input_graph.clone();
+
+            let schedule = scheduler
+                .schedule_cancellable(&mut graph, tasks, None, &control)
+                .unwrap();
+
+            assert!(schedule.cancelled);
+            assert!(schedule.events.is_empty());
+            assert_eq!(graph.get_values(), input_graph.get_values());
+        }
+    }
+    #[test]
+    fn runs_are_cancelled_at_the_deadline() {
+        let start = Utc::now();
+        let (mut graph, tasks) = random_day(0, 4, start);
+
+        let control = RunControl::default().with_deadline(Instant::now());
+        let schedule = AllPermutationsAlgorithm::new()
+            .schedule_cancellable(&mut graph.clone(), tasks.clone(), None, &control)
+            .unwrap();
+        assert!(schedule.cancelled);
+
+        let control = RunControl::default()
+            .with_deadline(Instant::now() + std::time::Duration::from_secs(60 * 60));
+        let schedule = AllPermutationsAlgorithm::new()
+            .schedule_cancellable(&mut graph, tasks.clone(), None, &control)
+            .unwrap();
+        assert!(!schedule.cancelled);
+        assert_eq!(schedule.events.len(), tasks.len());
+    }
+    #[test]
+    fn global_scheduler_returns_the_scheduled_tasks_when_cancelled() {
+        let start = Utc::now();
+        let (mut graph, tasks) = random_day(1, 4, start);
+        let cancellation = CancellationToken::new();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let control = RunControl::new(cancellation.clone()).with_progress({
+            let reports = reports.clone();
+            move |progress| {
+                reports.lock().unwrap().push(progress);
+                cancellation.cancel();
+            }
+        });
+
+        let schedule = GlobalSchedulerAlgorithm
+            .schedule_cancellable(&mut graph, tasks.clone(), None, &control)
+            .unwrap();
+
+        assert!(schedule.cancelled);
+        assert_eq!(schedule.events.len(), 1);
+        assert_eq!(schedule.events[0].task_id, tasks[0].id);
+        assert_eq!(
+            *reports.lock().unwrap(),
+            vec![SchedulingProgress {
+                steps: 1,
+                best_cost: None
+            }]
+        );
+    }
+    #[test]
+    fn all_permutations_scheduler_reports_progress() {
+        let start = Utc::now();
+        let (input_graph, tasks) = random_day(2, 6, start);
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let control = RunControl::default().with_progress({
+            let reports = reports.clone();
+            move |progress| reports.lock().unwrap().push(progress)
+        });
+
+        let mut graph = input_graph.clone();
+        let schedule = AllPermutationsAlgorithm::new()
+            .schedule_cancellable(&mut graph, tasks, None, &control)
+            .unwrap();
+
+        assert!(!schedule.cancelled);
+        // Every better schedule is reported, so the lowest cost reported is the cost of the result
+        let best_cost = reports
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|progress| progress.best_cost)
+            .min_by(f64::total_cmp);
+        assert_eq!(best_cost, Some(graph_cost(&graph)));
+    }
+    #[test]
+    fn all_permutations_scheduler_returns_the_best_so_far_when_cancelled() {
+        let start = Utc::now();
+        let (input_graph, tasks) = random_day(3, 8, start);
+        let cancellation = CancellationToken::new();
+        // Cancelled as soon as the first complete schedule has been found
+        let control = RunControl::new(cancellation.clone()).with_progress(move |_| {
+            cancellation.cancel();
+        });
+
+        let mut graph = input_graph.clone();
+        let schedule = AllPermutationsAlgorithm::new()
+            .schedule_cancellable(&mut graph, tasks.clone(), None, &control)
+            .unwrap();
+
+        assert!(schedule.cancelled);
+        assert_eq!(schedule.events.len(), tasks.len());
+        let mut expected_graph = input_graph.clone();
+        for event in &schedule.events {
+            let task = tasks.iter().find(|task| task.id == event.task_id).unwrap();
+            assert!(event.start_time >= task.timespan.start);
+            assert!(event.start_time + Duration::from(task.duration) <= task.timespan.end);
+            remove_fixed_event_from_graph(&mut expected_graph, task, event.start_time).unwrap();
+        }
+        // The graph is left as the returned schedule leaves it
+        for (actual, expected) in graph.get_values().iter().zip(expected_graph.get_values()) {
+            assert!((actual - expected).abs() < 1e-9);
+        }
+    }
     #[test]
     fn global_scheduler_simple_reorder() {
         let scheduler = GlobalSchedulerAlgorithm;
@@ -389,6 +1510,7 @@ mod tests {
                 },
                 duration: Duration::seconds(2).into(),
                 effect: 3.0,
+                exclusions: Vec::new(),
             },
             Task {
                 id: 1.into(),
@@ -398,6 +1520,7 @@ mod tests {
                 },
                 duration: Duration::seconds(1).into(),
                 effect: 4.0,
+                exclusions: Vec::new(),
             },
         ];
 
@@ -418,19 +1541,21 @@ mod tests {
                 id: 0.into(),
                 timespan: Timespan {
                     start,
-                    end: start + Duration::seconds(2),
+                    end: start + Duration::seconds(3),
                 },
                 duration: Duration::seconds(1).into(),
                 effect: 4.0,
+                exclusions: Vec::new(),
             },
             Task {
                 id: 1.into(),
                 timespan: Timespan {
                     start,
-                    end: start + Duration::seconds(2),
+                    end: start + Duration::seconds(3),
                 },
                 duration: Duration::seconds(2).into(),
                 effect: 3.0,
+                exclusions: Vec::new(),
             },
         ];
 
@@ -442,6 +1567,32 @@ mod tests {
         assert_eq!(events, expected)
     }
     #[test]
+    fn tasks_never_run_past_their_timespan() {
+        let start = Utc::now();
+        let schedulers: [Box<dyn SchedulerAlgorithm>; 3] = [
+            Box::new(NaiveSchedulerAlgorithm),
+            Box::new(GlobalSchedulerAlgorithm),
+            Box::new(AllPermutationsAlgorithm::new()),
+        ];
+
+        for scheduler in schedulers {
+            let tasks = TaskFactory::new().make_tasks(
+                1,
+                start,
+                Duration::seconds(1).into(),
+                Duration::seconds(2),
+                None,
+                Some(1.0),
+            );
+            // The most energy is in the timeslot right after the timespan ends
+            let mut graph = DiscreteGraph::new(vec![1.0, 2.0, 9.0], Duration::seconds(1), start);
+
+            let events = scheduler.schedule(&mut graph, tasks).unwrap();
+
+            assert_eq!(events, make_expected_unpublished_events!(start, 1));
+        }
+    }
+    #[test]
     fn global_scheduler_mutiple_tasks() {
         let scheduler = GlobalSchedulerAlgorithm;
         let start = Utc::now();
@@ -736,6 +1887,264 @@ mod tests {
 
         assert_eq!(events, expected)
     }
+    #[test]
+    fn naive_scheduler_exclusion() {
+        let scheduler = NaiveSchedulerAlgorithm;
+        let start = Utc::now();
+
+        let mut tasks = TaskFactory::new().make_tasks(
+            1,
+            start,
+            Duration::seconds(3).into(),
+            Duration::seconds(7),
+            None,
+            None,
+        );
+        tasks[0].exclusions = vec![Timespan {
+            start: start + Duration::seconds(2),
+            end: start + Duration::seconds(3),
+        }];
+
+        let mut graph = DiscreteGraph::new(
+            vec![0.0, 5.0, 8.0, 9.0, 8.0, 5.0, 0.0],
+            Duration::seconds(1),
+            start,
+        );
+
+        let events = scheduler.schedule(&mut graph, tasks).unwrap();
+        let expected = make_expected_unpublished_events!(start, 3);
+
+        assert_eq!(events, expected)
+    }
+    #[test]
+    fn global_scheduler_fully_excluded() {
+        let scheduler = GlobalSchedulerAlgorithm;
+        let start = Utc::now();
+
+        let mut tasks = TaskFactory::new().make_tasks(
+            1,
+            start,
+            Duration::seconds(3).into(),
+            Duration::seconds(7),
+            None,
+            None,
+        );
+        tasks[0].exclusions = vec![Timespan {
+            start: start + Duration::seconds(2),
+            end: start + Duration::seconds(5),
+        }];
+
+        let mut graph = DiscreteGraph::new(
+            vec![0.0, 5.0, 8.0, 9.0, 8.0, 5.0, 0.0],
+            Duration::seconds(1),
+            start,
+        );
+
+        assert!(scheduler.schedule(&mut graph, tasks).is_err());
+    }
+    #[test]
+    fn schedulers_prefer_low_carbon_imports() {
+        let start = Utc::now();
+        let schedulers: Vec<Box<dyn SchedulerAlgorithm>> = vec![
+            Box::new(NaiveSchedulerAlgorithm),
+            Box::new(GlobalSchedulerAlgorithm),
+            Box::new(AllPermutationsAlgorithm::new()),
+        ];
+
+        for scheduler in schedulers {
+            let tasks = TaskFactory::new().make_tasks(
+                1,
+                start,
+                Duration::hours(1).into(),
+                Duration::hours(4),
+                None,
+                Some(1000.0),
+            );
+            // Every hour has to be imported, which is cleanest in the second hour
+            let mut graph = DiscreteGraph::new(vec![0.0; 5], Duration::hours(1), start);
+            let carbon_intensity = DiscreteGraph::new(
+                vec![300.0, 100.0, 200.0, 400.0, 400.0],
+                Duration::hours(1),
+                start,
+            );
+
+            let events = scheduler
+                .schedule_with_carbon_intensity(&mut graph, tasks, &carbon_intensity)
+                .unwrap();
+
+            assert_eq!(events, make_expected_unpublished_events_hours!(start, 1));
+        }
+    }
+    #[test]
+    fn carbon_intensity_does_not_move_tasks_out_of_the_surplus() {
+        let scheduler = GlobalSchedulerAlgorithm;
+        let start = Utc::now();
+
+        let tasks = TaskFactory::new().make_tasks(
+            1,
+            start,
+            Duration::hours(1).into(),
+            Duration::hours(4),
+            None,
+            Some(1000.0),
+        );
+        let mut graph = DiscreteGraph::new(
+            vec![0.0, 500.0, 1000.0, 0.0, 0.0],
+            Duration::hours(1),
+            start,
+        );
+        // The grid is cleanest when there is no surplus
+        let carbon_intensity = DiscreteGraph::new(
+            vec![10.0, 500.0, 500.0, 10.0, 10.0],
+            Duration::hours(1),
+            start,
+        );
+
+        let events = scheduler
+            .schedule_with_carbon_intensity(&mut graph, tasks, &carbon_intensity)
+            .unwrap();
+
+        assert_eq!(events, make_expected_unpublished_events_hours!(start, 2));
+    }
+    #[test]
+    fn explanation_lists_separate_alternatives() {
+        let start = Utc::now();
+        let tasks = TaskFactory::new().make_tasks(
+            1,
+            start,
+            Duration::hours(2).into(),
+            Duration::hours(6),
+            None,
+            Some(10.0),
+        );
+        let input_graph = DiscreteGraph::new(
+            vec![0.0, 10.0, 10.0, 0.0, 5.0, 5.0],
+            Duration::hours(1),
+            start,
+        );
+        let mut graph = input_graph.clone();
+
+        let events = GlobalSchedulerAlgorithm
+            .schedule(&mut graph, tasks.clone())
+            .unwrap();
+        let explanation = explain_event(&input_graph, &graph, &tasks[0], &events[0], None).unwrap();
+
+        assert_eq!(explanation.chosen.start_time, start + Duration::hours(1));
+        assert_eq!(explanation.chosen.score, 20.0);
+        assert_eq!(explanation.chosen.renewable_coverage, 1.0);
+        // The windows overlapping the chosen one or a better alternative are left out
+        assert_eq!(explanation.alternatives.len(), 1);
+        assert_eq!(
+            explanation.alternatives[0].start_time,
+            start + Duration::hours(4)
+        );
+        assert_eq!(explanation.alternatives[0].score, 10.0);
+        assert_eq!(explanation.alternatives[0].renewable_coverage, 0.5);
+        assert!(explanation.binding_constraints.is_empty());
+    }
+    #[test]
+    fn explanation_names_the_binding_constraints() {
+        let start = Utc::now();
+        let mut factory = TaskFactory::new();
+        let mut tasks = factory.make_tasks(
+            2,
+            start,
+            Duration::hours(1).into(),
+            Duration::hours(4),
+            None,
+            Some(10.0),
+        );
+        tasks.extend(factory.make_tasks(
+            1,
+            start,
+            Duration::hours(1).into(),
+            Duration::hours(2),
+            None,
+            Some(10.0),
+        ));
+        let input_graph = DiscreteGraph::new(vec![0.0, 5.0, 20.0, 10.0], Duration::hours(1), start);
+        let mut graph = input_graph.clone();
+
+        let events = GlobalSchedulerAlgorithm
+            .schedule(&mut graph, tasks.clone())
+            .unwrap();
+        let explain = |index: usize| {
+            explain_event(&input_graph, &graph, &tasks[index], &events[index], None).unwrap()
+        };
+
+        // The first task gets the best window
+        assert!(explain(0).binding_constraints.is_empty());
+        // The second task would have started in the third hour without the first task
+        assert_eq!(events[1].start_time, start + Duration::hours(3));
+        assert_eq!(
+            explain(1).binding_constraints,
+            vec![BindingConstraint::OtherTasks]
+        );
+        assert_eq!(explain(1).alternatives.len(), 3);
+        // The third task must end before the third hour, and only half of it is covered
+        assert_eq!(events[2].start_time, start + Duration::hours(1));
+        assert_eq!(
+            explain(2).binding_constraints,
+            vec![BindingConstraint::TimespanEnd, BindingConstraint::Capacity]
+        );
+    }
+    #[test]
+    fn explanation_names_the_carbon_intensity() {
+        let start = Utc::now();
+        let tasks = TaskFactory::new().make_tasks(
+            1,
+            start,
+            Duration::hours(1).into(),
+            Duration::hours(2),
+            None,
+            Some(10.0),
+        );
+        let input_graph = DiscreteGraph::new(vec![0.0, 5.0], Duration::hours(1), start);
+        let carbon_intensity = DiscreteGraph::new(vec![100.0, 400.0], Duration::hours(1), start);
+        let mut graph = input_graph.clone();
+
+        let events = GlobalSchedulerAlgorithm
+            .schedule_with_carbon_intensity(&mut graph, tasks.clone(), &carbon_intensity)
+            .unwrap();
+        let explanation = explain_event(
+            &input_graph,
+            &graph,
+            &tasks[0],
+            &events[0],
+            Some(&carbon_intensity),
+        )
+        .unwrap();
+
+        assert_eq!(explanation.chosen.start_time, start);
+        assert_eq!(explanation.chosen.renewable_coverage, 0.0);
+        assert_eq!(
+            explanation.binding_constraints,
+            vec![
+                BindingConstraint::Capacity,
+                BindingConstraint::CarbonIntensity
+            ]
+        );
+    }
+    #[test]
+    fn remove_fixed_event() {
+        let start = Utc::now();
+
+        let tasks = TaskFactory::new().make_tasks(
+            1,
+            start - Duration::seconds(2),
+            Duration::seconds(3).into(),
+            Duration::seconds(7),
+            None,
+            Some(2.0),
+        );
+
+        let mut graph = DiscreteGraph::new(vec![5.0, 5.0, 5.0, 5.0], Duration::seconds(1), start);
+
+        // Started a second before the graph, so only two seconds remain
+        remove_fixed_event_from_graph(&mut graph, &tasks[0], start - Duration::seconds(1)).unwrap();
+
+        assert_eq!(graph.get_values(), &vec![3.0, 3.0, 5.0, 5.0]);
+    }
 }
 
 #[cfg(test)]
@@ -752,7 +2161,7 @@ mod dependancy_tests {
         let (timeslot_start, timeslot_end, timeslot_duration) = get_task_as_timeslots(task, graph)?;
 
         // The set of values I for task T
-        let task_interval = &graph.get_values()[timeslot_start..=timeslot_end];
+        let task_interval = &graph.get_values()[timeslot_start..timeslot_end];
 
         // The set P(d') created using I
         let mapped_graph = make_p_from_duration_in_timeslots(timeslot_duration, task_interval);
@@ -780,6 +2189,7 @@ mod dependancy_tests {
             },
             duration: Duration::minutes(7).into(),
             effect: 912.8998498308304,
+            exclusions: Vec::new(),
         };
         let graph = DiscreteGraph::new(
             vec![
@@ -867,6 +2277,7 @@ mod dependancy_tests {
             },
             duration: Duration::minutes(7).into(),
             effect: 912.8998498308304,
+            exclusions: Vec::new(),
         };
         let graph = DiscreteGraph::new(
             vec![
@@ -937,7 +2348,7 @@ mod dependancy_tests {
 
         let (timeslot_start, timeslot_end, _) = get_task_as_timeslots(&task, &graph).unwrap();
 
-        let actual = &graph.get_values()[timeslot_start..=timeslot_end];
+        let actual = &graph.get_values()[timeslot_start..timeslot_end];
 
         let expected = vec![
             433056.43667152204,
@@ -953,7 +2364,6 @@ mod dependancy_tests {
             1093766.9536934677,
             697539.8855571696,
             983674.677622651,
-            1102372.4221992705,
         ];
 
         assert_eq!(actual, expected);
@@ -970,6 +2380,7 @@ mod dependancy_tests {
             },
             duration: Duration::minutes(7).into(),
             effect: 912.8998498308304,
+            exclusions: Vec::new(),
         };
         let graph = DiscreteGraph::new(
             vec![
@@ -1041,7 +2452,7 @@ mod dependancy_tests {
         let (timeslot_start, timeslot_end, timeslot_duration) =
             get_task_as_timeslots(&task, &graph).unwrap();
 
-        let task_interval = &graph.get_values()[timeslot_start..=timeslot_end];
+        let task_interval = &graph.get_values()[timeslot_start..timeslot_end];
 
         let actual = make_p_from_duration_in_timeslots(timeslot_duration, task_interval);
 
@@ -1053,7 +2464,6 @@ mod dependancy_tests {
             7222382.71511947,
             7203267.558024773,
             7034509.6846240545,
-            7376393.408475317,
         ];
 
         assert_eq!(actual, expected);
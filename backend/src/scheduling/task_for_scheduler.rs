@@ -1,4 +1,5 @@
 use protocol::{
+    graph::DiscreteGraph,
     tasks::TaskId,
     time::{Milliseconds, Timespan},
 };
@@ -9,6 +10,8 @@ pub struct TaskForScheduler {
     pub timespan: Timespan,
     pub duration: Milliseconds,
     pub effect: f64,
+    // Timespans in which the task must not run
+    pub exclusions: Vec<Timespan>,
 }
 
 impl TaskForScheduler {
@@ -23,6 +26,13 @@ impl TaskForScheduler {
             timespan,
             duration,
             effect,
+            exclusions: Vec::new(),
         }
     }
+
+    /// Whether the task can run within the timeslots of the graph that lie in its timespan.
+    pub fn fits_within(&self, graph: &DiscreteGraph) -> bool {
+        let slots = graph.slots_within(&self.timespan);
+        slots.len() as i64 * graph.get_time_delta().num_milliseconds() >= i64::from(self.duration)
+    }
 }
@@ -1,7 +1,9 @@
-use protocol::{tasks::TaskId, time::DateTimeUtc};
+use protocol::{events::EventExplanation, tasks::TaskId, time::DateTimeUtc};
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct UnpublishedEvent {
     pub task_id: TaskId,
     pub start_time: DateTimeUtc,
+    /// Why the scheduler chose the start time, `None` for pinned events
+    pub explanation: Option<EventExplanation>,
 }
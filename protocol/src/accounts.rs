@@ -1,3 +1,5 @@
+use std::fmt::{self, Display};
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -16,9 +18,9 @@ impl AuthToken {
     }
 }
 
-impl ToString for AuthToken {
-    fn to_string(&self) -> String {
-        self.0.to_string()
+impl Display for AuthToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
@@ -38,3 +40,10 @@ pub struct RegisterOrLoginRequest {
 pub struct RegisterOrLoginResponse {
     pub auth_token: AuthToken,
 }
+
+/// The account's settings, which are also sent to update them.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct AccountSettings {
+    /// The IANA name of the time zone, e.g. "Europe/Copenhagen"
+    pub time_zone: String,
+}
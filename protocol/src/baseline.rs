@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ErrorDetail;
+
+pub const MINUTES_PER_DAY: i64 = 24 * 60;
+/// The largest yearly consumption a standard load profile can be scaled to.
+pub const MAX_ANNUAL_KWH: f64 = 1_000_000.0;
+
+/// The unscheduled load of a household in W, such as a fridge and lighting,
+/// with a value per `resolution_minutes` from local midnight that repeats every day.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct DailyProfile {
+    pub resolution_minutes: i64,
+    pub values: Vec<f64>,
+}
+
+impl DailyProfile {
+    /// Returns an [ErrorDetail] for every invalid field.
+    pub fn validate(&self) -> Result<(), Vec<ErrorDetail>> {
+        let mut errors = Vec::new();
+
+        if self.resolution_minutes <= 0 || MINUTES_PER_DAY % self.resolution_minutes != 0 {
+            errors.push(ErrorDetail::field(
+                "resolution_minutes",
+                "The resolution must divide a day into whole timeslots",
+            ));
+        } else if self.values.len() as i64 != MINUTES_PER_DAY / self.resolution_minutes {
+            errors.push(ErrorDetail::field(
+                "values",
+                &format!(
+                    "A profile with a resolution of {} minutes must have {} values",
+                    self.resolution_minutes,
+                    MINUTES_PER_DAY / self.resolution_minutes
+                ),
+            ));
+        }
+
+        if self
+            .values
+            .iter()
+            .any(|value| !value.is_finite() || *value < 0.0)
+        {
+            errors.push(ErrorDetail::field(
+                "values",
+                "Every value must be a non-negative number",
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The load at `minute` after local midnight.
+    pub fn value_at(&self, minute: i64) -> f64 {
+        let index = minute.rem_euclid(MINUTES_PER_DAY) / self.resolution_minutes;
+        self.values.get(index as usize).copied().unwrap_or_default()
+    }
+}
+
+/// A typical daily shape of consumption, which is scaled to the yearly consumption of a household.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StandardLoadProfile {
+    /// A household with a low night load and peaks in the morning and evening
+    Household,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum SetBaselineRequest {
+    /// An uploaded profile, e.g. from measurements
+    Profile { profile: DailyProfile },
+    /// A standard load profile scaled to the yearly consumption
+    Standard {
+        load_profile: StandardLoadProfile,
+        annual_kwh: f64,
+    },
+}
+
+impl SetBaselineRequest {
+    /// Returns an [ErrorDetail] for every invalid field.
+    pub fn validate(&self) -> Result<(), Vec<ErrorDetail>> {
+        match self {
+            SetBaselineRequest::Profile { profile } => profile.validate(),
+            SetBaselineRequest::Standard { annual_kwh, .. } => {
+                if annual_kwh.is_finite() && (0.0..=MAX_ANNUAL_KWH).contains(annual_kwh) {
+                    Ok(())
+                } else {
+                    Err(vec![ErrorDetail::field(
+                        "annual_kwh",
+                        &format!(
+                            "The yearly consumption must be between 0 and {MAX_ANNUAL_KWH} kWh"
+                        ),
+                    )])
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetBaselineResponse {
+    /// `None` if the account has no baseline consumption
+    pub profile: Option<DailyProfile>,
+}
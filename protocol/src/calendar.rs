@@ -0,0 +1,75 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::devices::DeviceId;
+
+/// The secret in the url of a calendar feed, which lets calendar apps subscribe without an auth token.
+#[derive(Deserialize, Serialize, sqlx::Type, Debug, Clone, PartialEq, Eq, Hash)]
+#[sqlx(transparent)]
+pub struct CalendarFeedSecret(Uuid);
+
+impl CalendarFeedSecret {
+    pub fn new() -> Self {
+        CalendarFeedSecret(Uuid::new_v4())
+    }
+}
+
+impl Display for CalendarFeedSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Default for CalendarFeedSecret {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A subscribable calendar of the account's events, or of a single device's events.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct CalendarFeed {
+    pub secret: CalendarFeedSecret,
+    pub device_id: Option<DeviceId>,
+}
+
+impl CalendarFeed {
+    /// The path of the feed, relative to the backend's address.
+    pub fn path(&self) -> String {
+        format!("/calendar/feed.ics?secret={}", self.secret)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetCalendarRequest {
+    /// Only include the events of this device
+    pub device_id: Option<DeviceId>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetCalendarFeedRequest {
+    pub secret: CalendarFeedSecret,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateCalendarFeedRequest {
+    /// Only include the events of this device
+    pub device_id: Option<DeviceId>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateCalendarFeedResponse {
+    pub feed: CalendarFeed,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetCalendarFeedsResponse {
+    pub feeds: Vec<CalendarFeed>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct DeleteCalendarFeedRequest {
+    pub secret: CalendarFeedSecret,
+}
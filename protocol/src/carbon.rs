@@ -0,0 +1,71 @@
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::ErrorDetail, graph::DiscreteGraph, time::DateTimeUtc};
+
+/// The carbon intensity of the grid in gCO2/kWh, with a value per timeslot from `start_time`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CarbonIntensitySeries {
+    pub start_time: DateTimeUtc,
+    pub resolution_minutes: i64,
+    pub values: Vec<f64>,
+}
+
+impl CarbonIntensitySeries {
+    /// Returns an [ErrorDetail] for every invalid field.
+    pub fn validate(&self) -> Result<(), Vec<ErrorDetail>> {
+        let mut errors = Vec::new();
+
+        if self.resolution_minutes <= 0 {
+            errors.push(ErrorDetail::field(
+                "resolution_minutes",
+                "The resolution must be positive",
+            ));
+        }
+
+        if self.values.is_empty() {
+            errors.push(ErrorDetail::field("values", "The series must not be empty"));
+        } else if self
+            .values
+            .iter()
+            .any(|value| !value.is_finite() || *value < 0.0)
+        {
+            errors.push(ErrorDetail::field(
+                "values",
+                "Every value must be a non-negative number",
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl From<CarbonIntensitySeries> for DiscreteGraph {
+    fn from(series: CarbonIntensitySeries) -> Self {
+        DiscreteGraph::new(
+            series.values,
+            Duration::minutes(series.resolution_minutes),
+            series.start_time,
+        )
+    }
+}
+
+impl From<DiscreteGraph> for CarbonIntensitySeries {
+    fn from(graph: DiscreteGraph) -> Self {
+        CarbonIntensitySeries {
+            start_time: graph.get_start_time(),
+            resolution_minutes: graph.get_time_delta().num_minutes(),
+            values: graph.get_values().clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetCarbonIntensityResponse {
+    /// `None` if no series has been ingested
+    pub series: Option<CarbonIntensitySeries>,
+}
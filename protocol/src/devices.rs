@@ -2,6 +2,8 @@ use derive_more::{Display, From, Into};
 use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
 
+use crate::errors::ErrorDetail;
+
 #[derive(
     Deserialize, Serialize, Debug, sqlx::Type, PartialEq, Eq, From, Into, Clone, Copy, Display, Hash,
 )]
@@ -19,6 +21,30 @@ pub struct CreateDeviceRequest {
     pub effect: f64,
 }
 
+impl CreateDeviceRequest {
+    /// Returns an [ErrorDetail] for every invalid field.
+    pub fn validate(&self) -> Result<(), Vec<ErrorDetail>> {
+        let mut errors = Vec::new();
+
+        if self.name.trim().is_empty() {
+            errors.push(ErrorDetail::field("name", "The name must not be empty"));
+        }
+
+        if !self.effect.is_finite() || self.effect <= 0.0 {
+            errors.push(ErrorDetail::field(
+                "effect",
+                "The effect must be a positive number",
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct CreateDeviceResponse {
     pub device: Device,
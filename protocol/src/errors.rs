@@ -0,0 +1,72 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+/// Machine-readable error codes returned by the backend in [ErrorResponse].
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    NotFound,
+    Conflict,
+    ValidationFailed,
+    InternalError,
+    /// The request could not be answered in time, and may succeed when retried
+    Unavailable,
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = match self {
+            ErrorCode::BadRequest => "bad_request",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::ValidationFailed => "validation_failed",
+            ErrorCode::InternalError => "internal_error",
+            ErrorCode::Unavailable => "unavailable",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+/// Additional information about an error, optionally tied to a field of the request.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ErrorDetail {
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ErrorDetail {
+    pub fn field(field: &str, message: &str) -> Self {
+        ErrorDetail {
+            field: Some(field.to_string()),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// The JSON body of every non-successful response from the backend.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ErrorResponse {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default)]
+    pub details: Vec<ErrorDetail>,
+}
+
+impl Display for ErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)?;
+        for detail in &self.details {
+            match &detail.field {
+                Some(field) => write!(f, "; {}: {}", field, detail.message)?,
+                None => write!(f, "; {}", detail.message)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ErrorResponse {}
@@ -1,7 +1,10 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::{devices::DeviceId, tasks::TaskId};
+use crate::{devices::DeviceId, tasks::TaskId, time::DateTimeLocal};
+
+/// How many alternative windows an [EventExplanation] lists at most.
+pub const MAX_EXPLANATION_ALTERNATIVES: usize = 3;
 
 #[derive(Deserialize, Serialize, Debug, sqlx::Type, PartialEq, Eq, Clone, Copy)]
 #[sqlx(transparent)]
@@ -28,3 +31,65 @@ pub struct Event {
 pub struct GetDeviceEventRequest {
     pub device_id: DeviceId,
 }
+
+/// The events with their start times in the account's time zone.
+#[derive(Deserialize, Serialize)]
+pub struct GetLocalEventsResponse {
+    pub time_zone: String,
+    pub events: Vec<LocalEvent>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct LocalEvent {
+    pub id: EventId,
+    pub task_id: TaskId,
+    pub start_time: DateTimeLocal,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetEventExplanationRequest {
+    pub event_id: EventId,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetEventExplanationResponse {
+    pub explanation: EventExplanation,
+}
+
+/// Why the scheduler started an event when it did.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct EventExplanation {
+    /// The window the event runs in
+    pub chosen: WindowScore,
+    /// The best other windows the task could have run in, best first
+    pub alternatives: Vec<WindowScore>,
+    /// The constraints that kept the event from a window with more renewable energy
+    pub binding_constraints: Vec<BindingConstraint>,
+}
+
+/// A window the task could run in, scored with the energy left for it by the other tasks.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct WindowScore {
+    pub start_time: DateTime<Utc>,
+    /// The renewable energy in W available to the task, summed over the timeslots of the window,
+    /// which the scheduler maximizes
+    pub score: f64,
+    /// The share, between 0 and 1, of the task's energy that renewable energy is expected to cover
+    pub renewable_coverage: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BindingConstraint {
+    /// The event starts at the start of the task's timespan, and starting earlier would be better
+    TimespanStart,
+    /// The event ends at the end of the task's timespan, and ending later would be better
+    TimespanEnd,
+    /// Not even the chosen window has enough renewable energy to cover the task
+    Capacity,
+    /// Without the other tasks the event would run in another window
+    OtherTasks,
+    /// The event runs in another window than the one with the most renewable energy,
+    /// as the energy imported there is less carbon intensive
+    CarbonIntensity,
+}
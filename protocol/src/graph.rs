@@ -1,7 +1,78 @@
-use crate::time::DateTimeUtc;
-use chrono::Duration;
+use std::{
+    fmt::{self, Display},
+    ops::Range,
+};
+
+use crate::time::{DateTimeUtc, Timespan};
+use chrono::{DateTime, Duration};
 use serde::{Deserialize, Serialize};
 
+/// Returned when changing the values of a [DiscreteGraph] outside of the time it covers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutOfRangeError {
+    pub timespan: Timespan,
+    pub graph: Timespan,
+}
+
+impl Display for OutOfRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The timespan from {} to {} is outside the graph from {} to {}",
+            self.timespan.start, self.timespan.end, self.graph.start, self.graph.end
+        )
+    }
+}
+
+impl std::error::Error for OutOfRangeError {}
+
+/// Which part of two graphs is kept when they are combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overlap {
+    /// Only the timeslots covered by both graphs
+    Intersect,
+    /// Every timeslot covered by either graph, where the other graph counts as zero
+    Union,
+    /// The graphs must cover the same time, otherwise combining them fails
+    Exact,
+}
+
+/// Returned when two graphs cannot be combined with the requested [Overlap].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CombineError {
+    /// The graphs do not share a whole timeslot, with [Overlap::Intersect]
+    Disjoint { left: Timespan, right: Timespan },
+    /// The graphs cover different times, with [Overlap::Exact]
+    Mismatch { left: Timespan, right: Timespan },
+}
+
+impl Display for CombineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (reason, left, right) = match self {
+            CombineError::Disjoint { left, right } => ("do not overlap", left, right),
+            CombineError::Mismatch { left, right } => ("cover different times", left, right),
+        };
+        write!(
+            f,
+            "The graphs from {} to {} and from {} to {} {}",
+            left.start, left.end, right.start, right.end, reason
+        )
+    }
+}
+
+impl std::error::Error for CombineError {}
+
+/// How the values of a [DiscreteGraph] are combined or split when it is resampled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResampleMode {
+    /// The values are amounts per timeslot, such as energy, so the sum of the values is preserved.
+    /// Merging timeslots adds their values and splitting a timeslot divides its value.
+    Sum,
+    /// The values are rates, such as effect, so the area under the graph is preserved.
+    /// Merging timeslots averages their values and splitting a timeslot repeats its value.
+    Mean,
+}
+
 /// #### Important: end_time is non-inclusive
 /// ### Example
 /// time_delta = Duration::hours(4)
@@ -42,12 +113,544 @@ impl DiscreteGraph {
     pub fn get_end_time(&self) -> DateTimeUtc {
         self.end_time
     }
-    pub fn sub_value(&mut self, index: usize, effect: f64) {
-        self.values[index] -= effect;
+    /// The length of time covered by the values.
+    pub fn get_duration(&self) -> Duration {
+        self.time_delta * self.values.len() as i32
+    }
+    /// Resamples the graph to timeslots of `time_delta` from the same start time.
+    ///
+    /// The last timeslot is extended to a whole `time_delta`, where the extension counts as zero.
+    ///
+    /// # Panics
+    /// If `time_delta` is not positive.
+    pub fn resample(&self, time_delta: Duration, mode: ResampleMode) -> DiscreteGraph {
+        let len = div_ceil(self.get_duration(), time_delta);
+        self.resample_onto(self.start_time, time_delta, len, mode)
+    }
+    /// Moves the start back to the previous multiple of the time delta since the Unix epoch,
+    /// so graphs with the same time delta share their timeslot boundaries.
+    ///
+    /// The parts of the timeslots outside the original graph count as zero.
+    pub fn align(&self, mode: ResampleMode) -> DiscreteGraph {
+        let delta = self.time_delta.num_milliseconds();
+        let aligned = self.start_time.timestamp_millis().div_euclid(delta) * delta;
+        let start_time = DateTime::from_timestamp_millis(aligned).unwrap();
+        if start_time == self.start_time {
+            return self.clone();
+        }
+        let len = div_ceil(
+            self.start_time + self.get_duration() - start_time,
+            self.time_delta,
+        );
+        self.resample_onto(start_time, self.time_delta, len, mode)
+    }
+    /// The time covered by the values, from the start of the first timeslot to the end of the last.
+    pub fn get_timespan(&self) -> Timespan {
+        Timespan::new(self.start_time, self.start_time + self.get_duration())
+    }
+    /// The index of the timeslot that contains `time`, `None` if it is outside the graph.
+    pub fn index_at(&self, time: DateTimeUtc) -> Option<usize> {
+        let offset = (time - self.start_time).num_milliseconds();
+        let index = offset.div_euclid(self.time_delta.num_milliseconds());
+        usize::try_from(index)
+            .ok()
+            .filter(|index| *index < self.values.len())
+    }
+    /// The start of the timeslot at `index`, where an index of the length is the end of the graph.
+    pub fn time_at(&self, index: usize) -> DateTimeUtc {
+        self.start_time + self.time_delta * index as i32
+    }
+    /// The start time and value of every timeslot.
+    pub fn slots(&self) -> impl Iterator<Item = (DateTimeUtc, f64)> + '_ {
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (self.time_at(index), *value))
     }
-    pub fn sub_values(&mut self, index: usize, effect: f64, duration: usize) {
-        for n in 0..duration {
-            self.sub_value(index + n, effect);
+    /// The indices of the timeslots that lie entirely within `timespan`.
+    pub fn slots_within(&self, timespan: &Timespan) -> Range<usize> {
+        let first = self.slot_boundary(timespan.start, true);
+        let last = self.slot_boundary(timespan.end, false).max(first);
+        first..last
+    }
+    /// The indices of the timeslots that overlap `timespan`.
+    pub fn slots_overlapping(&self, timespan: &Timespan) -> Range<usize> {
+        let first = self.slot_boundary(timespan.start, false);
+        let last = self.slot_boundary(timespan.end, true).max(first);
+        first..last
+    }
+    /// The timeslots that overlap `timespan`, which is empty if none do.
+    pub fn sub_graph(&self, timespan: &Timespan) -> DiscreteGraph {
+        let slots = self.slots_overlapping(timespan);
+
+        DiscreteGraph::new(
+            self.values[slots.clone()].to_vec(),
+            self.time_delta,
+            self.time_at(slots.start),
+        )
+    }
+    /// Adds `value` to every timeslot that overlaps `timespan`.
+    ///
+    /// Fails without changing the graph if `timespan` is not within the graph.
+    pub fn checked_add(&mut self, timespan: &Timespan, value: f64) -> Result<(), OutOfRangeError> {
+        let graph = self.get_timespan();
+        if timespan.start < graph.start || graph.end < timespan.end {
+            return Err(OutOfRangeError {
+                timespan: timespan.clone(),
+                graph,
+            });
+        }
+
+        for index in self.slots_overlapping(timespan) {
+            self.values[index] += value;
+        }
+        Ok(())
+    }
+    /// Subtracts `value` from every timeslot that overlaps `timespan`.
+    ///
+    /// Fails without changing the graph if `timespan` is not within the graph.
+    pub fn checked_sub(&mut self, timespan: &Timespan, value: f64) -> Result<(), OutOfRangeError> {
+        self.checked_add(timespan, -value)
+    }
+    /// Combines the values of two graphs timeslot by timeslot with `operation`.
+    ///
+    /// The result has the shorter time delta of the two, with timeslots aligned to the start of `self`.
+    /// Both graphs are resampled to it with [ResampleMode::Mean], as the values are effects.
+    pub fn combine(
+        &self,
+        other: &DiscreteGraph,
+        overlap: Overlap,
+        operation: impl Fn(f64, f64) -> f64,
+    ) -> Result<DiscreteGraph, CombineError> {
+        let time_delta = self.time_delta.min(other.time_delta);
+        let (left, right) = (self.get_timespan(), other.get_timespan());
+
+        // The timespan of the result, and whether partially covered timeslots at its ends are kept
+        let (timespan, partial) = match overlap {
+            Overlap::Intersect => {
+                let start = left.start.max(right.start);
+                let end = left.end.min(right.end);
+                (Timespan::new(start, end.max(start)), false)
+            }
+            Overlap::Union => (
+                Timespan::new(left.start.min(right.start), left.end.max(right.end)),
+                true,
+            ),
+            Overlap::Exact if left != right => return Err(CombineError::Mismatch { left, right }),
+            Overlap::Exact => (left.clone(), true),
+        };
+
+        let delta = time_delta.num_milliseconds();
+        let boundary = |time: DateTimeUtc, round_up: bool| {
+            let offset = (time - self.start_time).num_milliseconds();
+            offset.div_euclid(delta) + i64::from(round_up && offset.rem_euclid(delta) != 0)
+        };
+        let first = boundary(timespan.start, !partial);
+        let last = boundary(timespan.end, partial).max(first);
+        if overlap == Overlap::Intersect && first == last {
+            return Err(CombineError::Disjoint { left, right });
+        }
+
+        let start_time = self.start_time + Duration::milliseconds(first * delta);
+        let len = (last - first) as usize;
+        let left = self.resample_onto(start_time, time_delta, len, ResampleMode::Mean);
+        let right = other.resample_onto(start_time, time_delta, len, ResampleMode::Mean);
+        let values = left
+            .values
+            .iter()
+            .zip(&right.values)
+            .map(|(left, right)| operation(*left, *right))
+            .collect();
+
+        Ok(DiscreteGraph::new(values, time_delta, start_time))
+    }
+    /// The sum of the graphs, such as the production of several energy sources.
+    pub fn add(
+        &self,
+        other: &DiscreteGraph,
+        overlap: Overlap,
+    ) -> Result<DiscreteGraph, CombineError> {
+        self.combine(other, overlap, |left, right| left + right)
+    }
+    /// The values of `other` subtracted from these, such as the consumption from the production.
+    pub fn sub(
+        &self,
+        other: &DiscreteGraph,
+        overlap: Overlap,
+    ) -> Result<DiscreteGraph, CombineError> {
+        self.combine(other, overlap, |left, right| left - right)
+    }
+    /// The smaller value of the graphs in each timeslot.
+    pub fn min(
+        &self,
+        other: &DiscreteGraph,
+        overlap: Overlap,
+    ) -> Result<DiscreteGraph, CombineError> {
+        self.combine(other, overlap, f64::min)
+    }
+    /// The larger value of the graphs in each timeslot.
+    pub fn max(
+        &self,
+        other: &DiscreteGraph,
+        overlap: Overlap,
+    ) -> Result<DiscreteGraph, CombineError> {
+        self.combine(other, overlap, f64::max)
+    }
+    /// Every value multiplied by `factor`.
+    pub fn scale(&self, factor: f64) -> DiscreteGraph {
+        DiscreteGraph::new(
+            self.values.iter().map(|value| value * factor).collect(),
+            self.time_delta,
+            self.start_time,
+        )
+    }
+    /// The index of the timeslot boundary at or around `time`, clamped to the graph.
+    fn slot_boundary(&self, time: DateTimeUtc, round_up: bool) -> usize {
+        let delta = self.time_delta.num_milliseconds();
+        let offset = (time - self.start_time).num_milliseconds();
+        let slot = offset.div_euclid(delta) + i64::from(round_up && offset.rem_euclid(delta) != 0);
+        slot.clamp(0, self.values.len() as i64) as usize
+    }
+    /// Spreads the values over `len` timeslots of `time_delta` from `start_time`,
+    /// in proportion to how much each of the timeslots overlap.
+    fn resample_onto(
+        &self,
+        start_time: DateTimeUtc,
+        time_delta: Duration,
+        len: usize,
+        mode: ResampleMode,
+    ) -> DiscreteGraph {
+        let source_delta = self.time_delta.num_milliseconds();
+        let target_delta = time_delta.num_milliseconds();
+        let offset = (self.start_time - start_time).num_milliseconds();
+        let mut values = vec![0.0; len];
+
+        for (index, value) in self.values.iter().enumerate() {
+            let source_start = offset + index as i64 * source_delta;
+            let source_end = source_start + source_delta;
+            let first = source_start.div_euclid(target_delta).max(0);
+            let last = (source_end - 1)
+                .div_euclid(target_delta)
+                .min(len as i64 - 1);
+            for target in first..=last {
+                let overlap = source_end.min((target + 1) * target_delta)
+                    - source_start.max(target * target_delta);
+                let share = match mode {
+                    ResampleMode::Sum => overlap as f64 / source_delta as f64,
+                    ResampleMode::Mean => overlap as f64 / target_delta as f64,
+                };
+                values[target as usize] += value * share;
+            }
+        }
+
+        DiscreteGraph::new(values, time_delta, start_time)
+    }
+}
+
+/// The amount of `time_delta` timeslots needed to cover `duration`.
+fn div_ceil(duration: Duration, time_delta: Duration) -> usize {
+    let delta = time_delta.num_milliseconds();
+    assert!(delta > 0, "The time delta must be positive.");
+    (duration.num_milliseconds().max(0) + delta - 1).div_euclid(delta) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn start() -> DateTimeUtc {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    fn energy(graph: &DiscreteGraph) -> f64 {
+        graph.get_values().iter().sum::<f64>() * graph.get_time_delta().num_milliseconds() as f64
+    }
+
+    fn assert_close(left: f64, right: f64) {
+        assert!(
+            (left - right).abs() <= 1e-9 * left.abs().max(right.abs()).max(1.0),
+            "{left} != {right}"
+        );
+    }
+
+    #[test]
+    fn splitting_repeats_rates_and_divides_amounts() {
+        let graph = DiscreteGraph::new(vec![4.0, 8.0], Duration::hours(1), start());
+
+        let mean = graph.resample(Duration::minutes(30), ResampleMode::Mean);
+        assert_eq!(mean.get_values(), &vec![4.0, 4.0, 8.0, 8.0]);
+        assert_eq!(mean.get_start_time(), start());
+
+        let sum = graph.resample(Duration::minutes(30), ResampleMode::Sum);
+        assert_eq!(sum.get_values(), &vec![2.0, 2.0, 4.0, 4.0]);
+    }
+
+    #[test]
+    fn merging_averages_rates_and_adds_amounts() {
+        let graph = DiscreteGraph::new(vec![1.0, 2.0, 3.0], Duration::minutes(20), start());
+
+        let mean = graph.resample(Duration::minutes(40), ResampleMode::Mean);
+        // The second timeslot only has a value for its first half
+        assert_eq!(mean.get_values(), &vec![1.5, 1.5]);
+
+        let sum = graph.resample(Duration::minutes(40), ResampleMode::Sum);
+        assert_eq!(sum.get_values(), &vec![3.0, 3.0]);
+    }
+
+    #[test]
+    fn align_starts_at_a_timeslot_boundary() {
+        let graph = DiscreteGraph::new(
+            vec![10.0, 20.0],
+            Duration::hours(1),
+            start() + Duration::minutes(15),
+        );
+
+        let aligned = graph.align(ResampleMode::Mean);
+        assert_eq!(aligned.get_start_time(), start());
+        assert_eq!(aligned.get_values(), &vec![7.5, 17.5, 5.0]);
+
+        let already_aligned = aligned.align(ResampleMode::Mean);
+        assert_eq!(already_aligned.get_values(), aligned.get_values());
+    }
+
+    #[test]
+    fn sub_graph_keeps_the_overlapping_timeslots() {
+        let graph = DiscreteGraph::new(vec![1.0, 2.0, 3.0, 4.0], Duration::hours(1), start());
+
+        let sub_graph = graph.sub_graph(&Timespan::new(
+            start() + Duration::minutes(90),
+            start() + Duration::hours(3),
+        ));
+        assert_eq!(sub_graph.get_values(), &vec![2.0, 3.0]);
+        assert_eq!(sub_graph.get_start_time(), start() + Duration::hours(1));
+
+        let outside = graph.sub_graph(&Timespan::new(
+            start() + Duration::days(1),
+            start() + Duration::days(2),
+        ));
+        assert!(outside.get_values().is_empty());
+    }
+
+    #[test]
+    fn times_map_to_timeslots_and_back() {
+        let graph = DiscreteGraph::new(vec![1.0, 2.0, 3.0], Duration::hours(1), start());
+
+        assert_eq!(graph.index_at(start()), Some(0));
+        assert_eq!(graph.index_at(start() + Duration::minutes(119)), Some(1));
+        assert_eq!(graph.index_at(start() - Duration::milliseconds(1)), None);
+        assert_eq!(graph.index_at(start() + Duration::hours(3)), None);
+        assert_eq!(graph.time_at(2), start() + Duration::hours(2));
+        assert_eq!(graph.time_at(3), graph.get_timespan().end);
+
+        let slots: Vec<_> = graph.slots().collect();
+        assert_eq!(
+            slots,
+            vec![
+                (start(), 1.0),
+                (start() + Duration::hours(1), 2.0),
+                (start() + Duration::hours(2), 3.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn slots_within_exclude_partial_timeslots() {
+        let graph = DiscreteGraph::new(vec![0.0; 4], Duration::hours(1), start());
+        let timespan = Timespan::new(
+            start() + Duration::minutes(30),
+            start() + Duration::hours(3),
+        );
+
+        assert_eq!(graph.slots_within(&timespan), 1..3);
+        assert_eq!(graph.slots_overlapping(&timespan), 0..3);
+        // A timespan ending at the end of the graph includes the last timeslot
+        assert_eq!(graph.slots_within(&graph.get_timespan()), 0..4);
+        // Timespans outside the graph are clamped to it
+        let before = Timespan::new(start() - Duration::hours(5), start() - Duration::hours(4));
+        assert!(graph.slots_within(&before).is_empty());
+        assert!(graph.slots_overlapping(&before).is_empty());
+    }
+
+    #[test]
+    fn checked_sub_rejects_timespans_outside_the_graph() {
+        let mut graph = DiscreteGraph::new(vec![5.0; 3], Duration::hours(1), start());
+
+        graph
+            .checked_sub(
+                &Timespan::new(
+                    start() + Duration::minutes(30),
+                    start() + Duration::hours(2),
+                ),
+                2.0,
+            )
+            .unwrap();
+        assert_eq!(graph.get_values(), &vec![3.0, 3.0, 5.0]);
+
+        let overlong = Timespan::new(start() + Duration::hours(2), start() + Duration::hours(4));
+        let error = graph.checked_add(&overlong, 1.0).unwrap_err();
+        assert_eq!(error.timespan, overlong);
+        assert_eq!(error.graph, graph.get_timespan());
+        // Nothing is changed when it fails
+        assert_eq!(graph.get_values(), &vec![3.0, 3.0, 5.0]);
+    }
+
+    #[test]
+    fn sources_are_combined_at_the_finer_time_delta() {
+        let solar = DiscreteGraph::new(vec![100.0, 200.0], Duration::hours(1), start());
+        let consumption =
+            DiscreteGraph::new(vec![10.0, 20.0, 30.0, 40.0], Duration::minutes(30), start());
+
+        let available = solar.sub(&consumption, Overlap::Exact).unwrap();
+        assert_eq!(available.get_time_delta(), Duration::minutes(30));
+        assert_eq!(available.get_values(), &vec![90.0, 80.0, 170.0, 160.0]);
+
+        let doubled = solar.scale(2.0).add(&solar, Overlap::Exact).unwrap();
+        assert_eq!(doubled.get_values(), &vec![300.0, 600.0]);
+
+        let lowest = solar.min(&consumption.scale(5.0), Overlap::Exact).unwrap();
+        assert_eq!(lowest.get_values(), &vec![50.0, 100.0, 150.0, 200.0]);
+        let highest = solar.max(&consumption.scale(5.0), Overlap::Exact).unwrap();
+        assert_eq!(highest.get_values(), &vec![100.0, 100.0, 200.0, 200.0]);
+    }
+
+    #[test]
+    fn overlap_decides_the_timespan_of_the_result() {
+        let first = DiscreteGraph::new(vec![1.0, 2.0, 3.0], Duration::hours(1), start());
+        let second = DiscreteGraph::new(
+            vec![10.0, 20.0, 30.0],
+            Duration::hours(1),
+            start() + Duration::hours(2),
+        );
+
+        let intersection = first.add(&second, Overlap::Intersect).unwrap();
+        assert_eq!(intersection.get_start_time(), start() + Duration::hours(2));
+        assert_eq!(intersection.get_values(), &vec![13.0]);
+
+        let union = first.add(&second, Overlap::Union).unwrap();
+        assert_eq!(union.get_start_time(), start());
+        assert_eq!(union.get_values(), &vec![1.0, 2.0, 13.0, 20.0, 30.0]);
+
+        assert_eq!(
+            first.add(&second, Overlap::Exact).unwrap_err(),
+            CombineError::Mismatch {
+                left: first.get_timespan(),
+                right: second.get_timespan()
+            }
+        );
+
+        let later = DiscreteGraph::new(vec![1.0], Duration::hours(1), start() + Duration::days(1));
+        assert!(matches!(
+            first.add(&later, Overlap::Intersect),
+            Err(CombineError::Disjoint { .. })
+        ));
+    }
+
+    fn graphs() -> impl Strategy<Value = DiscreteGraph> {
+        (
+            prop::collection::vec(-5000.0..5000.0, 0..100),
+            1i64..180,
+            0i64..1440,
+        )
+            .prop_map(|(values, minutes, offset)| {
+                DiscreteGraph::new(
+                    values,
+                    Duration::minutes(minutes),
+                    start() + Duration::minutes(offset),
+                )
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn mean_resampling_conserves_energy(graph in graphs(), minutes in 1i64..180) {
+            let resampled = graph.resample(Duration::minutes(minutes), ResampleMode::Mean);
+
+            assert_close(energy(&resampled), energy(&graph));
+            prop_assert!(resampled.get_duration() >= graph.get_duration());
+            prop_assert!(resampled.get_duration() < graph.get_duration() + Duration::minutes(minutes));
+        }
+
+        #[test]
+        fn sum_resampling_conserves_the_sum(graph in graphs(), minutes in 1i64..180) {
+            let resampled = graph.resample(Duration::minutes(minutes), ResampleMode::Sum);
+
+            assert_close(
+                resampled.get_values().iter().sum(),
+                graph.get_values().iter().sum(),
+            );
+        }
+
+        #[test]
+        fn alignment_conserves_energy(graph in graphs()) {
+            let aligned = graph.align(ResampleMode::Mean);
+            let delta = graph.get_time_delta().num_milliseconds();
+
+            prop_assert_eq!(aligned.get_start_time().timestamp_millis() % delta, 0);
+            prop_assert!(aligned.get_start_time() <= graph.get_start_time());
+            assert_close(energy(&aligned), energy(&graph));
+        }
+
+        #[test]
+        fn times_are_within_their_timeslot(graph in graphs(), minutes in -60i64..20000) {
+            let time = graph.get_start_time() + Duration::minutes(minutes);
+
+            match graph.index_at(time) {
+                Some(index) => {
+                    prop_assert!(graph.time_at(index) <= time);
+                    prop_assert!(time < graph.time_at(index + 1));
+                }
+                None => {
+                    let timespan = graph.get_timespan();
+                    prop_assert!(time < timespan.start || timespan.end <= time);
+                }
+            }
+        }
+
+        #[test]
+        fn union_of_sources_conserves_energy(first in graphs(), second in graphs()) {
+            let total = first.add(&second, Overlap::Union).unwrap();
+
+            assert_close(energy(&total), energy(&first) + energy(&second));
+        }
+
+        #[test]
+        fn subtracting_a_source_undoes_adding_it(first in graphs(), second in graphs()) {
+            let second = DiscreteGraph::new(
+                second.get_values().clone(),
+                first.get_time_delta(),
+                first.get_start_time(),
+            );
+            prop_assume!(!first.get_values().is_empty() && !second.get_values().is_empty());
+
+            let total = first.add(&second, Overlap::Intersect).unwrap();
+            let difference = total.sub(&second, Overlap::Intersect).unwrap();
+
+            let len = difference.get_values().len();
+            prop_assert_eq!(len, first.get_values().len().min(second.get_values().len()));
+            for (actual, expected) in difference.get_values().iter().zip(first.get_values()) {
+                assert_close(*actual, *expected);
+            }
+        }
+
+        #[test]
+        fn adjacent_sub_graphs_make_up_the_graph(graph in graphs(), split in 0i64..10000) {
+            let middle = graph.get_start_time() + Duration::minutes(split);
+            let before = graph.sub_graph(&Timespan::new(graph.get_start_time() - Duration::days(1), middle));
+            let after = graph.sub_graph(&Timespan::new(middle, graph.get_start_time() + Duration::days(30)));
+
+            // A timeslot that the split falls within is in both
+            let overlap = usize::from(
+                (split * 60_000) % graph.get_time_delta().num_milliseconds() != 0
+                    && middle < graph.get_start_time() + graph.get_duration(),
+            );
+            prop_assert_eq!(before.get_values().len() + after.get_values().len() - overlap, graph.get_values().len());
+            prop_assert_eq!(
+                [&before.get_values()[..], &after.get_values()[overlap..]].concat(),
+                graph.get_values().clone()
+            );
         }
     }
 }
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentStatus {
+    Ok,
+    Unavailable,
+}
+
+/// The status of every component the backend needs to serve requests.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReadinessResponse {
+    pub database: ComponentStatus,
+    pub background_service: ComponentStatus,
+}
+
+impl ReadinessResponse {
+    pub fn is_ready(&self) -> bool {
+        self.database == ComponentStatus::Ok && self.background_service == ComponentStatus::Ok
+    }
+}
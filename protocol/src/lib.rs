@@ -1,7 +1,14 @@
 pub mod accounts;
+pub mod baseline;
+pub mod calendar;
+pub mod carbon;
 pub mod devices;
+pub mod errors;
 pub mod events;
 pub mod graph;
+pub mod health;
+pub mod reports;
 pub mod scheduling;
 pub mod tasks;
 pub mod time;
+pub mod time_series;
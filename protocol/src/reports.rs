@@ -0,0 +1,92 @@
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    devices::DeviceId,
+    errors::ErrorDetail,
+    time::{DateTimeUtc, Timespan},
+};
+
+/// The longest period a single energy report can cover.
+pub const MAX_REPORT_DAYS: i64 = 366;
+
+/// Reports on the runs that start between `start` and `end`.
+#[derive(Deserialize, Serialize)]
+pub struct EnergyReportRequest {
+    pub start: DateTimeUtc,
+    pub end: DateTimeUtc,
+    /// The price of a kWh from the grid, to report the cost savings
+    #[serde(default)]
+    pub price_per_kwh: Option<f64>,
+    /// The grams of CO2 emitted per kWh from the grid, to report the CO2 savings
+    #[serde(default)]
+    pub co2_grams_per_kwh: Option<f64>,
+}
+
+impl EnergyReportRequest {
+    /// Returns an [ErrorDetail] for every invalid field.
+    pub fn validate(&self) -> Result<(), Vec<ErrorDetail>> {
+        let mut errors = Vec::new();
+
+        if self.end <= self.start {
+            errors.push(ErrorDetail::field("end", "The end must be after the start"));
+        } else if self.end - self.start > Duration::days(MAX_REPORT_DAYS) {
+            errors.push(ErrorDetail::field(
+                "end",
+                &format!("A report can cover at most {MAX_REPORT_DAYS} days"),
+            ));
+        }
+
+        for (field, value) in [
+            ("price_per_kwh", self.price_per_kwh),
+            ("co2_grams_per_kwh", self.co2_grams_per_kwh),
+        ] {
+            if value.is_some_and(|value| !value.is_finite() || value < 0.0) {
+                errors.push(ErrorDetail::field(
+                    field,
+                    "The value must be a non-negative number",
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The energy used by runs, compared to running every task at the start of its timespan.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Default)]
+pub struct EnergyUsage {
+    pub runs: u32,
+    pub energy_kwh: f64,
+    /// The energy covered by renewable surplus at the scheduled times
+    pub renewable_kwh: f64,
+    /// The energy that would have been covered by renewable surplus when running immediately
+    pub baseline_renewable_kwh: f64,
+    /// The share, between 0 and 1, of the energy covered by renewable surplus
+    pub renewable_share: f64,
+    pub baseline_renewable_share: f64,
+    /// The energy from the grid that scheduling saved, which is negative if it used more
+    pub saved_kwh: f64,
+    /// Only reported when a price is given
+    pub cost_savings: Option<f64>,
+    /// Only reported when the CO2 emissions of the grid are given
+    pub co2_savings_kg: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct DeviceEnergyReport {
+    pub device_id: DeviceId,
+    pub device_name: String,
+    pub usage: EnergyUsage,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct EnergyReport {
+    pub timespan: Timespan,
+    pub devices: Vec<DeviceEnergyReport>,
+    pub total: EnergyUsage,
+}
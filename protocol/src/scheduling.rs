@@ -1,10 +1,41 @@
-use crate::graph::DiscreteGraph;
+use crate::{
+    graph::DiscreteGraph,
+    tasks::TaskId,
+    time::{DateTimeUtc, Milliseconds},
+};
 use serde::{Deserialize, Serialize};
 
+/// The scheduling algorithms of the backend, with their parameters.
+///
+/// Serialized with the algorithm in the `name` field, e.g. `{"name": "all_permutations", "max_tasks": 8}`.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(tag = "name", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Algorithm {
+    #[default]
+    Naive,
+    Global,
+    AllPermutations {
+        /// Falls back to the global algorithm when there are more tasks than this
+        #[serde(default)]
+        max_tasks: Option<usize>,
+    },
+}
+
+impl Algorithm {
+    /// The name used in the `name` field when serialized.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Naive => "naive",
+            Algorithm::Global => "global",
+            Algorithm::AllPermutations { .. } => "all_permutations",
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct SchedulingGlob {
     pub discrete_graph: DiscreteGraph,
-    pub alg: u8,
+    pub algorithm: Algorithm,
 }
 
 impl SchedulingGlob {
@@ -12,7 +43,72 @@ impl SchedulingGlob {
         &self.discrete_graph
     }
 
-    pub fn get_alg(&self) -> u8 {
-        self.alg
+    pub fn get_algorithm(&self) -> &Algorithm {
+        &self.algorithm
     }
 }
+
+/// An algorithm offered by the backend, with its default parameters.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AlgorithmInfo {
+    pub algorithm: Algorithm,
+    pub description: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GetAlgorithmsResponse {
+    pub algorithms: Vec<AlgorithmInfo>,
+}
+
+#[derive(Deserialize, Serialize, Debug, sqlx::Type, PartialEq, Eq, Clone, Copy)]
+#[sqlx(transparent)]
+pub struct SchedulingRunId(i64);
+
+/// A single run of the scheduler in the background service.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SchedulingRun {
+    pub id: SchedulingRunId,
+    pub algorithm: Algorithm,
+    pub task_count: i64,
+    /// The cost of the resulting graph, `None` if the run failed
+    pub cost: Option<f64>,
+    /// The estimated grams of CO2 of the energy imported from the grid,
+    /// `None` if no carbon intensity was known or the run failed
+    #[serde(default)]
+    pub emissions: Option<f64>,
+    pub started_at: DateTimeUtc,
+    pub duration: Milliseconds,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetSchedulingRunsRequest {
+    /// The amount of runs to return, newest first
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetSchedulingRunsResponse {
+    pub runs: Vec<SchedulingRun>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetSchedulingRunDiffRequest {
+    pub id: SchedulingRunId,
+}
+
+/// A task whose event was moved or created by a scheduling run.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct EventChange {
+    pub task_id: TaskId,
+    /// `None` if the run created the event
+    pub previous_start_time: Option<DateTimeUtc>,
+    pub start_time: DateTimeUtc,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetSchedulingRunDiffResponse {
+    pub run: SchedulingRun,
+    pub input_graph: DiscreteGraph,
+    pub changes: Vec<EventChange>,
+}
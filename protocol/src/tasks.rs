@@ -1,11 +1,13 @@
 use std::ops::AddAssign;
 
+use chrono::{NaiveDate, NaiveTime};
 use derive_more::{Display, From, Into};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     devices::DeviceId,
-    time::{Milliseconds, Timespan},
+    errors::ErrorDetail,
+    time::{DateTimeLocal, DateTimeUtc, LocalTimespan, Milliseconds, Timespan},
 };
 
 #[derive(
@@ -22,6 +24,7 @@ use crate::{
     Display,
     PartialOrd,
     Ord,
+    Hash,
 )]
 #[sqlx(transparent)]
 pub struct TaskId(i64);
@@ -44,15 +47,156 @@ pub struct CreateTaskRequest {
     pub device_id: DeviceId,
 }
 
+impl CreateTaskRequest {
+    /// Checks that the task can still be scheduled at `now`.
+    /// Returns an [ErrorDetail] for every invalid field.
+    pub fn validate(&self, now: DateTimeUtc) -> Result<(), Vec<ErrorDetail>> {
+        let mut errors = Vec::new();
+        let duration = i64::from(self.duration);
+
+        if duration <= 0 {
+            errors.push(ErrorDetail::field(
+                "duration",
+                "The duration must be positive",
+            ));
+        }
+
+        if self.timespan.end <= self.timespan.start {
+            errors.push(ErrorDetail::field(
+                "timespan",
+                "The timespan must end after it starts",
+            ));
+        } else if self.timespan.end <= now {
+            errors.push(ErrorDetail::field(
+                "timespan",
+                "The timespan must not be entirely in the past",
+            ));
+        } else if duration > 0 {
+            let start = self.timespan.start.max(now);
+            if duration > (self.timespan.end - start).num_milliseconds() {
+                errors.push(ErrorDetail::field(
+                    "duration",
+                    "The duration must fit within the remaining timespan",
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The most days a single request can create tasks for.
+pub const MAX_LOCAL_TASK_DAYS: u32 = 31;
+
+/// Creates a task for a daily window in the account's local time, e.g. "tonight from 22:00 to 06:00".
+#[derive(Deserialize, Serialize)]
+pub struct CreateLocalTasksRequest {
+    pub device_id: DeviceId,
+    pub duration: Milliseconds,
+    /// The local date the first window starts on
+    pub date: NaiveDate,
+    pub start: NaiveTime,
+    /// The window ends on the next day if this is not after `start`
+    pub end: NaiveTime,
+    /// The number of consecutive days to create a task for
+    #[serde(default = "one_day")]
+    pub days: u32,
+}
+
+fn one_day() -> u32 {
+    1
+}
+
+impl CreateLocalTasksRequest {
+    /// Checks the fields that do not depend on the time zone.
+    /// The resulting tasks are validated as a [CreateTaskRequest] each.
+    pub fn validate(&self) -> Result<(), Vec<ErrorDetail>> {
+        if self.days == 0 || self.days > MAX_LOCAL_TASK_DAYS {
+            return Err(vec![ErrorDetail::field(
+                "days",
+                &format!("The number of days must be between 1 and {MAX_LOCAL_TASK_DAYS}"),
+            )]);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateLocalTasksResponse {
+    pub tasks: Vec<LocalTask>,
+}
+
+/// The tasks with their times in the account's time zone.
+#[derive(Deserialize, Serialize)]
+pub struct GetLocalTasksResponse {
+    pub time_zone: String,
+    pub tasks: Vec<LocalTask>,
+}
+
+/// The predicted outcome of scheduling a task, without creating it.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct PreviewTaskResponse {
+    pub start_time: DateTimeUtc,
+    /// The share, between 0 and 1, of the task's energy that is expected to be renewable
+    pub renewable_coverage: f64,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct DeleteTaskRequest {
     pub id: TaskId,
 }
 
+/// Fixes the task to `start_time`, so the scheduler never moves it.
+#[derive(Deserialize, Serialize)]
+pub struct PinTaskRequest {
+    pub id: TaskId,
+    pub start_time: DateTimeUtc,
+}
+
+/// Prevents the scheduler from running the task during `timespan`.
+#[derive(Deserialize, Serialize)]
+pub struct ExcludeTimespanRequest {
+    pub id: TaskId,
+    pub timespan: Timespan,
+}
+
+/// Removes the pin from the task, so it is optimized again.
+#[derive(Deserialize, Serialize)]
+pub struct UnpinTaskRequest {
+    pub id: TaskId,
+}
+
+/// Removes all exclusions from the task, so it may run anywhere within its timespan again.
+#[derive(Deserialize, Serialize)]
+pub struct ClearExclusionsRequest {
+    pub id: TaskId,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Task {
     pub id: TaskId,
     pub timespan: Timespan,
     pub duration: Milliseconds,
     pub device_id: DeviceId,
+    #[serde(default)]
+    pub pinned_start: Option<DateTimeUtc>,
+    #[serde(default)]
+    pub exclusions: Vec<Timespan>,
+}
+
+/// A [Task] with its times in the account's time zone.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct LocalTask {
+    pub id: TaskId,
+    pub timespan: LocalTimespan,
+    pub duration: Milliseconds,
+    pub device_id: DeviceId,
+    #[serde(default)]
+    pub pinned_start: Option<DateTimeLocal>,
+    #[serde(default)]
+    pub exclusions: Vec<LocalTimespan>,
 }
@@ -1,8 +1,10 @@
-use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, TimeZone, Utc};
 use derive_more::From;
 use serde::{Deserialize, Serialize};
 
 pub type DateTimeUtc = DateTime<Utc>;
+/// A point in time together with the UTC offset of the local time it was given or shown in.
+pub type DateTimeLocal = DateTime<FixedOffset>;
 
 #[derive(Deserialize, Serialize, sqlx::Type, Debug, PartialEq, Eq, Clone, Copy, From)]
 #[sqlx(transparent)]
@@ -41,6 +43,7 @@ impl Timespan {
         Timespan { start, end }
     }
 
+    /// Creates a timespan from naive values that are in UTC, as they are stored in the database.
     pub fn new_from_naive(start: NaiveDateTime, end: NaiveDateTime) -> Self {
         fn to_utc(date_time: NaiveDateTime) -> DateTimeUtc {
             Utc::from_utc_datetime(&Utc, &date_time)
@@ -48,3 +51,10 @@ impl Timespan {
         Timespan::new(to_utc(start), to_utc(end))
     }
 }
+
+/// A timespan in the local time of an account.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone)]
+pub struct LocalTimespan {
+    pub start: DateTimeLocal,
+    pub end: DateTimeLocal,
+}
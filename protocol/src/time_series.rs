@@ -0,0 +1,391 @@
+use std::fmt::{self, Display};
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{graph::DiscreteGraph, time::DateTimeUtc};
+
+const CSV_HEADER: &str = "timestamp,value";
+/// The most values a graph made from a time series may have, e.g. almost two years of minutes.
+pub const MAX_TIME_SERIES_VALUES: i64 = 1_000_000;
+
+/// A measured or forecast value at a point in time.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TimeSeriesPoint {
+    pub timestamp: DateTimeUtc,
+    pub value: f64,
+}
+
+/// Timestamped values, where each value holds until the next timestamp.
+///
+/// In JSON it is `{"points": [{"timestamp": "2024-06-01T12:00:00Z", "value": 1.5}, ...]}`,
+/// and in CSV it is a `timestamp,value` row per point with RFC 3339 timestamps.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct TimeSeries {
+    pub points: Vec<TimeSeriesPoint>,
+}
+
+/// What to do with timestamps that are missing from an otherwise uniformly spaced series.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GapPolicy {
+    /// Fail with [TimeSeriesError::Gap]
+    Reject,
+    /// Use zero for the missing values
+    Zero,
+    /// Interpolate the missing values linearly between the values around the gap
+    Interpolate,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimeSeriesError {
+    /// A CSV line, counted from 1, could not be parsed
+    InvalidLine { line: usize, message: String },
+    /// The spacing cannot be known from less than two points
+    TooFewPoints,
+    /// A value is not a finite number
+    InvalidValue { timestamp: DateTimeUtc },
+    /// A timestamp is not after the one before it
+    NotIncreasing { timestamp: DateTimeUtc },
+    /// The time since the previous timestamp is not a multiple of the spacing of the series
+    Irregular {
+        timestamp: DateTimeUtc,
+        spacing: Duration,
+    },
+    /// Timestamps are missing between `start` and `end`, with [GapPolicy::Reject]
+    Gap {
+        start: DateTimeUtc,
+        end: DateTimeUtc,
+    },
+    /// Filling the series at its shortest spacing would take more than [MAX_TIME_SERIES_VALUES] values
+    TooManyValues { spacing: Duration },
+}
+
+impl Display for TimeSeriesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeSeriesError::InvalidLine { line, message } => {
+                write!(f, "Line {line} is invalid: {message}")
+            }
+            TimeSeriesError::TooFewPoints => {
+                write!(f, "A time series needs at least two points")
+            }
+            TimeSeriesError::InvalidValue { timestamp } => {
+                write!(f, "The value at {timestamp} is not a finite number")
+            }
+            TimeSeriesError::NotIncreasing { timestamp } => {
+                write!(f, "The timestamp {timestamp} is not after the previous one")
+            }
+            TimeSeriesError::Irregular { timestamp, spacing } => write!(
+                f,
+                "The timestamp {timestamp} is not a multiple of {} seconds after the previous one",
+                spacing.num_milliseconds() as f64 / 1000.0
+            ),
+            TimeSeriesError::Gap { start, end } => {
+                write!(f, "Values are missing between {start} and {end}")
+            }
+            TimeSeriesError::TooManyValues { spacing } => write!(
+                f,
+                "The series needs more than {MAX_TIME_SERIES_VALUES} values at a spacing of {} seconds",
+                spacing.num_milliseconds() as f64 / 1000.0
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TimeSeriesError {}
+
+impl TimeSeries {
+    /// A point at the start of every timeslot of `graph`.
+    pub fn from_graph(graph: &DiscreteGraph) -> Self {
+        TimeSeries {
+            points: graph
+                .slots()
+                .map(|(timestamp, value)| TimeSeriesPoint { timestamp, value })
+                .collect(),
+        }
+    }
+
+    /// Converts the points to a graph, whose time delta is the shortest time between two points.
+    ///
+    /// Every other time between two points must be a multiple of it, and the missing points
+    /// are handled according to `gaps`. The graph has at most [MAX_TIME_SERIES_VALUES] values.
+    pub fn to_graph(&self, gaps: GapPolicy) -> Result<DiscreteGraph, TimeSeriesError> {
+        let [first, second, ..] = self.points.as_slice() else {
+            return Err(TimeSeriesError::TooFewPoints);
+        };
+
+        let mut time_delta = second.timestamp - first.timestamp;
+        for (previous, point) in self.points.iter().zip(&self.points[1..]) {
+            if point.timestamp <= previous.timestamp {
+                return Err(TimeSeriesError::NotIncreasing {
+                    timestamp: point.timestamp,
+                });
+            }
+            time_delta = time_delta.min(point.timestamp - previous.timestamp);
+        }
+
+        // A single short spacing in a long series would otherwise fill it with a huge amount of values
+        let delta = time_delta.num_milliseconds();
+        let last = self.points[self.points.len() - 1].timestamp;
+        let len = (last - first.timestamp)
+            .num_milliseconds()
+            .checked_div(delta);
+        if len.is_none_or(|len| len >= MAX_TIME_SERIES_VALUES) {
+            return Err(TimeSeriesError::TooManyValues {
+                spacing: time_delta,
+            });
+        }
+
+        let mut values = Vec::with_capacity(self.points.len());
+        let mut previous: Option<&TimeSeriesPoint> = None;
+        for point in &self.points {
+            if !point.value.is_finite() {
+                return Err(TimeSeriesError::InvalidValue {
+                    timestamp: point.timestamp,
+                });
+            }
+
+            if let Some(previous) = previous {
+                let spacing = (point.timestamp - previous.timestamp).num_milliseconds();
+                if spacing % delta != 0 {
+                    return Err(TimeSeriesError::Irregular {
+                        timestamp: point.timestamp,
+                        spacing: time_delta,
+                    });
+                }
+
+                let missing = spacing / delta - 1;
+                if missing > 0 && gaps == GapPolicy::Reject {
+                    return Err(TimeSeriesError::Gap {
+                        start: previous.timestamp,
+                        end: point.timestamp,
+                    });
+                }
+                for step in 1..=missing {
+                    values.push(match gaps {
+                        GapPolicy::Zero | GapPolicy::Reject => 0.0,
+                        GapPolicy::Interpolate => {
+                            let share = step as f64 / (missing + 1) as f64;
+                            previous.value + (point.value - previous.value) * share
+                        }
+                    });
+                }
+            }
+
+            values.push(point.value);
+            previous = Some(point);
+        }
+
+        Ok(DiscreteGraph::new(values, time_delta, first.timestamp))
+    }
+
+    /// Parses `timestamp,value` rows, optionally after a `timestamp,value` header.
+    ///
+    /// Empty lines are skipped, and both LF and CRLF line endings are accepted.
+    pub fn from_csv(input: &str) -> Result<Self, TimeSeriesError> {
+        let mut points = Vec::new();
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || (points.is_empty() && line.eq_ignore_ascii_case(CSV_HEADER)) {
+                continue;
+            }
+
+            let invalid = |message: String| TimeSeriesError::InvalidLine {
+                line: index + 1,
+                message,
+            };
+            let Some((timestamp, value)) = line.split_once(',') else {
+                return Err(invalid("Expected a timestamp and a value".to_owned()));
+            };
+            let timestamp = DateTime::parse_from_rfc3339(timestamp.trim())
+                .map_err(|error| invalid(format!("Invalid timestamp: {error}")))?
+                .with_timezone(&Utc);
+            let value = value
+                .trim()
+                .parse()
+                .map_err(|error| invalid(format!("Invalid value: {error}")))?;
+
+            points.push(TimeSeriesPoint { timestamp, value });
+        }
+
+        Ok(TimeSeries { points })
+    }
+
+    /// Writes a `timestamp,value` header and a row per point, with CRLF line endings.
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("{CSV_HEADER}\r\n");
+        for point in &self.points {
+            csv.push_str(&format!(
+                "{},{}\r\n",
+                point.timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                point.value
+            ));
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn start() -> DateTimeUtc {
+        Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    fn series(points: &[(i64, f64)]) -> TimeSeries {
+        TimeSeries {
+            points: points
+                .iter()
+                .map(|(minutes, value)| TimeSeriesPoint {
+                    timestamp: start() + Duration::minutes(*minutes),
+                    value: *value,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn csv_is_parsed_with_or_without_a_header() {
+        let with_header =
+            "timestamp,value\r\n2024-06-01T12:00:00Z,1.5\r\n2024-06-01T14:15:00+02:00,2\r\n\r\n";
+        let without_header = "2024-06-01T12:00:00Z, 1.5\n2024-06-01T12:15:00Z, 2\n";
+
+        let expected = series(&[(0, 1.5), (15, 2.0)]);
+        assert_eq!(TimeSeries::from_csv(with_header).unwrap(), expected);
+        assert_eq!(TimeSeries::from_csv(without_header).unwrap(), expected);
+    }
+
+    #[test]
+    fn invalid_csv_lines_are_reported() {
+        let error = TimeSeries::from_csv("timestamp,value\n2024-06-01T12:00:00Z,1\nyesterday,2\n")
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            TimeSeriesError::InvalidLine { line: 3, .. }
+        ));
+
+        let error = TimeSeries::from_csv("2024-06-01T12:00:00Z").unwrap_err();
+        assert!(matches!(
+            error,
+            TimeSeriesError::InvalidLine { line: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn csv_is_written_with_a_header() {
+        let csv = series(&[(0, 1.5), (60, -2.0)]).to_csv();
+
+        assert_eq!(
+            csv,
+            "timestamp,value\r\n2024-06-01T12:00:00Z,1.5\r\n2024-06-01T13:00:00Z,-2\r\n"
+        );
+    }
+
+    #[test]
+    fn spacing_must_be_uniform() {
+        let graph = series(&[(0, 1.0), (15, 2.0), (30, 3.0)])
+            .to_graph(GapPolicy::Reject)
+            .unwrap();
+        assert_eq!(graph.get_time_delta(), Duration::minutes(15));
+        assert_eq!(graph.get_values(), &vec![1.0, 2.0, 3.0]);
+
+        assert_eq!(
+            series(&[(0, 1.0), (15, 2.0), (40, 3.0)])
+                .to_graph(GapPolicy::Zero)
+                .unwrap_err(),
+            TimeSeriesError::Irregular {
+                timestamp: start() + Duration::minutes(40),
+                spacing: Duration::minutes(15)
+            }
+        );
+        assert_eq!(
+            series(&[(0, 1.0), (15, 2.0), (15, 3.0)])
+                .to_graph(GapPolicy::Zero)
+                .unwrap_err(),
+            TimeSeriesError::NotIncreasing {
+                timestamp: start() + Duration::minutes(15)
+            }
+        );
+        assert_eq!(
+            series(&[(0, 1.0)]).to_graph(GapPolicy::Zero).unwrap_err(),
+            TimeSeriesError::TooFewPoints
+        );
+    }
+
+    #[test]
+    fn gaps_are_handled_by_the_policy() {
+        let gappy = series(&[(0, 1.0), (15, 2.0), (60, 8.0)]);
+
+        assert_eq!(
+            gappy.to_graph(GapPolicy::Reject).unwrap_err(),
+            TimeSeriesError::Gap {
+                start: start() + Duration::minutes(15),
+                end: start() + Duration::minutes(60)
+            }
+        );
+        assert_eq!(
+            gappy.to_graph(GapPolicy::Zero).unwrap().get_values(),
+            &vec![1.0, 2.0, 0.0, 0.0, 8.0]
+        );
+        assert_eq!(
+            gappy.to_graph(GapPolicy::Interpolate).unwrap().get_values(),
+            &vec![1.0, 2.0, 4.0, 6.0, 8.0]
+        );
+    }
+
+    #[test]
+    fn series_with_too_many_values_are_rejected() {
+        // A year apart, but with one pair of points a millisecond apart
+        let mut series = series(&[(0, 1.0), (365 * 24 * 60, 2.0)]);
+        series.points.insert(
+            1,
+            TimeSeriesPoint {
+                timestamp: start() + Duration::milliseconds(1),
+                value: 3.0,
+            },
+        );
+
+        assert_eq!(
+            series.to_graph(GapPolicy::Zero).unwrap_err(),
+            TimeSeriesError::TooManyValues {
+                spacing: Duration::milliseconds(1)
+            }
+        );
+
+        // Spacings below a millisecond cannot be represented at all
+        series.points[1].timestamp = start() + Duration::microseconds(1);
+        assert!(matches!(
+            series.to_graph(GapPolicy::Zero).unwrap_err(),
+            TimeSeriesError::TooManyValues { .. }
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn graphs_round_trip_through_csv(
+            values in prop::collection::vec(-1e6..1e6, 2..200),
+            minutes in 1i64..120,
+            offset in 0i64..1_000_000,
+        ) {
+            let graph = DiscreteGraph::new(
+                values,
+                Duration::minutes(minutes),
+                start() + Duration::milliseconds(offset),
+            );
+
+            let csv = TimeSeries::from_graph(&graph).to_csv();
+            let parsed = TimeSeries::from_csv(&csv)
+                .unwrap()
+                .to_graph(GapPolicy::Reject)
+                .unwrap();
+
+            prop_assert_eq!(parsed.get_values(), graph.get_values());
+            prop_assert_eq!(parsed.get_time_delta(), graph.get_time_delta());
+            prop_assert_eq!(parsed.get_start_time(), graph.get_start_time());
+        }
+    }
+}
@@ -1,14 +1,20 @@
-use anyhow::{anyhow, bail, Result};
+use std::path::Path;
+
+use anyhow::{Context, Result};
 use chrono::{DateTime, Duration, Utc};
 use rand::Rng;
 
 use crate::{
     data_factory::{delete_devices, generate_devices, generate_tasks, generate_users, BASE_URL},
-    http_client::HttpClient,
+    http_client::{error_from_response, HttpClient},
 };
 use http::Request;
 use http_body_util::BodyExt;
-use protocol::{graph::DiscreteGraph, scheduling::SchedulingGlob};
+use protocol::{
+    graph::DiscreteGraph,
+    scheduling::{Algorithm, AlgorithmInfo, GetAlgorithmsResponse, SchedulingGlob},
+    time_series::{GapPolicy, TimeSeries},
+};
 use tower::{Service, ServiceExt};
 
 pub fn make_discrete_graph_from_delta(
@@ -28,6 +34,30 @@ pub fn make_discrete_graph_from_delta(
     )
 }
 
+/// Reads measured production from a `timestamp,value` CSV file or a JSON [TimeSeries] file,
+/// and moves it to start at `time_now` so it can be replayed.
+pub fn load_discrete_graph(path: &Path, time_now: DateTime<Utc>) -> Result<DiscreteGraph> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read production file '{}'", path.display()))?;
+    let is_csv = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    let time_series = if is_csv {
+        TimeSeries::from_csv(&contents)?
+    } else {
+        serde_json::from_str(&contents)?
+    };
+    let graph = time_series
+        .to_graph(GapPolicy::Interpolate)
+        .with_context(|| format!("Invalid production file '{}'", path.display()))?;
+
+    Ok(DiscreteGraph::new(
+        graph.get_values().clone(),
+        graph.get_time_delta(),
+        time_now,
+    ))
+}
+
 pub async fn compare(client: &mut HttpClient) -> Result<()> {
     let amount_of_users = 1;
     let amount_of_devices_per_user = 1;
@@ -40,22 +70,28 @@ pub async fn compare(client: &mut HttpClient) -> Result<()> {
     let runs = 100;
     let time_now = Utc::now();
     let total_duration = Duration::hours(24);
-    let mut naive_result: f64 = 0.0;
-    let mut global_result: f64 = 0.0;
-    let mut all_perm_result: f64 = 0.0;
+    let algorithms = get_algorithms(client).await?;
+    let mut results = vec![0.0; algorithms.len()];
     let auth_tokens = generate_users(amount_of_users, client).await?;
+    // Measured production replaces the random graphs when SIMULATOR_PRODUCTION_FILE is set
+    let measured_graph = std::env::var_os("SIMULATOR_PRODUCTION_FILE")
+        .map(|path| load_discrete_graph(Path::new(&path), time_now))
+        .transpose()?;
 
     for i in 0..runs {
         if i % 50 == 0 {
             println!("Round: {}", i);
         }
-        let discrete_graph = make_discrete_graph_from_delta(
-            time_now,
-            Duration::minutes(1),
-            total_duration,
-            min_available_effect,
-            max_available_effect,
-        );
+        let discrete_graph = match &measured_graph {
+            Some(graph) => graph.clone(),
+            None => make_discrete_graph_from_delta(
+                time_now,
+                Duration::minutes(1),
+                total_duration,
+                min_available_effect,
+                max_available_effect,
+            ),
+        };
 
         let device_ownership = generate_devices(
             amount_of_devices_per_user,
@@ -76,99 +112,73 @@ pub async fn compare(client: &mut HttpClient) -> Result<()> {
         )
         .await?;
 
-        let discrete_graph_naive =
-            run_scheduling_algorithm(0, discrete_graph.clone(), client).await?;
-        let discrete_graph_global =
-            run_scheduling_algorithm(1, discrete_graph.clone(), client).await?;
-        let discrete_graph_all_perm = run_scheduling_algorithm(2, discrete_graph, client).await?;
-
-        let current_naive_result: f64 = discrete_graph_naive
-            .get_values()
-            .iter()
-            .map(|&val| {
-                if val < 0.0 {
-                    val.powi(3).abs()
-                } else {
-                    val.powi(2)
-                }
-            })
-            .sum();
-
-        let current_global_result: f64 = discrete_graph_global
-            .get_values()
-            .iter()
-            .map(|&val| {
-                if val < 0.0 {
-                    val.powi(3).abs()
-                } else {
-                    val.powi(2)
-                }
-            })
-            .sum();
-
-        let current_all_perm_result: f64 = discrete_graph_all_perm
-            .get_values()
-            .iter()
-            .map(|&val| {
-                if val < 0.0 {
-                    val.powi(3).abs()
-                } else {
-                    val.powi(2)
-                }
-            })
-            .sum();
-
-        if current_naive_result < current_global_result {
-            println!("Naive was better: {}", i);
+        for (algorithm_info, result) in algorithms.iter().zip(results.iter_mut()) {
+            let scheduled_graph = run_scheduling_algorithm(
+                algorithm_info.algorithm.clone(),
+                discrete_graph.clone(),
+                client,
+            )
+            .await?;
+
+            *result += scheduled_graph
+                .get_values()
+                .iter()
+                .map(|&val| {
+                    if val < 0.0 {
+                        val.powi(3).abs()
+                    } else {
+                        val.powi(2)
+                    }
+                })
+                .sum::<f64>();
         }
-        naive_result += current_naive_result;
-        global_result += current_global_result;
-        all_perm_result += current_all_perm_result;
 
         delete_devices(device_ownership.clone(), client).await?;
     }
 
-    println!("Naive result: {}", naive_result);
-    println!("-------------------------------------------------------------");
-    println!("Global result: {}", global_result);
-    println!("-------------------------------------------------------------");
-    println!("All perm result: {}", all_perm_result);
-
-    if global_result < naive_result {
-        println!(
-            "Naive algorithm is {}% worse",
-            ((naive_result - global_result) / global_result) * 100.0
-        );
-    } else {
-        println!(
-            "Global algorithm is {}% worse",
-            ((global_result - naive_result) / naive_result) * 100.0
-        );
+    for (algorithm_info, result) in algorithms.iter().zip(&results) {
+        println!("{:?} result: {}", algorithm_info.algorithm, result);
+        println!("-------------------------------------------------------------");
     }
 
-    if all_perm_result < global_result {
+    let best_result = results.iter().copied().fold(f64::INFINITY, f64::min);
+    for (algorithm_info, result) in algorithms.iter().zip(&results) {
         println!(
-            "Global algorithm is {}% worse",
-            ((global_result - all_perm_result) / all_perm_result) * 100.0
-        );
-    } else {
-        println!(
-            "All perm algorithm is {}% worse",
-            ((all_perm_result - global_result) / global_result) * 100.0
+            "{:?} algorithm is {}% worse than the best",
+            algorithm_info.algorithm,
+            ((result - best_result) / best_result) * 100.0
         );
     }
 
     Ok(())
 }
 
+/// Fetches the algorithms the backend can run.
+async fn get_algorithms(client: &mut HttpClient) -> Result<Vec<AlgorithmInfo>> {
+    let request = Request::builder()
+        .uri(BASE_URL.to_owned() + "/scheduling/algorithms")
+        .method("GET")
+        .body(String::new())?;
+
+    let response = client.ready().await?.call(request).await?;
+
+    if !response.status().is_success() {
+        return Err(error_from_response(response).await);
+    }
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let response: GetAlgorithmsResponse = serde_json::from_slice(&body)?;
+    Ok(response.algorithms)
+}
+
 async fn run_scheduling_algorithm(
-    alg: u8,
+    algorithm: Algorithm,
     discrete_graph: DiscreteGraph,
     client: &mut HttpClient,
 ) -> Result<DiscreteGraph> {
     let body = serde_json::to_string(&SchedulingGlob {
         discrete_graph,
-        alg,
+        algorithm,
     })?;
 
     let request = Request::builder()
@@ -180,17 +190,7 @@ async fn run_scheduling_algorithm(
     let response = client.ready().await?.call(request).await?;
 
     if !response.status().is_success() {
-        let status = response.status();
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .map_err(|e| anyhow!(e))?;
-        bail!(
-            "status message: {} message: {:?}",
-            status,
-            String::from_utf8(body.to_bytes().to_vec())
-        );
+        return Err(error_from_response(response).await);
     }
 
     let body = response.into_body().collect().await.unwrap().to_bytes();
@@ -5,9 +5,9 @@ use std::collections::HashMap;
 use tracing::{event, Level};
 use uuid::Uuid;
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::Result;
 
-use crate::http_client::HttpClient;
+use crate::http_client::{error_from_response, HttpClient};
 use chrono::{DateTime, Duration, Utc};
 use http::Request;
 use http_body_util::BodyExt;
@@ -136,17 +136,7 @@ async fn generate_user(mut client: HttpClient) -> Result<AuthToken> {
     let response = client.ready().await?.call(request).await?;
 
     if !response.status().is_success() {
-        let status = response.status();
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .map_err(|e| anyhow!(e))?;
-        bail!(
-            "status message: {} message: {:?}",
-            status,
-            String::from_utf8(body.to_bytes().to_vec())
-        );
+        return Err(error_from_response(response).await);
     }
 
     let body = response.into_body().collect().await.unwrap().to_bytes();
@@ -175,17 +165,7 @@ async fn generate_device(
     let response = client.ready().await?.call(request).await?;
 
     if !response.status().is_success() {
-        let status = response.status();
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .map_err(|e| anyhow!(e))?;
-        bail!(
-            "status message: {} message: {:?}",
-            status,
-            String::from_utf8(body.to_bytes().to_vec())
-        );
+        return Err(error_from_response(response).await);
     }
 
     let body = response.into_body().collect().await.unwrap().to_bytes();
@@ -234,17 +214,7 @@ async fn generate_task(
     let response = client.ready().await?.call(request).await?;
 
     if !response.status().is_success() {
-        let status = response.status();
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .map_err(|e| anyhow!(e))?;
-        bail!(
-            "status message: {} message: {:?}",
-            status,
-            String::from_utf8(body.to_bytes().to_vec())
-        );
+        return Err(error_from_response(response).await);
     }
 
     let body = response.into_body().collect().await.unwrap().to_bytes();
@@ -264,17 +234,7 @@ async fn delete_device(auth_token: AuthToken, id: DeviceId, mut client: HttpClie
     let response = client.ready().await?.call(request).await?;
 
     if !response.status().is_success() {
-        let status = response.status();
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .map_err(|e| anyhow!(e))?;
-        bail!(
-            "status message: {} message: {:?}",
-            status,
-            String::from_utf8(body.to_bytes().to_vec())
-        );
+        return Err(error_from_response(response).await);
     }
 
     Ok(())
@@ -1,5 +1,8 @@
-use http::HeaderValue;
+use anyhow::anyhow;
+use http::{HeaderValue, Response};
+use http_body_util::BodyExt;
 use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use protocol::errors::ErrorResponse;
 use tower_http::{
     classify::{SharedClassifier, StatusInRangeAsFailures},
     decompression::Decompression,
@@ -11,3 +14,27 @@ pub(crate) type HttpClient = Trace<
     SetRequestHeader<Decompression<Client<HttpConnector, String>>, HeaderValue>,
     SharedClassifier<StatusInRangeAsFailures>,
 >;
+
+/// Turns an unsuccessful response into an error.
+///
+/// If the backend sent an [ErrorResponse] it can be retrieved with `downcast_ref`.
+pub(crate) async fn error_from_response<B>(response: Response<B>) -> anyhow::Error
+where
+    B: BodyExt,
+    B::Error: std::fmt::Display + std::fmt::Debug + Send + Sync + 'static,
+{
+    let status = response.status();
+    let body = match response.into_body().collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(e) => return anyhow!(e),
+    };
+
+    match serde_json::from_slice::<ErrorResponse>(&body) {
+        Ok(error_response) => anyhow!(error_response),
+        Err(_) => anyhow!(
+            "status message: {} message: {:?}",
+            status,
+            String::from_utf8(body.to_vec())
+        ),
+    }
+}